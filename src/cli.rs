@@ -1,29 +1,99 @@
 use std::path::PathBuf;
 
 use anyhow::Result;
-use clap::{command, Parser};
+use clap::{Parser, Subcommand};
 use figlet_rs::FIGfont;
 use owo_colors::{OwoColorize, Stream};
 
 use crate::{
     cli::checks::check_prerequisites,
     constants::{DEFAULT_OUTPUT_PATH, DEFAULT_SETTINGS_PATH},
+    fortune::{
+        fortune_data::fortune_settings::{DuplexMode, PaperName, RenderBackend},
+        fortune_export::{ExportOptions, OutputFormat, DEFAULT_DPI},
+        fortune_manifest::SlipSelector,
+    },
 };
 
+pub mod card;
 mod checks;
 pub mod windows;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 pub struct CliArgs {
-    /// Path to the output PDF.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Path to the output PDF, or to the output directory for the other formats and
+    /// `--per-slip`.
     #[arg(short, long, value_name = "FILE", default_value = DEFAULT_OUTPUT_PATH)]
     pub output: PathBuf,
 
+    /// Format of the output. Other than a single PDF, a file is written for every page.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Pdf)]
+    pub format: OutputFormat,
+
+    /// Write every slip to its own file, on the template with a single slip.
+    #[arg(long)]
+    pub per_slip: bool,
+
+    /// Resolution of the PNG files, in dots per inch.
+    #[arg(long, value_name = "DPI", default_value_t = DEFAULT_DPI, value_parser = parse_dpi)]
+    pub dpi: f32,
+
     /// Custom path to the settings YAML file.
     #[arg(short, long, value_name = "FILE", default_value = DEFAULT_SETTINGS_PATH)]
     pub config: PathBuf,
 
+    /// Backend to convert SVG to PDF, overrides the one from the settings file.
+    #[arg(short, long, value_enum)]
+    pub renderer: Option<RenderBackend>,
+
+    /// How the fronts and backs are arranged in the PDF, overrides the one from the settings
+    /// file.
+    #[arg(long, value_enum)]
+    pub duplex: Option<DuplexMode>,
+
+    /// Draw crop marks in the margin of the front pages, where the slips are cut.
+    #[arg(long)]
+    pub crop_marks: bool,
+
+    /// How far the artwork of a slip runs past where it is cut, in millimetres, overrides the
+    /// one from the settings file.
+    #[arg(long, value_name = "MM", value_parser = parse_bleed)]
+    pub bleed: Option<f32>,
+
+    /// Draw registration marks in the corners of the front pages, for print-and-cut machines.
+    #[arg(long)]
+    pub registration_marks: bool,
+
+    /// Paper to lay out the template with a single slip on, overrides the one from the settings
+    /// file.
+    #[arg(long, value_enum)]
+    pub paper: Option<PaperName>,
+
+    /// Value for a `{{key}}` placeholder in the template, overrides the settings file.
+    /// Can be given multiple times.
+    #[arg(long = "var", value_name = "KEY=VALUE", value_parser = parse_variable)]
+    pub variables: Vec<(String, String)>,
+
+    /// Seed to shuffle the fortunes with, to generate the same PDF again.
+    #[arg(long, value_name = "U64")]
+    pub seed: Option<u64>,
+
+    /// Path to write a manifest of the slips to, as JSON or CSV by its extension.
+    #[arg(short, long, value_name = "FILE")]
+    pub manifest: Option<PathBuf>,
+
+    /// Serial of the first slip, overrides the one from the settings file.
+    #[arg(long, value_name = "N")]
+    pub serial_start: Option<u64>,
+
+    /// Forget the fortunes of earlier runs in the history file before generating.
+    #[arg(long)]
+    pub reset_history: bool,
+
     /// Skip the prerequisites checks.
     #[arg(short, long)]
     pub skip_checks: bool,
@@ -33,16 +103,118 @@ pub struct CliArgs {
     pub verbose: bool,
 }
 
+/// Without a subcommand, the fortune slips are generated to a PDF.
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Check the fortune texts for fortunes that do not fit the template, duplicates,
+    /// empty fortunes and trailing whitespace.
+    Lint,
+    /// Print slips of an earlier run again, with the same texts and serials, using the manifest
+    /// of that run.
+    Reprint {
+        /// Manifest of the earlier run, written with `--manifest`.
+        #[arg(value_name = "MANIFEST")]
+        manifest: PathBuf,
+
+        /// Slips to reprint, by serial (e.g. `42`) or by page and slot (e.g. `3/2`).
+        #[arg(value_name = "SLIP", required = true)]
+        slips: Vec<SlipSelector>,
+    },
+    /// Draw a single fortune slip and show it in the terminal, without a template.
+    Draw {
+        /// Luck level of the slip, by key or name, e.g. `great_luck` or `Great Luck`.
+        #[arg(long, value_name = "LUCK")]
+        luck: Option<String>,
+
+        /// Print the slip as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Serve random slips on a local web page, with a button to shake the box of slips.
+    ///
+    /// Also serves a slip as JSON at `/api/slip` and as SVG at `/api/slip.svg`, both with an
+    /// optional `?luck=` parameter.
+    Serve {
+        /// Port to listen on, on localhost.
+        #[arg(short, long, default_value_t = 8080)]
+        port: u16,
+    },
+}
+
+impl CliArgs {
+    pub fn export_options(&self) -> ExportOptions {
+        ExportOptions {
+            format: self.format,
+            per_slip: self.per_slip,
+            dpi: self.dpi,
+        }
+    }
+
+    /// Returns the path to write to. A directory for files drops the `.pdf` extension of the
+    /// output, so the default output `fortune_slips.pdf` becomes `fortune_slips`.
+    pub fn output_path(&self) -> PathBuf {
+        let is_pdf = self.output.extension().is_some_and(|ext| ext == "pdf");
+        if !self.export_options().is_single_pdf() && is_pdf {
+            return self.output.with_extension("");
+        }
+
+        self.output.clone()
+    }
+}
+
+fn parse_variable(arg: &str) -> Result<(String, String), String> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or(format!("expected KEY=VALUE, found '{arg}'"))?;
+
+    if key.trim().is_empty() {
+        return Err(format!("the key of '{arg}' is empty"));
+    }
+
+    Ok((key.trim().to_string(), value.to_string()))
+}
+
+fn parse_dpi(arg: &str) -> Result<f32, String> {
+    let dpi: f32 = arg
+        .parse()
+        .map_err(|_| format!("expected a number, found '{arg}'"))?;
+
+    if !dpi.is_finite() || dpi <= 0.0 {
+        return Err(format!("the resolution must be above zero, not {arg}"));
+    }
+
+    Ok(dpi)
+}
+
+fn parse_bleed(arg: &str) -> Result<f32, String> {
+    let bleed: f32 = arg
+        .parse()
+        .map_err(|_| format!("expected a number, found '{arg}'"))?;
+
+    if !bleed.is_finite() || bleed < 0.0 {
+        return Err(format!("the bleed must be zero or more, not {arg}"));
+    }
+
+    Ok(bleed)
+}
+
 /// Parses the CLI arguments and returns the write options.
 pub fn execute() -> Result<CliArgs> {
     windows::enable_ansi_support();
 
     let cli = CliArgs::parse();
+
+    // Drawing a slip only prints the slip, and needs no renderer, fonts or template.
+    if matches!(cli.command, Some(Command::Draw { .. })) {
+        return Ok(cli);
+    }
+
     print_logo();
     println!("Welcome to the fortune slips generator!");
     println!();
-    if !cli.skip_checks {
-        check_prerequisites(&cli.config)?;
+    // Serving only writes SVG, which the browser renders, so no renderer is needed.
+    if !cli.skip_checks && !matches!(cli.command, Some(Command::Serve { .. })) {
+        check_prerequisites(&cli.config, cli.renderer)?;
     }
 
     Ok(cli)
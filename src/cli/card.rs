@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use owo_colors::{OwoColorize, Stream};
+use serde::Serialize;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::fortune::fortune_splitter::FortuneSlipTextRef;
+
+/// Width of the text inside the card, in terminal columns.
+const CARD_WIDTH: usize = 44;
+
+/// A drawn slip as JSON, with the fortunes ordered by category.
+#[derive(Serialize)]
+struct SlipJson<'a> {
+    header: &'a str,
+    luck_level: &'a str,
+    fortunes: BTreeMap<&'a str, &'a str>,
+}
+
+/// Returns the slip as JSON, e.g. `{"header": "大吉", "luck_level": "Great Luck", ...}`.
+pub fn format_json(slip: &FortuneSlipTextRef) -> Result<String> {
+    let slip_json = SlipJson {
+        header: slip.header,
+        luck_level: slip.luck_level,
+        fortunes: slip
+            .category_to_fortune
+            .iter()
+            .map(|(category, fortune)| (category.as_str(), fortune.as_str()))
+            .collect(),
+    };
+
+    Ok(serde_json::to_string_pretty(&slip_json)?)
+}
+
+/// Returns the slip as a card with a border, with the header and luck level on top and the
+/// fortune of every category below.
+pub fn format_card(slip: &FortuneSlipTextRef) -> String {
+    let border = |line: String| {
+        line.if_supports_color(Stream::Stdout, |text| text.dimmed())
+            .to_string()
+    };
+    let row = |text: String, text_width: usize| {
+        let padding = " ".repeat(CARD_WIDTH.saturating_sub(text_width));
+        format!(
+            "{} {text}{padding} {}",
+            border("│".into()),
+            border("│".into())
+        )
+    };
+    let centered_row = |text: &str, styled: String| {
+        let width = text.width();
+        let left = CARD_WIDTH.saturating_sub(width) / 2;
+        row(format!("{}{styled}", " ".repeat(left)), left + width)
+    };
+
+    let horizontal = "─".repeat(CARD_WIDTH + 2);
+    let mut lines = vec![
+        border(format!("╭{horizontal}╮")),
+        centered_row(
+            slip.header,
+            slip.header
+                .if_supports_color(Stream::Stdout, |text| text.bold())
+                .if_supports_color(Stream::Stdout, |text| text.red())
+                .to_string(),
+        ),
+        centered_row(
+            slip.luck_level,
+            slip.luck_level
+                .if_supports_color(Stream::Stdout, |text| text.bold())
+                .if_supports_color(Stream::Stdout, |text| text.yellow())
+                .to_string(),
+        ),
+        border(format!("├{horizontal}┤")),
+    ];
+
+    let mut categories = slip.category_to_fortune.iter().collect::<Vec<_>>();
+    categories.sort();
+
+    for (idx, (category, fortune)) in categories.into_iter().enumerate() {
+        if idx > 0 {
+            lines.push(row(String::new(), 0));
+        }
+
+        let title = capitalize(category);
+        lines.push(row(
+            title
+                .if_supports_color(Stream::Stdout, |text| text.bold())
+                .if_supports_color(Stream::Stdout, |text| text.cyan())
+                .to_string(),
+            title.width(),
+        ));
+
+        for line in wrap(fortune, CARD_WIDTH) {
+            let width = line.width();
+            lines.push(row(line, width));
+        }
+    }
+
+    lines.push(border(format!("╰{horizontal}╯")));
+    lines.join("\n")
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// Wraps the text on spaces to lines of at most `width` columns, keeping its line breaks.
+///
+/// A word that is wider than a line, such as Japanese text without spaces, is broken anywhere.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+
+        for word in paragraph.split_whitespace() {
+            let separator = usize::from(!line.is_empty());
+            if line.width() + separator + word.width() <= width {
+                if separator == 1 {
+                    line.push(' ');
+                }
+                line.push_str(word);
+                continue;
+            }
+
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+
+            for c in word.chars() {
+                if line.width() + c.width().unwrap_or_default() > width {
+                    lines.push(std::mem::take(&mut line));
+                }
+                line.push(c);
+            }
+        }
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use unicode_width::UnicodeWidthStr;
+
+    use crate::fortune::{
+        fortune_data::fortune_settings::LuckLevelStyle, fortune_splitter::FortuneSlipTextRef,
+    };
+
+    use super::{format_card, format_json, wrap, CARD_WIDTH};
+
+    #[rstest]
+    #[case("Good mood will attract people.", 12, vec!["Good mood", "will attract", "people."])]
+    #[case("First line\nSecond", 20, vec!["First line", "Second"])]
+    #[case("Unbelievable", 5, vec!["Unbel", "ievab", "le"])]
+    #[case("大吉大吉大吉", 5, vec!["大吉", "大吉", "大吉"])]
+    #[case("", 5, vec![])]
+    fn test_wrap(#[case] text: &str, #[case] width: usize, #[case] expected: Vec<&str>) {
+        assert_eq!(wrap(text, width), expected);
+    }
+
+    #[test]
+    fn test_format_card_and_json() {
+        let (love, health) = ("love".to_string(), "health".to_string());
+        let long_fortune = "Be open to meet new people, you will find love in the future.".into();
+        let short_fortune = "Rest well.".to_string();
+        let slip = FortuneSlipTextRef {
+            header: "中吉",
+            luck_level: "Good Luck",
+            style: &LuckLevelStyle::default(),
+            category_to_fortune: HashMap::from([(&love, &long_fortune), (&health, &short_fortune)]),
+            serial: None,
+        };
+
+        // Colors are only added when stdout is a terminal, so not in the tests.
+        let card = format_card(&slip);
+
+        let lines = card.lines().collect::<Vec<_>>();
+        assert!(lines.iter().all(|line| line.width() == CARD_WIDTH + 4));
+        assert_eq!(lines[1].trim_matches([' ', '│']), "中吉");
+        assert_eq!(lines[2].trim_matches([' ', '│']), "Good Luck");
+        assert_eq!(lines[4].trim_matches([' ', '│']), "Health");
+        assert_eq!(lines[7].trim_matches([' ', '│']), "Love");
+        assert_eq!(lines.len(), 11);
+
+        let json: serde_json::Value = serde_json::from_str(&format_json(&slip).unwrap()).unwrap();
+        assert_eq!(json["header"], "中吉");
+        assert_eq!(json["fortunes"]["health"], "Rest well.");
+    }
+}
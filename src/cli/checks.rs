@@ -8,7 +8,10 @@ use owo_colors::{OwoColorize, Stream};
 use rust_fontconfig::{FcFontCache, FcPattern};
 use which::which;
 
-use crate::{cli::windows, fortune::fortune_data::FortuneData};
+use crate::{
+    cli::windows,
+    fortune::fortune_data::{fortune_settings::RenderBackend, FortuneData},
+};
 #[cfg(windows)]
 use font_loader::system_fonts;
 
@@ -17,20 +20,23 @@ const REQUIRED_FONTS: [&str; 3] = ["Dosis", "Hina Mincho", "Kaushan Script"];
 /// Checks for the prerequisites to run the program.
 ///
 /// Exits the program if not all prerequisites are met.
-pub fn check_prerequisites(config_path: &Path) -> Result<()> {
+pub fn check_prerequisites(
+    config_path: &Path,
+    renderer_override: Option<RenderBackend>,
+) -> Result<()> {
     println!("Prerequisites:");
 
-    let inkscape_check = check_if_inkscape_is_installed();
+    let renderer_check = check_if_renderer_is_available(config_path, renderer_override);
     let fonts_check = check_if_fonts_are_installed();
     let fortune_settings_check = check_if_fortune_settings_are_valid(config_path);
 
-    print_prerequisite("1. Inkscape is installed", &inkscape_check);
+    print_prerequisite("1. Renderer is available", &renderer_check);
     print_prerequisite("2. Fonts are installed", &fonts_check);
     print_prerequisite("3. Fortune settings are valid", &fortune_settings_check);
 
     println!();
 
-    if inkscape_check.is_err() || fonts_check.is_err() || fortune_settings_check.is_err() {
+    if renderer_check.is_err() || fonts_check.is_err() || fortune_settings_check.is_err() {
         printdoc! {"
             For installation details, check: {url}
             (Own risk: you can skip the checks with the `--skip-checks` flag.)
@@ -62,6 +68,25 @@ fn print_prerequisite(prerequisite: &str, is_installed: &Result<()>) {
     }
 }
 
+/// Only the Inkscape backend depends on an external program.
+///
+/// Invalid settings are reported by their own check, so they fall back to the default backend.
+pub fn check_if_renderer_is_available(
+    config_path: &Path,
+    renderer_override: Option<RenderBackend>,
+) -> Result<()> {
+    let render_backend = renderer_override.unwrap_or_else(|| {
+        FortuneData::open(config_path)
+            .map(|fortune_data| fortune_data.get_settings().renderer)
+            .unwrap_or_default()
+    });
+
+    match render_backend {
+        RenderBackend::Native => Ok(()),
+        RenderBackend::Inkscape => check_if_inkscape_is_installed(),
+    }
+}
+
 pub fn check_if_inkscape_is_installed() -> Result<()> {
     which("inkscape")
         .map(|_| ())
@@ -1,3 +1,2 @@
-pub const NR_SLIPS_PER_PAGE: usize = 4;
 pub const DEFAULT_SETTINGS_PATH: &str = "fortune_settings.yaml";
 pub const DEFAULT_OUTPUT_PATH: &str = "fortune_slips.pdf";
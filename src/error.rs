@@ -8,4 +8,10 @@ pub enum Error {
     FortuneSettingsLoadFailure(String),
     #[error("Failed to generate the fortune slips.")]
     PdfGenerateFailure(String),
+    #[error("The fortune texts have {0} issues.")]
+    LintIssuesFound(usize),
+    #[error("Failed to draw a fortune slip.")]
+    DrawFailure(String),
+    #[error("Failed to serve the fortune slips.")]
+    ServeFailure(String),
 }
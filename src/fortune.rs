@@ -1,37 +1,193 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
 
-use anyhow::{bail, Result};
-use itertools::intersperse;
-use log::{debug, info};
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, FixedOffset, Local};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use log::{debug, info, warn};
+use rand::{thread_rng, Rng};
 use rayon::prelude::*;
+use sha2::{Digest, Sha256};
 use tempfile::tempdir;
 
 use crate::{
-    constants::NR_SLIPS_PER_PAGE,
-    pdf::merge_pdf,
-    svg::{svg_editor::SvgEditor, svg_file::SvgFile},
+    fortune::fortune_data::fortune_settings::{DuplexMode, Imposition, PrintMarks, SerialSettings},
+    pdf::{merge_pdf, pdf_date, DocumentInfo, PageTransform, PdfBookmark, PdfInput},
+    renderer::{self, Renderer},
+    svg::{svg_bounds::PageBounds, svg_editor::SvgEditor, svg_file::SvgFile},
 };
 
 use self::{
     fortune_data::FortuneData,
-    fortune_slip_writer::FortuneSlipWriter,
+    fortune_export::ExportOptions,
+    fortune_history::{FortuneHistory, HistoryRun},
+    fortune_linter::LintIssue,
+    fortune_manifest::{write_manifest, ManifestFormat, SlipRecord},
+    fortune_slip_writer::{
+        backside_writer::BacksideWriter, imposition::impose_template, placeholders::substitute,
+        FortuneSlipWriter,
+    },
     fortune_splitter::{FortuneSlipTextRef, FortuneSplitter},
 };
 
 pub mod fortune_data;
-mod fortune_slip_writer;
+pub mod fortune_export;
+pub mod fortune_history;
+pub mod fortune_linter;
+pub mod fortune_manifest;
+pub(crate) mod fortune_slip_writer;
 pub mod fortune_splitter;
 
 pub struct FortuneGenerator {
     fortune_data: FortuneData,
+    renderer: Box<dyn Renderer>,
+    duplex: DuplexMode,
+    print_marks: PrintMarks,
+    /// How the template with a single slip is laid out on the paper, if it is.
+    imposition: Option<Imposition>,
+    seed: u64,
+    /// Variables that take precedence over the variables in the fortune settings.
+    variables: HashMap<String, String>,
+    /// Serial of the first slip, overriding the one in the fortune settings.
+    serial_start: Option<u64>,
+    /// Where to write the manifest of the generated slips to, if anywhere.
+    manifest_path: Option<PathBuf>,
+    /// SHA-256 of the settings file, stored in the PDF metadata.
+    settings_hash: String,
 }
 
 impl FortuneGenerator {
     /// Opens the fortune settings file and returns a `FortuneGenerator` instance.
     pub fn open(settings_yaml_path: impl AsRef<Path>) -> Result<FortuneGenerator> {
-        let fortune_data = FortuneData::open(settings_yaml_path)?;
+        let fortune_data = FortuneData::open(&settings_yaml_path)?;
+        let settings_hash = format!("{:x}", Sha256::digest(fortune_data.get_settings_data()));
+        let renderer = renderer::for_backend(fortune_data.get_settings().renderer);
+        let duplex = fortune_data.get_settings().duplex;
+        let print_marks = fortune_data.get_settings().print_marks;
+        let imposition = fortune_data.get_settings().imposition;
+
+        Ok(FortuneGenerator {
+            fortune_data,
+            renderer,
+            duplex,
+            print_marks,
+            imposition,
+            seed: thread_rng().gen(),
+            variables: HashMap::new(),
+            serial_start: None,
+            manifest_path: None,
+            settings_hash,
+        })
+    }
+
+    /// Sets the seed to shuffle the fortunes with, so the same PDF can be generated again.
+    ///
+    /// By default, a random seed is picked when opening the fortune settings.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Returns the seed to shuffle the fortunes with, which is also stored in the PDF metadata.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Sets a variable for the `{{variable}}` placeholders in the template.
+    ///
+    /// It takes precedence over the `variables` in the fortune settings and the built-in
+    /// variables `date` and `seed`.
+    pub fn set_variable(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.variables.insert(name.into(), value.into());
+    }
+
+    /// Sets the serial of the first slip, e.g. to continue the numbering of an earlier batch.
+    pub fn set_serial_start(&mut self, serial_start: u64) {
+        self.serial_start = Some(serial_start);
+    }
+
+    /// Sets the path to write a manifest of the generated slips to, as JSON or CSV by its
+    /// extension.
+    ///
+    /// The manifest lists the page, slot, serial, luck level and fortunes of every slip, and the
+    /// seed.
+    pub fn set_manifest_path(&mut self, manifest_path: impl Into<PathBuf>) {
+        self.manifest_path = Some(manifest_path.into());
+    }
+
+    /// Returns the variables for the placeholders in the template, apart from the ones the
+    /// writer adds for every page and slip.
+    fn variables(&self) -> Result<HashMap<String, String>> {
+        let mut variables = HashMap::from([
+            ("date".to_string(), self.today()?),
+            ("seed".to_string(), self.seed.to_string()),
+        ]);
+        variables.extend(self.fortune_data.get_settings().variables.clone());
+        variables.extend(self.variables.clone());
+        Ok(variables)
+    }
+
+    /// Returns the day of the creation date, so `SOURCE_DATE_EPOCH` fixes it as well.
+    fn today(&self) -> Result<String> {
+        Ok(self.creation_date()?.format("%Y-%m-%d").to_string())
+    }
+
+    /// Returns the creation date of the PDF, which is now unless `SOURCE_DATE_EPOCH` is set for
+    /// a reproducible PDF.
+    fn creation_date(&self) -> Result<DateTime<FixedOffset>> {
+        match std::env::var("SOURCE_DATE_EPOCH") {
+            Ok(epoch) => {
+                let seconds = epoch.parse().map_err(|_| {
+                    anyhow!("SOURCE_DATE_EPOCH is not a number of seconds: {epoch}")
+                })?;
+                DateTime::from_timestamp(seconds, 0)
+                    .map(|date| date.fixed_offset())
+                    .ok_or(anyhow!("SOURCE_DATE_EPOCH is out of range: {epoch}"))
+            }
+            Err(_) => Ok(Local::now().fixed_offset()),
+        }
+    }
+
+    /// Replaces the renderer that converts the SVG pages to PDF.
+    ///
+    /// By default, the renderer is picked by the `renderer` key in the fortune settings.
+    pub fn set_renderer(&mut self, renderer: Box<dyn Renderer>) {
+        self.renderer = renderer;
+    }
+
+    /// Replaces how the fronts and backs are arranged in the PDF.
+    ///
+    /// By default, it is the `duplex` key in the fortune settings.
+    pub fn set_duplex(&mut self, duplex: DuplexMode) {
+        self.duplex = duplex;
+    }
+
+    /// Returns the marks to cut the slips by.
+    pub fn print_marks(&self) -> PrintMarks {
+        self.print_marks
+    }
+
+    /// Replaces the marks to cut the slips by, which are drawn on the front pages.
+    ///
+    /// By default, it is the `print_marks` key in the fortune settings.
+    pub fn set_print_marks(&mut self, print_marks: PrintMarks) {
+        self.print_marks = print_marks;
+    }
+
+    /// Returns how the template with a single slip is laid out on the paper, if it is.
+    pub fn imposition(&self) -> Option<Imposition> {
+        self.imposition
+    }
 
-        Ok(FortuneGenerator { fortune_data })
+    /// Lays out the template with a single slip on the paper for the front pages, or uses the
+    /// front template with `None`.
+    ///
+    /// By default, it is the `imposition` key in the fortune settings.
+    pub fn set_imposition(&mut self, imposition: Option<Imposition>) {
+        self.imposition = imposition;
     }
 
     /// Writes the fortunes to target PDF file.
@@ -42,73 +198,482 @@ impl FortuneGenerator {
     /// use fortune_generator::fortune::FortuneGenerator;
     ///
     /// let fortune_gen = FortuneGenerator::open("test_utils/data/fortune_settings.yaml")?;
-    /// fortune_gen.generate_to_pdf("output.pdf"); // Writes the fortunes to output.pdf
-    /// # std::fs::remove_file("output.pdf").unwrap();
+    /// # let temp_dir = tempfile::tempdir()?;
+    /// # let output_path = temp_dir.path().join("output.pdf");
+    /// fortune_gen.generate_to_pdf(&output_path)?; // Writes the fortunes to output.pdf
     /// # Ok(())
     /// ```
     ///
     pub fn generate_to_pdf(&self, pdf_path: impl AsRef<Path>) -> Result<()> {
-        if pdf_path.as_ref().is_dir() {
+        self.generate(pdf_path, &ExportOptions::default())
+    }
+
+    /// Writes the fortunes to the target PDF file, or for other export options to a file for
+    /// every page or slip in the target directory.
+    ///
+    /// The files in the directory only have the fronts, named `page_001` or by the serial of
+    /// the slip, e.g. `slip_42`.
+    ///
+    /// Example:
+    /// ```
+    /// # use anyhow::Ok;
+    /// use fortune_generator::fortune::{
+    ///     fortune_export::{ExportOptions, OutputFormat},
+    ///     FortuneGenerator,
+    /// };
+    ///
+    /// let fortune_gen = FortuneGenerator::open("test_utils/data/fortune_settings.yaml")?;
+    /// let export = ExportOptions {
+    ///     format: OutputFormat::Png,
+    ///     per_slip: true,
+    ///     dpi: 96.0,
+    /// };
+    /// # let temp_dir = tempfile::tempdir()?;
+    /// # let slips = temp_dir.path().join("slips");
+    /// fortune_gen.generate(&slips, &export)?; // Writes slips/slip_1.png, slips/slip_2.png, ...
+    /// # Ok(())
+    /// ```
+    pub fn generate(&self, output: impl AsRef<Path>, export: &ExportOptions) -> Result<()> {
+        self.check_output_paths(output.as_ref(), export)?;
+
+        let history = self.open_history()?;
+        let fortunes = self.get_random_fortunes(history.as_ref())?;
+        println!("Writing {} fortunes...", fortunes.len());
+
+        self.write_slips(&fortunes, output, export)?;
+
+        let run = HistoryRun::new(self.today()?, self.seed, &fortunes);
+        if let (Some(mut history), Some(settings)) =
+            (history, &self.fortune_data.get_settings().history)
+        {
+            history.add_run(run, settings.recent_runs);
+            history.save(&settings.file)?;
+        }
+
+        Ok(())
+    }
+
+    /// Forgets the fortunes of earlier runs by removing the history file from the settings.
+    pub fn reset_history(&self) -> Result<()> {
+        match &self.fortune_data.get_settings().history {
+            Some(settings) => FortuneHistory::reset(&settings.file),
+            None => {
+                warn!("There is no history to reset, as the settings have no history file");
+                Ok(())
+            }
+        }
+    }
+
+    /// Opens the history of earlier runs, if the settings have a history file.
+    fn open_history(&self) -> Result<Option<FortuneHistory>> {
+        self.fortune_data
+            .get_settings()
+            .history
+            .as_ref()
+            .map(|settings| FortuneHistory::open(&settings.file))
+            .transpose()
+    }
+
+    /// Writes the slips of the records of an earlier manifest to the target PDF file again, with
+    /// the same texts and serials.
+    ///
+    /// The slips are packed onto as few pages as possible, with the backside of their luck level.
+    ///
+    /// Example:
+    /// ```
+    /// # use anyhow::Ok;
+    /// use fortune_generator::fortune::FortuneGenerator;
+    /// use fortune_generator::fortune_manifest::{read_manifest, select_records, SlipSelector};
+    ///
+    /// # let temp_dir = tempfile::tempdir()?;
+    /// # let [manifest_path, output_path, reprint_path] =
+    /// #     ["manifest.json", "output.pdf", "reprint.pdf"].map(|name| temp_dir.path().join(name));
+    /// let mut fortune_gen = FortuneGenerator::open("test_utils/data/fortune_settings.yaml")?;
+    /// fortune_gen.set_manifest_path(&manifest_path);
+    /// fortune_gen.generate_to_pdf(&output_path)?;
+    ///
+    /// let selectors = ["2".parse()?, "2/1".parse()?];
+    /// let records = select_records(&read_manifest(&manifest_path)?, &selectors)?;
+    /// FortuneGenerator::open("test_utils/data/fortune_settings.yaml")?
+    ///     .reprint_to_pdf(&records, &reprint_path)?; // Writes slip 2 and 5 to reprint.pdf
+    /// # Ok(())
+    /// ```
+    pub fn reprint_to_pdf(&self, records: &[SlipRecord], pdf_path: impl AsRef<Path>) -> Result<()> {
+        self.reprint(records, pdf_path, &ExportOptions::default())
+    }
+
+    /// Writes the slips of the records again like [`FortuneGenerator::reprint_to_pdf`], with the
+    /// export options of [`FortuneGenerator::generate`].
+    pub fn reprint(
+        &self,
+        records: &[SlipRecord],
+        output: impl AsRef<Path>,
+        export: &ExportOptions,
+    ) -> Result<()> {
+        self.check_output_paths(output.as_ref(), export)?;
+
+        let mut slips = records
+            .iter()
+            .map(|record| self.slip_of_record(record))
+            .collect::<Result<Vec<_>>>()?;
+        // A stable sort, so the slips with the same backside share their pages.
+        slips.sort_by(|a, b| a.style.template_back.cmp(&b.style.template_back));
+        println!("Reprinting {} fortunes...", slips.len());
+
+        self.write_slips(&slips, output, export)
+    }
+
+    /// Returns the slip of a record of a manifest, with the style of its luck level.
+    fn slip_of_record<'s>(&'s self, record: &'s SlipRecord) -> Result<FortuneSlipTextRef<'s>> {
+        let luck_level_info = self
+            .fortune_data
+            .get_settings()
+            .luck_levels
+            .values()
+            .find(|info| info.eng == record.luck_level)
+            .ok_or(anyhow!(
+                "The luck level '{}' of slip {} is not in the fortune settings",
+                record.luck_level,
+                record.serial
+            ))?;
+
+        Ok(FortuneSlipTextRef {
+            header: &record.header,
+            luck_level: &record.luck_level,
+            style: &luck_level_info.style,
+            category_to_fortune: record.fortunes.iter().collect(),
+            serial: Some(&record.serial),
+        })
+    }
+
+    fn check_output_paths(&self, output: &Path, export: &ExportOptions) -> Result<()> {
+        if export.is_single_pdf() && output.is_dir() {
             bail!("The path to write the PDF file cannot be a directory");
         }
 
-        let fortunes = self.get_random_fortunes()?;
-        println!("Writing {} fortunes...", fortunes.len());
+        if !export.is_single_pdf() && output.is_file() {
+            bail!("The path to write the files to must be a directory, not a file");
+        }
+
+        if let Some(manifest_path) = &self.manifest_path {
+            ManifestFormat::from_path(manifest_path)?;
+        }
+
+        Ok(())
+    }
 
+    /// Writes the slips to the output as in the export options, and the manifest if it has a
+    /// path.
+    fn write_slips(
+        &self,
+        slips: &[FortuneSlipTextRef],
+        output: impl AsRef<Path>,
+        export: &ExportOptions,
+    ) -> Result<()> {
+        let output = output.as_ref();
+        let mut slip_writer = self.open_single_slip_writer()?;
+        if self.print_marks.any() && !export.per_slip {
+            slip_writer.add_print_marks(self.front_template()?, &self.print_marks)?;
+        }
+        // Every slip is a page of its own with --per-slip, so the manifest numbers its files.
+        let slips_per_page = match export.per_slip {
+            true => 1,
+            false => slip_writer.nr_slips_per_page(),
+        };
+        let pages = split_into_pages(slips, slips_per_page);
+        let records = self.slip_records(&pages);
+
+        if export.is_single_pdf() {
+            self.write_pages_to_pdf(&mut slip_writer, &pages, output)?;
+        } else if export.per_slip {
+            let mut slip_writer = self.open_drawn_slip_writer()?;
+            let names = records
+                .iter()
+                .map(|record| format!("slip_{}", record.serial))
+                .collect::<Vec<_>>();
+            self.export_pages(&mut slip_writer, &pages, &names, output, export)?;
+        } else {
+            let names = (1..=pages.len())
+                .map(|page_number| format!("page_{page_number:03}"))
+                .collect::<Vec<_>>();
+            self.export_pages(&mut slip_writer, &pages, &names, output, export)?;
+        }
+
+        if let Some(manifest_path) = &self.manifest_path {
+            write_manifest(&records, manifest_path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the pages with their backsides to the target PDF file.
+    fn write_pages_to_pdf(
+        &self,
+        slip_writer: &mut FortuneSlipWriter,
+        pages: &[&[FortuneSlipTextRef]],
+        pdf_path: &Path,
+    ) -> Result<()> {
+        let temp_dir = tempdir()?;
+
+        let front_pdf_paths = self.generate_pdf_fortunes(slip_writer, temp_dir.path(), pages)?;
+        let backside_pdf_paths = match self.duplex {
+            DuplexMode::FrontsOnly => vec![],
+            _ => {
+                let front_slips = slip_writer.slip_bounds(self.front_template()?)?;
+                self.generate_backside_pdfs(temp_dir.path(), pages, &front_slips)?
+            }
+        };
+
+        let fronts = front_pdf_paths
+            .into_iter()
+            .zip(sheet_bookmarks(pages, "front"))
+            .collect();
+        let backs = backside_pdf_paths
+            .into_iter()
+            .zip(sheet_bookmarks(pages, "back"))
+            .collect();
+        let pdf_inputs = arrange_for_duplex(fronts, backs, self.duplex)
+            .into_iter()
+            .map(|((path, bookmarks), transform)| PdfInput {
+                path,
+                transform,
+                bookmarks,
+            })
+            .collect::<Vec<_>>();
+
+        merge_pdf(&pdf_inputs, pdf_path, &self.document_info(pages)?)
+    }
+
+    /// Returns the document information of the PDF with the slips on the pages.
+    fn document_info(&self, pages: &[&[FortuneSlipTextRef]]) -> Result<DocumentInfo> {
+        let settings = &self.fortune_data.get_settings().document_info;
+        let variables = self.variables()?;
+        let fill = |text: &str| {
+            substitute(text, &variables).map_err(|e| anyhow!("In the document info: {e}"))
+        };
+
+        let slips = pages.iter().flat_map(|page| page.iter());
+        let subject = match &settings.subject {
+            Some(subject) => fill(subject)?,
+            None => format!(
+                "{} fortune slips: {}",
+                slips.clone().count(),
+                slips
+                    .map(|slip| slip.luck_level)
+                    .counts_by(|luck_level| luck_level)
+                    .into_iter()
+                    .sorted_by_key(|(luck_level, _)| self.rank_of(luck_level))
+                    .map(|(luck_level, count)| format!("{count} × {luck_level}"))
+                    .join(", ")
+            ),
+        };
+
+        let mut document_info = DocumentInfo::from([
+            (
+                "Title".to_string(),
+                fill(settings.title.as_deref().unwrap_or("Fortune slips"))?,
+            ),
+            ("Subject".to_string(), subject),
+            ("CreationDate".to_string(), pdf_date(self.creation_date()?)),
+            ("Seed".to_string(), self.seed.to_string()),
+            ("SettingsHash".to_string(), self.settings_hash.clone()),
+            (
+                "ToolVersion".to_string(),
+                format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            ),
+        ]);
+        if let Some(author) = &settings.author {
+            document_info.insert("Author".to_string(), fill(author)?);
+        }
+
+        Ok(document_info)
+    }
+
+    /// Returns the position of the luck level in the settings, which are ordered by rank.
+    fn rank_of(&self, luck_level: &str) -> usize {
+        self.fortune_data
+            .get_settings()
+            .luck_levels
+            .values()
+            .position(|info| info.eng == luck_level)
+            .unwrap_or(usize::MAX)
+    }
+
+    /// Writes every page to its own file in the directory, named by `names`, in the format of
+    /// the export options.
+    fn export_pages(
+        &self,
+        slip_writer: &mut FortuneSlipWriter,
+        pages: &[&[FortuneSlipTextRef]],
+        names: &[String],
+        dir: &Path,
+        export: &ExportOptions,
+    ) -> Result<()> {
         let temp_dir = tempdir()?;
+        fs::create_dir_all(dir)?;
+
+        let svg_files = Self::save_fortunes_to_svg(slip_writer, pages, temp_dir.path())?;
+
+        info!("Exporting {} files... (can take a while)", svg_files.len());
+        svg_files
+            .par_iter()
+            .zip(names)
+            .map(|(svg_file, name)| {
+                export.export_svg(svg_file, &dir.join(name), self.renderer.as_ref())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(())
+    }
+
+    /// Returns a record of every slip on the pages, in the order they are written.
+    fn slip_records(&self, pages: &[&[FortuneSlipTextRef]]) -> Vec<SlipRecord> {
+        let serials = self.serials();
 
-        let front_pdf_paths = self.generate_pdf_fortunes(temp_dir.path(), fortunes)?;
-        let backside_pdf_path = self.generate_backside_pdf(temp_dir.path())?;
+        pages
+            .iter()
+            .enumerate()
+            .flat_map(|(page_idx, page)| {
+                page.iter()
+                    .enumerate()
+                    .map(move |(slot_idx, slip)| (page_idx, slot_idx, slip))
+            })
+            .enumerate()
+            .map(|(slip_idx, (page_idx, slot_idx, slip))| {
+                let mut fortunes = slip
+                    .category_to_fortune
+                    .iter()
+                    .map(|(&category, &fortune)| (category.clone(), fortune.clone()))
+                    .collect::<IndexMap<_, _>>();
+                fortunes.sort_keys();
 
-        Self::intersperse_and_merge_pdfs(front_pdf_paths, backside_pdf_path, pdf_path)
+                SlipRecord {
+                    page: page_idx + 1,
+                    slot: slot_idx + 1,
+                    serial: slip
+                        .serial
+                        .map(str::to_string)
+                        .unwrap_or_else(|| serials.format(slip_idx)),
+                    luck_level: slip.luck_level.to_string(),
+                    header: slip.header.to_string(),
+                    seed: self.seed,
+                    fortunes,
+                }
+            })
+            .collect()
     }
 
-    fn get_random_fortunes(&self) -> Result<Vec<FortuneSlipTextRef>> {
-        let fortune_splitter = FortuneSplitter::new(&self.fortune_data);
+    fn get_random_fortunes<'s>(
+        &'s self,
+        history: Option<&'s FortuneHistory>,
+    ) -> Result<Vec<FortuneSlipTextRef<'s>>> {
+        let mut fortune_splitter = FortuneSplitter::with_seed(&self.fortune_data, self.seed);
+        if let Some(history) = history {
+            fortune_splitter.set_history(history);
+        }
         fortune_splitter.shuffle_and_split()
     }
 
     fn generate_pdf_fortunes(
         &self,
+        slip_writer: &mut FortuneSlipWriter,
         dir: &Path,
-        fortunes: Vec<FortuneSlipTextRef>,
+        pages: &[&[FortuneSlipTextRef]],
     ) -> Result<Vec<PathBuf>> {
-        let svg_files = self.save_fortunes_to_svg(&fortunes, dir)?;
+        let svg_files = Self::save_fortunes_to_svg(slip_writer, pages, dir)?;
         let front_pdf_paths = self.convert_svg_to_pdf_same_dir(&svg_files)?;
         Ok(front_pdf_paths)
     }
 
-    fn generate_backside_pdf(&self, dir: &Path) -> Result<PathBuf> {
-        let backside_template_path = &self.fortune_data.get_settings().template_back;
-        let backside_svg_file = SvgFile::new(backside_template_path)?;
+    /// Returns the backside PDF of every page.
+    ///
+    /// Backside templates with slots for the slips are written for every page, mirrored behind
+    /// the slips of the front. The others are the same for every page, so they are converted
+    /// only once.
+    fn generate_backside_pdfs(
+        &self,
+        dir: &Path,
+        pages: &[&[FortuneSlipTextRef]],
+        front_slips: &PageBounds<usize>,
+    ) -> Result<Vec<PathBuf>> {
+        let mut backside_writers: HashMap<&Path, Option<BacksideWriter>> = HashMap::new();
+        let mut static_pdfs: HashMap<&Path, PathBuf> = HashMap::new();
+        let mut backside_pdfs = vec![None; pages.len()];
+        let mut written_pages = vec![];
+        let mut first_slip_idx = 0;
+
+        for (page_idx, page) in pages.iter().enumerate() {
+            let template_back = self.template_back_of_page(page);
+
+            if !backside_writers.contains_key(template_back) {
+                let backside_writer = self.open_backside_writer(template_back, front_slips)?;
+                backside_writers.insert(template_back, backside_writer);
+            }
+
+            if let Some(backside_writer) = backside_writers
+                .get_mut(template_back)
+                .and_then(Option::as_mut)
+            {
+                let svg_path = dir.join(format!("backside_page_{page_idx}.svg"));
+                debug!("Writing backside #{} to '{}'", page_idx, svg_path.display());
+                backside_writer.write_page(page, first_slip_idx, page_idx + 1)?;
+                backside_writer.save_to_svg(&svg_path)?;
+                written_pages.push((page_idx, SvgFile::new(svg_path)?));
+            } else if let Some(pdf_path) = static_pdfs.get(template_back) {
+                backside_pdfs[page_idx] = Some(pdf_path.clone());
+            } else {
+                let target_path = dir.join(format!("backside_{}.pdf", static_pdfs.len()));
+                let pdf_path =
+                    SvgFile::new(template_back)?.to_pdf(target_path, self.renderer.as_ref())?;
+                static_pdfs.insert(template_back, pdf_path.clone());
+                backside_pdfs[page_idx] = Some(pdf_path);
+            }
+
+            first_slip_idx += page.len();
+        }
+
+        let (page_indices, svg_files): (Vec<_>, Vec<_>) = written_pages.into_iter().unzip();
+        let written_pdfs = self.convert_svg_to_pdf_same_dir(&svg_files)?;
+        for (page_idx, pdf_path) in page_indices.into_iter().zip(written_pdfs) {
+            backside_pdfs[page_idx] = Some(pdf_path);
+        }
 
-        let target_path = dir.join("backside.pdf");
-        backside_svg_file.to_pdf(target_path)
+        Ok(backside_pdfs.into_iter().flatten().collect())
     }
 
-    fn intersperse_and_merge_pdfs(
-        front_pdf_paths: Vec<PathBuf>,
-        backside_pdf_path: PathBuf,
-        pdf_path: impl AsRef<Path>,
-    ) -> Result<(), anyhow::Error> {
-        let mut all_pdf_paths =
-            intersperse(front_pdf_paths, backside_pdf_path.clone()).collect::<Vec<_>>();
-        all_pdf_paths.push(backside_pdf_path);
-        merge_pdf(&all_pdf_paths, pdf_path)
+    /// Opens the writer for the backside template, or returns `None` for a static backside.
+    fn open_backside_writer(
+        &self,
+        template_back: &Path,
+        front_slips: &PageBounds<usize>,
+    ) -> Result<Option<BacksideWriter>> {
+        let mut backside_writer = BacksideWriter::open(template_back, front_slips, self.duplex)?;
+
+        if let Some(backside_writer) = &mut backside_writer {
+            backside_writer.set_variables(self.variables()?);
+            backside_writer.set_serials(self.serials());
+        }
+
+        Ok(backside_writer)
+    }
+
+    /// Returns the backside template of the luck level on the page, or otherwise the one from
+    /// the settings.
+    fn template_back_of_page<'s>(&'s self, page: &[FortuneSlipTextRef<'s>]) -> &'s Path {
+        page.first()
+            .and_then(|slip| slip.style.template_back.as_deref())
+            .unwrap_or(&self.fortune_data.get_settings().template_back)
     }
 
     fn save_fortunes_to_svg(
-        &self,
-        fortune_slip_texts: &[FortuneSlipTextRef],
+        single_slip_writer: &mut FortuneSlipWriter,
+        fortune_text_all_pages: &[&[FortuneSlipTextRef]],
         svg_dir: impl AsRef<Path>,
     ) -> Result<Vec<SvgFile>> {
         let svg_dir = svg_dir.as_ref();
-        let fortune_text_all_pages = fortune_slip_texts.chunks(NR_SLIPS_PER_PAGE);
-
-        let mut single_slip_writer = self.open_single_slip_writer()?;
         let mut svg_files = vec![];
 
-        for (i, fortune_text_page) in fortune_text_all_pages.enumerate() {
+        for (i, fortune_text_page) in fortune_text_all_pages.iter().enumerate() {
             let svg_path = svg_dir.join(format!("{}.svg", i));
             debug!("Writing page #{} to '{}'", i, svg_path.display());
             single_slip_writer.write_page(fortune_text_page)?;
@@ -123,14 +688,154 @@ impl FortuneGenerator {
         info!("Converting SVG files to PDF... (can take a while)");
         let result = svg_paths
             .par_iter()
-            .map(|svg_path| svg_path.to_pdf_same_name())
+            .map(|svg_path| svg_path.to_pdf_same_name(self.renderer.as_ref()))
             .collect::<Result<Vec<_>>>();
         result
     }
 
+    /// Draws a single slip, without a template.
+    ///
+    /// The luck level can be forced by its key, e.g. `great_luck`, or its name, e.g. `Great Luck`
+    /// or `大吉`. Otherwise it is picked at random, weighted by how many slips every luck level
+    /// gets.
+    ///
+    /// Example:
+    /// ```
+    /// # use anyhow::Ok;
+    /// use fortune_generator::fortune::FortuneGenerator;
+    ///
+    /// let fortune_gen = FortuneGenerator::open("test_utils/data/fortune_settings.yaml")?;
+    /// let fortune_slip = fortune_gen.draw(Some("good luck"))?;
+    ///
+    /// assert_eq!(fortune_slip.header, "中吉");
+    /// # Ok(())
+    /// ```
+    pub fn draw(&self, luck: Option<&str>) -> Result<FortuneSlipTextRef<'_>> {
+        self.draw_with_seed(luck, self.seed)
+    }
+
+    /// Draws a single slip like [`FortuneGenerator::draw`], but shuffled with the given seed.
+    pub fn draw_with_seed(&self, luck: Option<&str>, seed: u64) -> Result<FortuneSlipTextRef<'_>> {
+        let luck_level_key = luck
+            .map(|luck| self.find_luck_level_key(luck))
+            .transpose()?;
+        FortuneSplitter::with_seed(&self.fortune_data, seed).draw(luck_level_key)
+    }
+
+    /// Returns the key of the luck level with the key or name, ignoring case.
+    fn find_luck_level_key(&self, luck: &str) -> Result<&str> {
+        let luck_levels = &self.fortune_data.get_settings().luck_levels;
+        let luck = luck.trim();
+
+        luck_levels
+            .iter()
+            .find(|(key, info)| {
+                [key.as_str(), &info.eng, &info.jap]
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(luck))
+            })
+            .map(|(key, _)| key.as_str())
+            .ok_or(anyhow!(
+                "Unknown luck level '{luck}'. Allowed: {}",
+                luck_levels.keys().join(", ")
+            ))
+    }
+
+    /// Checks the fortune texts for problems, such as fortunes that do not fit the template.
+    ///
+    /// Example:
+    /// ```
+    /// # use anyhow::Ok;
+    /// use fortune_generator::fortune::FortuneGenerator;
+    ///
+    /// let fortune_gen = FortuneGenerator::open("test_utils/data/fortune_settings.yaml")?;
+    ///
+    /// for issue in fortune_gen.lint()? {
+    ///     println!("{issue}");
+    /// }
+    /// # Ok(())
+    /// ```
+    pub fn lint(&self) -> Result<Vec<LintIssue>> {
+        // Without a template, the slip writer fails as well, which is reported as an issue.
+        let template = self
+            .front_template()
+            .unwrap_or(&self.fortune_data.get_settings().template_front);
+        fortune_linter::lint(&self.fortune_data, template, self.open_single_slip_writer())
+    }
+
     fn open_single_slip_writer(&self) -> Result<FortuneSlipWriter> {
-        let template_front = &self.fortune_data.get_settings().template_front;
-        let svg_editor = SvgEditor::open(template_front)?;
+        let settings = self.fortune_data.get_settings();
+        match &self.imposition {
+            Some(imposition) => {
+                let svg_editor = impose_template(self.front_template()?, imposition)?;
+                self.slip_writer_for(svg_editor, settings.slips_per_page)
+            }
+            None => self.open_slip_writer(&settings.template_front, settings.slips_per_page),
+        }
+    }
+
+    /// Returns the template of the front pages, which is the template with a single slip when
+    /// it is laid out on the paper.
+    fn front_template(&self) -> Result<&Path> {
+        let settings = self.fortune_data.get_settings();
+        match (&self.imposition, &settings.template_single_slip) {
+            (None, _) => Ok(&settings.template_front),
+            (Some(_), Some(template)) => Ok(template),
+            (Some(_), None) => bail!(
+                "The imposition lays out template_single_slip on the paper, but the settings \
+                 have no template_single_slip"
+            ),
+        }
+    }
+
+    /// Opens a writer for the template with a single slip, to write drawn slips one at a time.
+    ///
+    /// Falls back to the front template when the settings have no `template_single_slip`.
+    pub(crate) fn open_drawn_slip_writer(&self) -> Result<FortuneSlipWriter> {
+        let settings = self.fortune_data.get_settings();
+        let slip_writer = match &settings.template_single_slip {
+            Some(_) => self.open_single_slip_template_writer()?,
+            None => self.open_single_slip_writer()?,
+        };
+
+        if slip_writer.nr_slips_per_page() > 1 {
+            warn!(
+                "The template has {} slips, so only the first one is filled. Set \
+                 template_single_slip in the settings for a template with a single slip.",
+                slip_writer.nr_slips_per_page()
+            );
+        }
+
+        Ok(slip_writer)
+    }
+
+    /// Opens a writer for `template_single_slip`, or fails when the settings have none.
+    pub(crate) fn open_single_slip_template_writer(&self) -> Result<FortuneSlipWriter> {
+        let template = self
+            .fortune_data
+            .get_settings()
+            .template_single_slip
+            .as_ref()
+            .ok_or(anyhow!(
+                "The settings have no template_single_slip, a template with a single slip to \
+                 write the drawn slips on"
+            ))?;
+        self.open_slip_writer(template, None)
+    }
+
+    fn open_slip_writer(
+        &self,
+        template: &Path,
+        slips_per_page: Option<usize>,
+    ) -> Result<FortuneSlipWriter> {
+        self.slip_writer_for(SvgEditor::open(template)?, slips_per_page)
+    }
+
+    fn slip_writer_for(
+        &self,
+        svg_editor: SvgEditor,
+        slips_per_page: Option<usize>,
+    ) -> Result<FortuneSlipWriter> {
         let fortune_categories = self
             .fortune_data
             .get_categories()
@@ -138,21 +843,126 @@ impl FortuneGenerator {
             .cloned()
             .collect::<Vec<_>>();
 
-        FortuneSlipWriter::new(svg_editor, &fortune_categories)
+        let settings = self.fortune_data.get_settings();
+
+        let mut slip_writer =
+            FortuneSlipWriter::new(svg_editor, &fortune_categories, slips_per_page)?;
+        slip_writer.set_min_font_size(settings.min_font_size);
+        slip_writer.set_variables(self.variables()?);
+
+        slip_writer.set_serials(self.serials());
+
+        Ok(slip_writer)
+    }
+
+    /// Returns how the slips are numbered, with the start from the CLI if given.
+    fn serials(&self) -> SerialSettings {
+        let mut serials = self.fortune_data.get_settings().serials.clone();
+        if let Some(serial_start) = self.serial_start {
+            serials.start = serial_start;
+        }
+        serials
+    }
+}
+
+/// Returns the pages in the order to print them in the duplex mode, with the transform of
+/// every page.
+fn arrange_for_duplex<T>(
+    fronts: Vec<T>,
+    backs: Vec<T>,
+    duplex: DuplexMode,
+) -> Vec<(T, PageTransform)> {
+    let fronts = fronts.into_iter().map(|front| (front, PageTransform::None));
+
+    match duplex {
+        DuplexMode::LongEdge => fronts
+            .zip(backs.into_iter().map(|back| (back, PageTransform::None)))
+            .flat_map(|(front, back)| [front, back])
+            .collect(),
+        DuplexMode::ShortEdge => fronts
+            .zip(
+                backs
+                    .into_iter()
+                    .map(|back| (back, PageTransform::Rotate180)),
+            )
+            .flat_map(|(front, back)| [front, back])
+            .collect(),
+        DuplexMode::Manual => fronts
+            .chain(
+                backs
+                    .into_iter()
+                    .rev()
+                    .map(|back| (back, PageTransform::None)),
+            )
+            .collect(),
+        DuplexMode::FrontsOnly => fronts.collect(),
     }
 }
 
+/// Returns the bookmarks of every sheet, one for every luck level on it, e.g.
+/// `Great Luck – sheet 3 (front)`.
+fn sheet_bookmarks(pages: &[&[FortuneSlipTextRef]], side: &str) -> Vec<Vec<PdfBookmark>> {
+    pages
+        .iter()
+        .enumerate()
+        .map(|(page_idx, page)| {
+            page.iter()
+                .map(|slip| slip.luck_level)
+                .unique()
+                .map(|luck_level| PdfBookmark {
+                    group: luck_level.to_string(),
+                    title: format!("{luck_level} – sheet {} ({side})", page_idx + 1),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Splits the slips into pages.
+///
+/// Luck levels with their own backside template start on a new page, so every page has a single
+/// backside.
+fn split_into_pages<'s, 'a>(
+    slips: &'s [FortuneSlipTextRef<'a>],
+    nr_slips_per_page: usize,
+) -> Vec<&'s [FortuneSlipTextRef<'a>]> {
+    slips
+        .chunk_by(|a, b| a.style.template_back == b.style.template_back)
+        .flat_map(|same_backside| same_backside.chunks(nr_slips_per_page))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
+    use std::{
+        collections::HashSet,
+        fs::{self, File},
+        path::Path,
+    };
 
     use anyhow::Result;
+    use indexmap::IndexMap;
     use lopdf::Document;
     use pretty_assertions::assert_eq;
     use rstest::{fixture, rstest};
     use tempfile::tempdir;
 
-    use crate::fortune::FortuneGenerator;
+    use crate::{
+        fortune::{
+            arrange_for_duplex,
+            fortune_data::fortune_settings::{
+                DocumentInfoSettings, DuplexMode, HistorySettings, Imposition, PrintMarks,
+            },
+            fortune_export::{ExportOptions, OutputFormat},
+            fortune_history::FortuneHistory,
+            split_into_pages, FortuneGenerator, FortuneSlipTextRef, SlipRecord,
+        },
+        pdf::PageTransform,
+        renderer::MockRenderer,
+    };
+
+    const UPRIGHT: PageTransform = PageTransform::None;
+    const TURNED: PageTransform = PageTransform::Rotate180;
 
     #[fixture]
     fn fortune_generator() -> FortuneGenerator {
@@ -186,6 +996,486 @@ mod tests {
         Ok(())
     }
 
+    #[rstest]
+    fn test_generate_to_pdf_with_mock_renderer(
+        mut fortune_generator: FortuneGenerator,
+    ) -> Result<()> {
+        let temp_dir = tempdir()?;
+        let pdf_path = temp_dir.path().join("fortunes.pdf");
+        fortune_generator.set_renderer(Box::new(MockRenderer));
+        fortune_generator.generate_to_pdf(&pdf_path)?;
+
+        assert_eq!(open_pdf_and_count_pages(pdf_path)?, 4);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(DuplexMode::LongEdge, 4)]
+    #[case(DuplexMode::Manual, 4)]
+    #[case(DuplexMode::FrontsOnly, 2)]
+    fn test_generate_to_pdf_with_duplex(
+        mut fortune_generator: FortuneGenerator,
+        #[case] duplex: DuplexMode,
+        #[case] expected_pages: usize,
+    ) -> Result<()> {
+        let temp_dir = tempdir()?;
+        let pdf_path = temp_dir.path().join("fortunes.pdf");
+        fortune_generator.set_renderer(Box::new(MockRenderer));
+        fortune_generator.set_duplex(duplex);
+        fortune_generator.generate_to_pdf(&pdf_path)?;
+
+        assert_eq!(open_pdf_and_count_pages(pdf_path)?, expected_pages);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(DuplexMode::LongEdge, vec![("f1", UPRIGHT), ("b1", UPRIGHT), ("f2", UPRIGHT), ("b2", UPRIGHT)])]
+    #[case(DuplexMode::ShortEdge, vec![("f1", UPRIGHT), ("b1", TURNED), ("f2", UPRIGHT), ("b2", TURNED)])]
+    #[case(DuplexMode::Manual, vec![("f1", UPRIGHT), ("f2", UPRIGHT), ("b2", UPRIGHT), ("b1", UPRIGHT)])]
+    #[case(DuplexMode::FrontsOnly, vec![("f1", UPRIGHT), ("f2", UPRIGHT)])]
+    fn test_arrange_for_duplex(
+        #[case] duplex: DuplexMode,
+        #[case] expected: Vec<(&str, PageTransform)>,
+    ) {
+        let pages = arrange_for_duplex(vec!["f1", "f2"], vec!["b1", "b2"], duplex);
+        assert_eq!(pages, expected);
+    }
+
+    #[rstest]
+    fn test_generate_svg_pages(fortune_generator: FortuneGenerator) -> Result<()> {
+        let temp_dir = tempdir()?;
+        let dir = temp_dir.path().join("pages");
+        let export = ExportOptions {
+            format: OutputFormat::Svg,
+            ..Default::default()
+        };
+
+        fortune_generator.generate(&dir, &export)?;
+
+        assert_eq!(file_names(&dir)?, vec!["page_001.svg", "page_002.svg"]);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(None, vec!["page_001.svg"], 8)]
+    #[case(Some(2), vec!["page_001.svg", "page_002.svg", "page_003.svg"], 2)]
+    fn test_generate_with_imposition(
+        #[case] slips_per_row: Option<usize>,
+        #[case] expected_pages: Vec<&str>,
+        #[case] expected_slots: usize,
+    ) -> Result<()> {
+        let temp_dir = tempdir()?;
+        let dir = temp_dir.path().join("pages");
+        let mut fortune_generator =
+            FortuneGenerator::open("test_utils/data/fortune_settings_imposition.yaml")?;
+        let mut imposition = fortune_generator.imposition().unwrap();
+        if slips_per_row.is_some() {
+            imposition.columns = slips_per_row;
+            imposition.rows = Some(1);
+        }
+        fortune_generator.set_imposition(Some(imposition));
+        let export = ExportOptions {
+            format: OutputFormat::Svg,
+            ..Default::default()
+        };
+
+        // The 5 slips fit on a single A4 page, or on 3 pages with 2 slips each.
+        fortune_generator.generate(&dir, &export)?;
+
+        assert_eq!(file_names(&dir)?, expected_pages);
+        let page = fs::read_to_string(dir.join("page_001.svg"))?;
+        assert_eq!(page.matches("data-slip=").count(), expected_slots);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_generate_with_imposition_without_single_slip_template_expect_error(
+        mut fortune_generator: FortuneGenerator,
+    ) {
+        let temp_dir = tempdir().unwrap();
+        fortune_generator.set_imposition(Some(Imposition::default()));
+
+        let error = fortune_generator
+            .generate_to_pdf(temp_dir.path().join("slips.pdf"))
+            .unwrap_err();
+
+        assert!(error.to_string().contains("template_single_slip"));
+    }
+
+    #[rstest]
+    fn test_generate_with_print_marks_on_unmarked_template(
+        mut fortune_generator: FortuneGenerator,
+    ) -> Result<()> {
+        let temp_dir = tempdir()?;
+        fortune_generator.set_print_marks(PrintMarks {
+            crop_marks: true,
+            ..Default::default()
+        });
+        let pages = ExportOptions {
+            format: OutputFormat::Svg,
+            ..Default::default()
+        };
+        let slips = ExportOptions {
+            per_slip: true,
+            ..pages
+        };
+
+        // The slips of the test template are not marked, so there is nothing to cut along.
+        let error = fortune_generator
+            .generate(temp_dir.path().join("pages"), &pages)
+            .unwrap_err();
+        assert!(error.to_string().contains("Can't tell where to cut"));
+
+        // Slips on their own are not cut, so they get no marks.
+        fortune_generator.generate(temp_dir.path().join("slips"), &slips)?;
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_generate_png_per_slip(mut fortune_generator: FortuneGenerator) -> Result<()> {
+        let temp_dir = tempdir()?;
+        let dir = temp_dir.path().join("slips");
+        fortune_generator.set_serial_start(3);
+        let export = ExportOptions {
+            format: OutputFormat::Png,
+            per_slip: true,
+            dpi: 10.0,
+        };
+
+        fortune_generator.generate(&dir, &export)?;
+
+        let expected = (3..8).map(|serial| format!("slip_{serial}.png"));
+        assert_eq!(file_names(&dir)?, expected.collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_generate_files_to_file_path_error(fortune_generator: FortuneGenerator) {
+        let temp_file = test_utils::create_temp_file("slips.png", "");
+        let export = ExportOptions {
+            format: OutputFormat::Png,
+            ..Default::default()
+        };
+
+        assert!(fortune_generator
+            .generate(&temp_file.path, &export)
+            .is_err());
+    }
+
+    #[rstest]
+    fn test_generate_to_pdf_stores_document_info(
+        mut fortune_generator: FortuneGenerator,
+    ) -> Result<()> {
+        let temp_dir = tempdir()?;
+        let pdf_path = temp_dir.path().join("fortunes.pdf");
+        fortune_generator.set_renderer(Box::new(MockRenderer));
+        fortune_generator.set_seed(42);
+        fortune_generator.generate_to_pdf(&pdf_path)?;
+
+        let doc = Document::load(pdf_path)?;
+        let info_id = doc.trailer.get(b"Info")?.as_reference()?;
+        let info = doc.get_dictionary(info_id)?;
+        let entry = |key: &[u8]| -> Result<String> {
+            Ok(String::from_utf8_lossy(info.get(key)?.as_str()?).to_string())
+        };
+        assert_eq!(entry(b"Seed")?, "42");
+        assert_eq!(entry(b"Title")?, "Fortune slips");
+        assert_eq!(entry(b"SettingsHash")?.len(), 64);
+        assert_eq!(
+            entry(b"ToolVersion")?,
+            format!("fortune_generator {}", env!("CARGO_PKG_VERSION"))
+        );
+        assert!(entry(b"CreationDate")?.starts_with("D:"));
+        assert!(info.get(b"Author").is_err());
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_document_info_from_settings(mut fortune_generator: FortuneGenerator) -> Result<()> {
+        let settings = fortune_generator.fortune_data.get_settings_mut();
+        settings.document_info = DocumentInfoSettings {
+            title: Some("{{event_name}} omikuji".to_string()),
+            author: Some("Shrine office".to_string()),
+            subject: None,
+        };
+        fortune_generator.set_variable("event_name", "Spring Festival");
+        let slips = fortune_generator.get_random_fortunes(None)?;
+        let pages = split_into_pages(&slips, 4);
+
+        let document_info = fortune_generator.document_info(&pages)?;
+
+        assert_eq!(document_info["Title"], "Spring Festival omikuji");
+        assert_eq!(document_info["Author"], "Shrine office");
+        let good_luck = slips.iter().filter(|slip| slip.luck_level == "Good Luck");
+        let bad_luck = slips.iter().filter(|slip| slip.luck_level == "Bad Luck");
+        assert_eq!(
+            document_info["Subject"],
+            format!(
+                "5 fortune slips: {} × Good Luck, {} × Bad Luck",
+                good_luck.count(),
+                bad_luck.count()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_date_is_the_day_of_the_creation_date(
+        fortune_generator: FortuneGenerator,
+    ) -> Result<()> {
+        let variables = fortune_generator.variables()?;
+
+        assert_eq!(
+            variables["date"],
+            fortune_generator
+                .creation_date()?
+                .format("%Y-%m-%d")
+                .to_string()
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_generate_to_pdf_bookmarks_sheets_by_luck_level(
+        mut fortune_generator: FortuneGenerator,
+    ) -> Result<()> {
+        let temp_dir = tempdir()?;
+        let pdf_path = temp_dir.path().join("fortunes.pdf");
+        fortune_generator.set_renderer(Box::new(MockRenderer));
+        let slips = fortune_generator.get_random_fortunes(None)?;
+        let last_luck_level = slips.last().unwrap().luck_level.to_string();
+        fortune_generator.generate_to_pdf(&pdf_path)?;
+
+        let toc = Document::load(pdf_path)?.get_toc()?.toc;
+        let pages_of = |title: &str| {
+            toc.iter()
+                .filter(|entry| entry.title == title)
+                .map(|entry| (entry.level, entry.page))
+                .collect::<Vec<_>>()
+        };
+        // The 5th slip is alone on sheet 2, which is page 3 and 4 with long-edge duplex.
+        assert_eq!(pages_of(&last_luck_level)[0].0, 1);
+        assert_eq!(
+            pages_of(&format!("{last_luck_level} – sheet 2 (front)")),
+            vec![(2, 3)]
+        );
+        assert_eq!(
+            pages_of(&format!("{last_luck_level} – sheet 2 (back)")),
+            vec![(2, 4)]
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_split_into_pages_by_backside(mut fortune_generator: FortuneGenerator) -> Result<()> {
+        let slips = fortune_generator.get_random_fortunes(None)?;
+        assert_eq!(page_sizes(&slips), vec![4, 1]);
+
+        let template_back = Path::new("test_utils/data/fortune_template/omikuji_backside_long.svg")
+            .canonicalize()?;
+        let settings = fortune_generator.fortune_data.get_settings_mut();
+        settings.luck_levels["bad_luck"].style.template_back = Some(template_back);
+
+        let slips = fortune_generator.get_random_fortunes(None)?;
+        assert_eq!(page_sizes(&slips), vec![3, 2]);
+
+        let temp_dir = tempdir()?;
+        let pdf_path = temp_dir.path().join("fortunes.pdf");
+        fortune_generator.set_renderer(Box::new(MockRenderer));
+        fortune_generator.generate_to_pdf(&pdf_path)?;
+        assert_eq!(open_pdf_and_count_pages(pdf_path)?, 4);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_generate_to_pdf_writes_manifest(mut fortune_generator: FortuneGenerator) -> Result<()> {
+        let temp_dir = tempdir()?;
+        let pdf_path = temp_dir.path().join("fortunes.pdf");
+        let manifest_path = temp_dir.path().join("manifest.json");
+        fortune_generator.set_renderer(Box::new(MockRenderer));
+        fortune_generator.set_seed(42);
+        fortune_generator.set_serial_start(100);
+        fortune_generator.set_manifest_path(&manifest_path);
+        fortune_generator.generate_to_pdf(&pdf_path)?;
+
+        let records: Vec<SlipRecord> = serde_json::from_reader(File::open(manifest_path)?)?;
+
+        let positions = records
+            .iter()
+            .map(|record| (record.page, record.slot, record.serial.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            positions,
+            vec![
+                (1, 1, "100"),
+                (1, 2, "101"),
+                (1, 3, "102"),
+                (1, 4, "103"),
+                (2, 1, "104")
+            ]
+        );
+        assert!(records.iter().all(|record| record.seed == 42));
+        assert_eq!(records[0].luck_level, "Good Luck");
+        assert_eq!(
+            records[0].fortunes.keys().collect::<Vec<_>>(),
+            vec!["general", "health", "love"]
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_generate_to_pdf_with_unknown_manifest_format(
+        mut fortune_generator: FortuneGenerator,
+    ) -> Result<()> {
+        let temp_dir = tempdir()?;
+        let pdf_path = temp_dir.path().join("fortunes.pdf");
+        fortune_generator.set_renderer(Box::new(MockRenderer));
+        fortune_generator.set_manifest_path(temp_dir.path().join("manifest.txt"));
+
+        assert!(fortune_generator.generate_to_pdf(&pdf_path).is_err());
+        assert!(!pdf_path.exists());
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_reprint_to_pdf(mut fortune_generator: FortuneGenerator) -> Result<()> {
+        let temp_dir = tempdir()?;
+        let manifest_path = temp_dir.path().join("manifest.json");
+        fortune_generator.set_renderer(Box::new(MockRenderer));
+        fortune_generator.set_manifest_path(&manifest_path);
+        fortune_generator.generate_to_pdf(temp_dir.path().join("fortunes.pdf"))?;
+        let records: Vec<SlipRecord> = serde_json::from_reader(File::open(&manifest_path)?)?;
+
+        // The last slip is on the second page, so one page is enough for both slips.
+        let damaged = [records[4].clone(), records[1].clone()];
+        let reprint_path = temp_dir.path().join("reprint.pdf");
+        let reprint_manifest_path = temp_dir.path().join("reprint.json");
+        fortune_generator.set_manifest_path(&reprint_manifest_path);
+        fortune_generator.reprint_to_pdf(&damaged, &reprint_path)?;
+
+        assert_eq!(open_pdf_and_count_pages(reprint_path)?, 2);
+        let reprinted: Vec<SlipRecord> =
+            serde_json::from_reader(File::open(reprint_manifest_path)?)?;
+        let expected = damaged
+            .iter()
+            .enumerate()
+            .map(|(idx, record)| SlipRecord {
+                page: 1,
+                slot: idx + 1,
+                ..record.clone()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(reprinted, expected);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_reprint_to_pdf_with_unknown_luck_level(
+        fortune_generator: FortuneGenerator,
+    ) -> Result<()> {
+        let temp_dir = tempdir()?;
+        let record = SlipRecord {
+            page: 1,
+            slot: 1,
+            serial: "1".to_string(),
+            luck_level: "Unknown Luck".to_string(),
+            header: "?".to_string(),
+            seed: 42,
+            fortunes: IndexMap::new(),
+        };
+
+        let result = fortune_generator.reprint_to_pdf(&[record], temp_dir.path().join("a.pdf"));
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_generate_to_pdf_records_history(mut fortune_generator: FortuneGenerator) -> Result<()> {
+        let temp_dir = tempdir()?;
+        let history_file = temp_dir.path().join("history.json");
+        fortune_generator.fortune_data.get_settings_mut().history = Some(HistorySettings {
+            file: history_file.clone(),
+            recent_runs: 2,
+        });
+        fortune_generator.set_renderer(Box::new(MockRenderer));
+
+        for seed in [1, 2, 3] {
+            fortune_generator.set_seed(seed);
+            fortune_generator.generate_to_pdf(temp_dir.path().join("fortunes.pdf"))?;
+        }
+
+        let history = FortuneHistory::open(&history_file)?;
+        let seeds = history
+            .runs()
+            .iter()
+            .map(|run| run.seed)
+            .collect::<Vec<_>>();
+        assert_eq!(seeds, vec![2, 3]);
+        assert_eq!(history.runs()[0].fortunes["love"].len(), 5);
+
+        fortune_generator.reset_history()?;
+        assert!(!history_file.exists());
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case("bad_luck")]
+    #[case("BAD LUCK")]
+    #[case("凶")]
+    fn test_draw_with_luck(fortune_generator: FortuneGenerator, #[case] luck: &str) -> Result<()> {
+        let slip = fortune_generator.draw(Some(luck))?;
+
+        assert_eq!(slip.luck_level, "Bad Luck");
+        assert_eq!(slip.category_to_fortune.len(), 3);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_draw(mut fortune_generator: FortuneGenerator) -> Result<()> {
+        assert!(fortune_generator.draw(Some("great_luck")).is_err());
+
+        let mut luck_levels = HashSet::new();
+        for seed in 0..20 {
+            fortune_generator.set_seed(seed);
+            luck_levels.insert(fortune_generator.draw(None)?.luck_level.to_string());
+        }
+        assert_eq!(
+            luck_levels,
+            HashSet::from(["Good Luck".into(), "Bad Luck".into()])
+        );
+
+        Ok(())
+    }
+
+    fn page_sizes(slips: &[FortuneSlipTextRef]) -> Vec<usize> {
+        split_into_pages(slips, 4).iter().map(|x| x.len()).collect()
+    }
+
+    fn file_names(dir: &Path) -> Result<Vec<String>> {
+        let mut names = fs::read_dir(dir)?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect::<Result<Vec<_>>>()?;
+        names.sort();
+        Ok(names)
+    }
+
     fn open_pdf_and_count_pages(pdf_path: impl AsRef<Path>) -> Result<usize> {
         let doc = Document::load(pdf_path)?;
         let pages = doc.get_pages();
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::Result;
 
@@ -7,7 +7,7 @@ use self::{fortune_loader::FortuneDataInner, fortune_settings::FortuneSettings};
 mod fortune_loader;
 pub mod fortune_settings;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct FortuneData {
     fortune_data_inner: FortuneDataInner,
 }
@@ -38,6 +38,16 @@ impl FortuneData {
         &self.fortune_data_inner.settings
     }
 
+    /// Returns the settings file as it was read, e.g. to hash it.
+    pub(crate) fn get_settings_data(&self) -> &[u8] {
+        &self.fortune_data_inner.settings_data
+    }
+
+    #[cfg(test)]
+    pub(crate) fn get_settings_mut(&mut self) -> &mut FortuneSettings {
+        &mut self.fortune_data_inner.settings
+    }
+
     /// Returns the fortune texts for the given category and luck level.
     ///
     /// Example:
@@ -83,7 +93,7 @@ impl FortuneData {
             .collect()
     }
 
-    /// Returns a reference to the luck level keys of the fortune data.
+    /// Returns the luck level keys ordered by rank, from the best to the worst luck.
     ///
     /// Example:
     /// ```
@@ -103,4 +113,24 @@ impl FortuneData {
             .keys()
             .collect()
     }
+
+    /// Returns the path of the file the fortune texts of the given category were loaded from.
+    ///
+    /// Example:
+    /// ```
+    /// # use anyhow::Ok;
+    /// use fortune_generator::fortune_data::FortuneData;
+    ///
+    /// let fortune_data = FortuneData::open("test_utils/data/fortune_settings.yaml")?;
+    /// let file = fortune_data.get_fortune_file("health").unwrap();
+    ///
+    /// assert!(file.ends_with("fortune_text/health_fortunes.yaml"));
+    /// # Ok(())
+    /// ```
+    pub fn get_fortune_file(&self, category: &str) -> Option<&Path> {
+        self.fortune_data_inner
+            .file_per_category
+            .get(category)
+            .map(PathBuf::as_path)
+    }
 }
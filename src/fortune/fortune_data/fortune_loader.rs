@@ -2,20 +2,27 @@ use anyhow::{anyhow, Context, Result};
 use serde::Deserialize;
 use std::{
     collections::{HashMap, HashSet},
-    fs::File,
+    fs::{self, File},
     io::BufReader,
-    path::Path,
+    path::{Path, PathBuf},
 };
 
-use super::fortune_settings::FortuneSettings;
+use super::fortune_settings::{FortuneSettings, PaperSize};
 
 type FortunesPerCategory = HashMap<String, LuckToFortunes>;
 type LuckToFortunes = HashMap<String, Vec<String>>;
+type FilePerCategory = HashMap<String, PathBuf>;
 
-#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[derive(Debug, PartialEq, Deserialize)]
 pub struct FortuneDataInner {
     pub settings: FortuneSettings,
     pub fortunes_per_category: FortunesPerCategory,
+    /// The file each category was loaded from.
+    #[serde(default)]
+    pub file_per_category: FilePerCategory,
+    /// The settings file as it was read, e.g. to hash it.
+    #[serde(skip)]
+    pub settings_data: Vec<u8>,
 }
 
 #[derive(Debug, PartialEq, Eq, Deserialize)]
@@ -26,13 +33,16 @@ struct FortuneTextDataFromFile {
 }
 
 pub fn load_fortune_data(path: impl AsRef<Path>) -> Result<FortuneDataInner> {
-    let fortune_settings = load_fortune_settings_with_updated_path(&path)?;
+    let (fortune_settings, settings_data) = load_fortune_settings_with_updated_path(&path)?;
 
-    let fortunes_per_category = load_fortune_contents(&fortune_settings.fortune_content_files)?;
+    let (fortunes_per_category, file_per_category) =
+        load_fortune_contents(&fortune_settings.fortune_content_files)?;
 
     let fortune_data = FortuneDataInner {
         settings: fortune_settings,
         fortunes_per_category,
+        file_per_category,
+        settings_data,
     };
 
     error_check(&fortune_data)?;
@@ -40,13 +50,19 @@ pub fn load_fortune_data(path: impl AsRef<Path>) -> Result<FortuneDataInner> {
     Ok(fortune_data)
 }
 
-fn load_fortune_settings_with_updated_path(path: impl AsRef<Path>) -> Result<FortuneSettings> {
-    let reader = open_file_with_context(path.as_ref())?;
-    let mut fortune_settings: FortuneSettings = serde_yaml::from_reader(reader)?;
+/// Loads the settings, with their paths relative to the settings file, and returns them with the
+/// data of the file.
+fn load_fortune_settings_with_updated_path(
+    path: impl AsRef<Path>,
+) -> Result<(FortuneSettings, Vec<u8>)> {
+    let settings_data = fs::read(path.as_ref())
+        .with_context(|| format!("Could not read from file at {:?}", path.as_ref()))?;
+    let mut fortune_settings: FortuneSettings = serde_yaml::from_slice(&settings_data)?;
 
     update_settings_paths(path.as_ref(), &mut fortune_settings)?;
+    sort_luck_levels_by_rank(&mut fortune_settings);
 
-    Ok(fortune_settings)
+    Ok((fortune_settings, settings_data))
 }
 
 fn open_file_with_context(path: impl AsRef<Path>) -> Result<BufReader<File>> {
@@ -70,11 +86,52 @@ fn update_settings_paths(
     }
     fortune_settings.template_back = parent_path.join(&fortune_settings.template_back);
     fortune_settings.template_front = parent_path.join(&fortune_settings.template_front);
+    if let Some(template) = &mut fortune_settings.template_single_slip {
+        *template = parent_path.join(&template);
+    }
+    if let Some(history) = &mut fortune_settings.history {
+        history.file = parent_path.join(&history.file);
+    }
+
+    for luck_level_info in fortune_settings.luck_levels.values_mut() {
+        let style = &mut luck_level_info.style;
+        for path in [&mut style.icon, &mut style.template_back]
+            .into_iter()
+            .flatten()
+        {
+            *path = parent_path.join(&path);
+        }
+    }
     Ok(())
 }
 
-fn load_fortune_contents(fortune_paths: &[impl AsRef<Path>]) -> Result<FortunesPerCategory> {
-    fortune_paths.iter().map(open_and_flatten).collect()
+/// Orders the luck levels by their rank, which defaults to their position in the settings file.
+fn sort_luck_levels_by_rank(fortune_settings: &mut FortuneSettings) {
+    let ranks: HashMap<String, usize> = fortune_settings
+        .luck_levels
+        .iter()
+        .enumerate()
+        .map(|(position, (key, info))| (key.clone(), info.rank.unwrap_or(position)))
+        .collect();
+
+    fortune_settings
+        .luck_levels
+        .sort_by(|key_a, _, key_b, _| ranks[key_a].cmp(&ranks[key_b]));
+}
+
+fn load_fortune_contents(
+    fortune_paths: &[impl AsRef<Path>],
+) -> Result<(FortunesPerCategory, FilePerCategory)> {
+    let contents = fortune_paths
+        .iter()
+        .map(|path| {
+            let (category, fortunes) = open_and_flatten(path)?;
+            let file = (category.clone(), path.as_ref().to_path_buf());
+            Ok(((category, fortunes), file))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(contents.into_iter().unzip())
 }
 
 fn open_and_flatten(fortune_path: impl AsRef<Path>) -> Result<(String, LuckToFortunes)> {
@@ -86,6 +143,46 @@ fn open_and_flatten(fortune_path: impl AsRef<Path>) -> Result<(String, LuckToFor
 fn error_check(fortune_data: &FortuneDataInner) -> Result<()> {
     check_consistency_luck_levels(fortune_data)?;
     check_unique_keys_categories(fortune_data)?;
+    check_numbers(&fortune_data.settings)?;
+    Ok(())
+}
+
+/// Checks that the numbers in the settings are finite and in range, e.g. a font size above zero.
+fn check_numbers(settings: &FortuneSettings) -> Result<()> {
+    if let Some(min_font_size) = settings.min_font_size {
+        check_positive("min_font_size", min_font_size.into())?;
+    }
+    for (key, luck_level) in &settings.luck_levels {
+        if let Some(weight) = luck_level.weight {
+            check_not_negative(&format!("weight of luck level '{key}'"), weight)?;
+        }
+    }
+    check_not_negative("bleed", settings.print_marks.bleed.into())?;
+    if let Some(imposition) = &settings.imposition {
+        check_not_negative("gutter", imposition.gutter.into())?;
+        check_not_negative("margin", imposition.margin.into())?;
+        if let PaperSize::Custom { width, height } = imposition.paper {
+            check_positive("paper width", width.into())?;
+            check_positive("paper height", height.into())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn check_positive(name: &str, value: f64) -> Result<()> {
+    if !value.is_finite() || value <= 0.0 {
+        anyhow::bail!("The {name} in the settings must be a number above zero, not {value}");
+    }
+
+    Ok(())
+}
+
+fn check_not_negative(name: &str, value: f64) -> Result<()> {
+    if !value.is_finite() || value < 0.0 {
+        anyhow::bail!("The {name} in the settings must be a number of zero or more, not {value}");
+    }
+
     Ok(())
 }
 
@@ -126,37 +223,57 @@ fn check_unique_keys_categories(fortune_data: &FortuneDataInner) -> Result<()> {
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::{collections::HashMap, path::PathBuf};
 
     use anyhow::Result;
-    use maplit::hashmap;
+    use indexmap::indexmap;
     use pretty_assertions::assert_eq;
 
-    use crate::fortune::fortune_data::fortune_settings::{FortuneSettings, LuckLevelInfo};
+    use crate::fortune::fortune_data::fortune_settings::{
+        DocumentInfoSettings, DuplexMode, FortuneSettings, GenerationMode, Imposition,
+        LuckLevelInfo, LuckLevelStyle, PaperSize, PrintMarks, RenderBackend, SerialSettings,
+    };
+
+    use rstest::rstest;
 
-    use super::{load_fortune_data, load_fortune_settings_with_updated_path};
+    use super::{
+        check_numbers, load_fortune_data, load_fortune_settings_with_updated_path,
+        sort_luck_levels_by_rank,
+    };
 
     #[test]
     fn test_load_fortune_settings_with_updated_path() -> Result<()> {
-        let fortune_settings: FortuneSettings =
+        let (fortune_settings, settings_data) =
             load_fortune_settings_with_updated_path("test_utils/data/fortune_settings.yaml")?;
 
         let expected_settings = get_test_settings();
 
         assert_eq!(fortune_settings, expected_settings);
+        assert_eq!(
+            settings_data,
+            std::fs::read("test_utils/data/fortune_settings.yaml")?
+        );
 
         Ok(())
     }
 
     fn get_test_settings() -> FortuneSettings {
-        let luck_levels = hashmap! {
+        let luck_levels = indexmap! {
             "good_luck".to_string() => LuckLevelInfo {
                 jap: "中吉".to_string(),
                 eng: "Good Luck".to_string(),
+                count: None,
+                weight: None,
+                rank: None,
+                style: LuckLevelStyle::default(),
             },
             "bad_luck".to_string() => LuckLevelInfo {
                 jap: "凶".to_string(),
                 eng: "Bad Luck".to_string(),
+                count: None,
+                weight: None,
+                rank: None,
+                style: LuckLevelStyle::default(),
             },
         };
 
@@ -175,7 +292,20 @@ mod tests {
             luck_levels,
             fortune_content_files,
             template_front,
+            template_single_slip: None,
             template_back,
+            renderer: RenderBackend::Native,
+            duplex: DuplexMode::LongEdge,
+            print_marks: PrintMarks::default(),
+            imposition: None,
+            slips_per_page: None,
+            min_font_size: None,
+            total_slips: None,
+            generation_mode: GenerationMode::Unique,
+            variables: HashMap::new(),
+            serials: SerialSettings::default(),
+            history: None,
+            document_info: DocumentInfoSettings::default(),
         }
     }
 
@@ -208,4 +338,120 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_sort_luck_levels_by_rank() -> Result<()> {
+        let (mut fortune_settings, _) =
+            load_fortune_settings_with_updated_path("test_utils/data/fortune_settings.yaml")?;
+        fortune_settings.luck_levels["bad_luck"].rank = Some(0);
+        fortune_settings.luck_levels["good_luck"].rank = Some(1);
+
+        sort_luck_levels_by_rank(&mut fortune_settings);
+
+        let keys: Vec<_> = fortune_settings.luck_levels.keys().collect();
+        assert_eq!(keys, vec!["bad_luck", "good_luck"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_fortune_data_file_per_category() -> Result<()> {
+        let fortune_data = load_fortune_data("test_utils/data/fortune_settings.yaml")?;
+
+        assert_eq!(
+            fortune_data.file_per_category["love"],
+            PathBuf::from("test_utils/data/fortune_text/love_fortunes.yaml")
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(0.0)]
+    #[case(-1.0)]
+    #[case(f32::NAN)]
+    fn test_check_numbers_with_invalid_min_font_size_expect_error(#[case] min_font_size: f32) {
+        let mut fortune_settings = get_test_settings();
+        fortune_settings.min_font_size = Some(min_font_size);
+
+        assert!(check_numbers(&fortune_settings).is_err());
+        fortune_settings.min_font_size = Some(8.0);
+        assert!(check_numbers(&fortune_settings).is_ok());
+    }
+
+    #[rstest]
+    #[case(-1.0)]
+    #[case(f64::NAN)]
+    #[case(f64::INFINITY)]
+    fn test_check_numbers_with_invalid_weight_expect_error(#[case] weight: f64) {
+        let mut fortune_settings = get_test_settings();
+        let luck_level = fortune_settings.luck_levels.values_mut().next().unwrap();
+        luck_level.weight = Some(weight);
+
+        assert!(check_numbers(&fortune_settings).is_err());
+        fortune_settings
+            .luck_levels
+            .values_mut()
+            .next()
+            .unwrap()
+            .weight = Some(0.0);
+        assert!(check_numbers(&fortune_settings).is_ok());
+    }
+
+    #[rstest]
+    #[case::negative_gutter(-1.0, 10.0, 100.0)]
+    #[case::infinite_gutter(f32::INFINITY, 10.0, 100.0)]
+    #[case::negative_margin(0.0, -1.0, 100.0)]
+    #[case::nan_margin(0.0, f32::NAN, 100.0)]
+    #[case::zero_paper(0.0, 10.0, 0.0)]
+    #[case::negative_paper(0.0, 10.0, -100.0)]
+    #[case::nan_paper(0.0, 10.0, f32::NAN)]
+    fn test_check_numbers_with_invalid_imposition_expect_error(
+        #[case] gutter: f32,
+        #[case] margin: f32,
+        #[case] paper_width: f32,
+    ) {
+        let mut fortune_settings = get_test_settings();
+        fortune_settings.imposition = Some(Imposition {
+            paper: PaperSize::Custom {
+                width: paper_width,
+                height: 150.0,
+            },
+            gutter,
+            margin,
+            ..Imposition::default()
+        });
+
+        assert!(check_numbers(&fortune_settings).is_err());
+        fortune_settings.imposition = Some(Imposition::default());
+        assert!(check_numbers(&fortune_settings).is_ok());
+    }
+
+    #[rstest]
+    #[case(-0.5)]
+    #[case(f32::NAN)]
+    #[case(f32::INFINITY)]
+    fn test_check_numbers_with_invalid_bleed_expect_error(#[case] bleed: f32) {
+        let mut fortune_settings = get_test_settings();
+        fortune_settings.print_marks.bleed = bleed;
+
+        assert!(check_numbers(&fortune_settings).is_err());
+        fortune_settings.print_marks.bleed = 0.0;
+        assert!(check_numbers(&fortune_settings).is_ok());
+    }
+
+    #[test]
+    fn test_deserialize_variables() -> Result<()> {
+        let yaml = "luck_levels: {}\nfortune_content_files: []\ntemplate_front: front.svg\n\
+                    template_back: back.svg\nvariables:\n  event_name: Spring Festival\n  \
+                    year: 2025\n";
+
+        let fortune_settings: FortuneSettings = serde_yaml::from_str(yaml)?;
+
+        assert_eq!(fortune_settings.variables["event_name"], "Spring Festival");
+        assert_eq!(fortune_settings.variables["year"], "2025");
+        assert!(serde_yaml::from_str::<FortuneSettings>(&format!("{yaml}  list: [1]\n")).is_err());
+
+        Ok(())
+    }
 }
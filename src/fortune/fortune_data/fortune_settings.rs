@@ -1,17 +1,299 @@
 use std::{collections::HashMap, path::PathBuf};
 
-use serde::Deserialize;
+use clap::ValueEnum;
+use indexmap::IndexMap;
+use serde::{de::Error, Deserialize, Deserializer};
+use serde_yaml::Value;
 
-#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[derive(Deserialize, Debug, PartialEq, Clone)]
 pub struct FortuneSettings {
-    pub luck_levels: HashMap<String, LuckLevelInfo>,
+    /// The luck levels, ordered by rank from the best to the worst luck.
+    pub luck_levels: IndexMap<String, LuckLevelInfo>,
     pub fortune_content_files: Vec<PathBuf>,
     pub template_front: PathBuf,
+    /// Template with a single slip, to show drawn slips one by one, e.g. in the `serve`
+    /// subcommand. Defaults to `template_front`.
+    #[serde(default)]
+    pub template_single_slip: Option<PathBuf>,
     pub template_back: PathBuf,
+    #[serde(default)]
+    pub renderer: RenderBackend,
+    /// How the fronts and backs are arranged in the PDF, for printing on both sides.
+    #[serde(default)]
+    pub duplex: DuplexMode,
+    /// Marks around the slips on the front pages, to cut them.
+    #[serde(default)]
+    pub print_marks: PrintMarks,
+    /// Lays out `template_single_slip` on the paper for the front pages, instead of
+    /// `template_front`.
+    #[serde(default)]
+    pub imposition: Option<Imposition>,
+    /// Number of slips on a page, detected from the front template when not given.
+    #[serde(default)]
+    pub slips_per_page: Option<usize>,
+    /// Smallest font size a fortune may be shrunk to when it does not fit its box.
+    #[serde(default)]
+    pub min_font_size: Option<f32>,
+    /// Exact number of slips to generate, divided over the luck levels by their `count` and
+    /// `weight`. Without it, every luck level gets as many slips as its fortunes allow.
+    #[serde(default)]
+    pub total_slips: Option<usize>,
+    #[serde(default)]
+    pub generation_mode: GenerationMode,
+    /// Values for the `{{variable}}` placeholders in the templates, e.g. `event_name`.
+    #[serde(default, deserialize_with = "deserialize_variables")]
+    pub variables: HashMap<String, String>,
+    #[serde(default)]
+    pub serials: SerialSettings,
+    /// Where to keep the fortunes of earlier runs, to print other fortunes first.
+    #[serde(default)]
+    pub history: Option<HistorySettings>,
+    /// The title, author and subject stored in the PDF.
+    #[serde(default)]
+    pub document_info: DocumentInfoSettings,
+}
+
+/// The document information of the PDF, which can contain `{{variable}}` placeholders.
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+#[serde(default)]
+pub struct DocumentInfoSettings {
+    /// Defaults to `Fortune slips`.
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// Defaults to the number of slips of every luck level.
+    pub subject: Option<String>,
 }
 
+/// The history of the fortunes printed in earlier runs.
 #[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct HistorySettings {
+    /// The JSON file with the history, relative to the settings file.
+    pub file: PathBuf,
+    /// How many of the most recent runs to remember.
+    #[serde(default = "default_recent_runs")]
+    pub recent_runs: usize,
+}
+
+fn default_recent_runs() -> usize {
+    3
+}
+
+/// How the slips are numbered, continuing over the pages.
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(default)]
+pub struct SerialSettings {
+    /// Serial of the first slip.
+    pub start: u64,
+    /// Smallest number of digits, padded with zeros, e.g. `00042` for 5 digits.
+    pub digits: usize,
+    /// What the `data-qr` boxes in the template encode, e.g. `https://example.com/{{serial}}`.
+    pub qr_content: String,
+}
+
+impl Default for SerialSettings {
+    fn default() -> Self {
+        SerialSettings {
+            start: 1,
+            digits: 0,
+            qr_content: "{{serial}}".to_string(),
+        }
+    }
+}
+
+impl SerialSettings {
+    /// Returns the serial of the slip at the given index, counting from the first slip.
+    pub fn format(&self, slip_idx: usize) -> String {
+        format!(
+            "{:0digits$}",
+            self.start + slip_idx as u64,
+            digits = self.digits
+        )
+    }
+}
+
+/// Marks on the front pages to cut the slips by, with a guillotine or a cutting plotter.
+#[derive(Deserialize, Debug, Default, PartialEq, Clone, Copy)]
+#[serde(default)]
+pub struct PrintMarks {
+    /// Draws a crop mark in the margin of the page for every line a slip is cut along.
+    pub crop_marks: bool,
+    /// How far the artwork of a slip runs past where it is cut, in millimetres. The crop marks
+    /// start outside of it.
+    pub bleed: f32,
+    /// Draws squares in three corners of the page for print-and-cut machines to line up with.
+    pub registration_marks: bool,
+}
+
+impl PrintMarks {
+    /// Returns whether any marks are drawn.
+    pub fn any(&self) -> bool {
+        self.crop_marks || self.registration_marks
+    }
+}
+
+/// How the template with a single slip is laid out on the paper, in rows and columns.
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(default)]
+pub struct Imposition {
+    pub paper: PaperSize,
+    pub orientation: Orientation,
+    /// Number of slips next to each other, as many as fit when not given.
+    pub columns: Option<usize>,
+    /// Number of slips below each other, as many as fit when not given.
+    pub rows: Option<usize>,
+    /// Space between the slips, in millimetres.
+    pub gutter: f32,
+    /// Smallest space between the slips and the edges of the paper, in millimetres.
+    pub margin: f32,
+}
+
+impl Default for Imposition {
+    fn default() -> Self {
+        Imposition {
+            paper: PaperSize::default(),
+            orientation: Orientation::default(),
+            columns: None,
+            rows: None,
+            gutter: 0.0,
+            margin: 10.0,
+        }
+    }
+}
+
+/// The size of the paper, by name, e.g. `a4`, or in millimetres, e.g.
+/// `{ width: 100, height: 150 }`.
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(untagged)]
+pub enum PaperSize {
+    Named(PaperName),
+    Custom { width: f32, height: f32 },
+}
+
+impl Default for PaperSize {
+    fn default() -> Self {
+        PaperSize::Named(PaperName::default())
+    }
+}
+
+impl PaperSize {
+    /// Returns the width and height in millimetres, upright.
+    pub fn size_in_mm(&self) -> (f32, f32) {
+        match self {
+            PaperSize::Named(PaperName::A4) => (210.0, 297.0),
+            PaperSize::Named(PaperName::A3) => (297.0, 420.0),
+            PaperSize::Named(PaperName::Letter) => (215.9, 279.4),
+            PaperSize::Custom { width, height } => (*width, *height),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone, Copy, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum PaperName {
+    /// 210 × 297 mm.
+    #[default]
+    A4,
+    /// 297 × 420 mm.
+    A3,
+    /// US Letter, 8.5 × 11 in.
+    Letter,
+}
+
+/// Which way the paper is turned.
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    /// The way that fits the most slips, upright when both fit as many.
+    #[default]
+    Auto,
+    Portrait,
+    Landscape,
+}
+
+/// Reads the variables as text, so `year: 2025` does not need quotes.
+fn deserialize_variables<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, String>, D::Error> {
+    HashMap::<String, Value>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, value)| match value {
+            Value::String(text) => Ok((name, text)),
+            Value::Number(number) => Ok((name, number.to_string())),
+            Value::Bool(boolean) => Ok((name, boolean.to_string())),
+            _ => Err(D::Error::custom(format!(
+                "The variable '{name}' must be a text or a number"
+            ))),
+        })
+        .collect()
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone)]
 pub struct LuckLevelInfo {
     pub jap: String,
     pub eng: String,
+    /// Exact number of slips with this luck level.
+    #[serde(default)]
+    pub count: Option<usize>,
+    /// Share of `total_slips` with this luck level, e.g. `0.1` for 10%.
+    #[serde(default)]
+    pub weight: Option<f64>,
+    /// Position of this luck level in the output, where lower ranks come first.
+    /// Defaults to the position in the settings file.
+    #[serde(default)]
+    pub rank: Option<usize>,
+    #[serde(default)]
+    pub style: LuckLevelStyle,
+}
+
+/// How the slips of a luck level look, applied to the elements marked with `data-style` in the
+/// front template.
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+#[serde(default)]
+pub struct LuckLevelStyle {
+    /// Fill colour of the `data-style="header"` elements, e.g. `"#c0392b"`.
+    pub header_color: Option<String>,
+    /// Fill colour of the `data-style="accent"` elements.
+    pub accent_color: Option<String>,
+    /// Image or SVG file shown by the `<image data-style="icon">` elements.
+    pub icon: Option<PathBuf>,
+    /// Backside for the pages of this luck level, instead of the `template_back` of the settings.
+    pub template_back: Option<PathBuf>,
+}
+
+/// The backend that converts the SVG pages to PDF.
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone, Copy, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderBackend {
+    /// Render in-process, without any external programs.
+    #[default]
+    Native,
+    /// Render by calling the `inkscape` binary.
+    Inkscape,
+}
+
+/// How the fronts and backs are arranged in the PDF, for printing on both sides.
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone, Copy, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplexMode {
+    /// Every front is followed by its back, for printers that flip on the long edge.
+    #[default]
+    LongEdge,
+    /// Every front is followed by its back rotated 180°, for printers that flip on the short
+    /// edge.
+    ShortEdge,
+    /// All fronts, then all backs in reverse order, to turn the printed stack over by hand.
+    Manual,
+    /// Only the fronts, without backs.
+    FrontsOnly,
+}
+
+/// Whether a fortune may be printed on more than one slip.
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum GenerationMode {
+    /// Every fortune is used at most once.
+    #[default]
+    Unique,
+    /// Fortunes are reused to reach `total_slips`, but no two slips have the exact same fortunes.
+    Reuse,
 }
@@ -0,0 +1,138 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+
+use crate::{renderer::Renderer, svg::svg_file::SvgFile};
+
+/// Resolution of the PNG files, in dots per inch.
+pub const DEFAULT_DPI: f32 = 300.0;
+
+/// The format of the generated files.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    /// A PDF, by default a single PDF with the backsides to print.
+    #[default]
+    Pdf,
+    /// The SVG pages as written on the template.
+    Svg,
+    /// The pages rendered to images.
+    Png,
+}
+
+impl OutputFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Svg => "svg",
+            OutputFormat::Png => "png",
+        }
+    }
+}
+
+/// How the slips are written: as a single PDF to print, or as a file for every page or slip to
+/// share or show on a screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportOptions {
+    pub format: OutputFormat,
+    /// Writes every slip to its own file, on the template with a single slip.
+    pub per_slip: bool,
+    /// Resolution of the PNG files, in dots per inch.
+    pub dpi: f32,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            format: OutputFormat::default(),
+            per_slip: false,
+            dpi: DEFAULT_DPI,
+        }
+    }
+}
+
+impl ExportOptions {
+    /// Returns whether the slips are written to a single PDF with the backsides, instead of
+    /// to a directory with a file for every page or slip.
+    pub fn is_single_pdf(&self) -> bool {
+        self.format == OutputFormat::Pdf && !self.per_slip
+    }
+
+    /// Writes the SVG file to `path` with the extension of the format, converting it if needed.
+    pub(crate) fn export_svg(
+        &self,
+        svg_file: &SvgFile,
+        path: &Path,
+        renderer: &dyn Renderer,
+    ) -> Result<PathBuf> {
+        let path = path.with_extension(self.format.extension());
+
+        match self.format {
+            OutputFormat::Pdf => svg_file.to_pdf(path, renderer),
+            OutputFormat::Png => svg_file.to_png(path, self.dpi),
+            OutputFormat::Svg => {
+                fs::copy(&svg_file.path, &path)
+                    .with_context(|| format!("Cannot save to {:?}", path))?;
+                Ok(path)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use crate::{renderer::MockRenderer, svg::svg_file::SvgFile};
+
+    use super::{ExportOptions, OutputFormat};
+
+    const SVG_EXAMPLE: &str = "<svg height='100' width='100' xmlns='http://www.w3.org/2000/svg'>
+                                 <circle cx='50' cy='50' r='40' />
+                               </svg>";
+
+    #[rstest]
+    #[case(OutputFormat::Pdf, false, true)]
+    #[case(OutputFormat::Pdf, true, false)]
+    #[case(OutputFormat::Png, false, false)]
+    fn test_is_single_pdf(
+        #[case] format: OutputFormat,
+        #[case] per_slip: bool,
+        #[case] expected: bool,
+    ) {
+        let options = ExportOptions {
+            format,
+            per_slip,
+            ..Default::default()
+        };
+        assert_eq!(options.is_single_pdf(), expected);
+    }
+
+    #[rstest]
+    #[case(OutputFormat::Pdf, "slip_1.pdf")]
+    #[case(OutputFormat::Svg, "slip_1.svg")]
+    #[case(OutputFormat::Png, "slip_1.png")]
+    fn test_export_svg(#[case] format: OutputFormat, #[case] expected_name: &str) {
+        let temp_file = test_utils::create_temp_file("temp.svg", SVG_EXAMPLE);
+        let svg_file = SvgFile::new(&temp_file.path).unwrap();
+        let options = ExportOptions {
+            format,
+            ..Default::default()
+        };
+
+        let path = options
+            .export_svg(
+                &svg_file,
+                &temp_file.dir.path().join("slip_1"),
+                &MockRenderer,
+            )
+            .unwrap();
+
+        assert_eq!(path, temp_file.dir.path().join(expected_name));
+        assert!(path.exists());
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::fortune_splitter::FortuneSlipTextRef;
+
+/// The fortunes printed in the most recent runs, oldest run first.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+pub struct FortuneHistory {
+    runs: Vec<HistoryRun>,
+}
+
+/// The fortunes printed in a single run.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct HistoryRun {
+    pub date: String,
+    pub seed: u64,
+    /// The printed fortunes of every category.
+    pub fortunes: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl HistoryRun {
+    /// Creates the run that printed the slips.
+    pub fn new(date: impl Into<String>, seed: u64, slips: &[FortuneSlipTextRef]) -> Self {
+        let mut fortunes: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+
+        for slip in slips {
+            for (&category, &fortune) in &slip.category_to_fortune {
+                fortunes
+                    .entry(category.clone())
+                    .or_default()
+                    .insert(fortune.clone());
+            }
+        }
+
+        HistoryRun {
+            date: date.into(),
+            seed,
+            fortunes,
+        }
+    }
+}
+
+impl FortuneHistory {
+    /// Opens the history file, or returns an empty history when the file does not exist yet.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(FortuneHistory::default());
+        }
+
+        let file = File::open(path)
+            .with_context(|| format!("Could not open the history '{}'", path.display()))?;
+        serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Could not read the history '{}'", path.display()))
+    }
+
+    /// Writes the history to the file, replacing the file if it exists.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .with_context(|| format!("Could not create the history '{}'", path.display()))?;
+        let mut writer = BufWriter::new(file);
+
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Removes the history file, if it exists.
+    pub fn reset(path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if path.exists() {
+            fs::remove_file(path)
+                .with_context(|| format!("Could not remove the history '{}'", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Adds a run, and forgets the oldest runs to keep only the `recent_runs` most recent ones.
+    pub fn add_run(&mut self, run: HistoryRun, recent_runs: usize) {
+        self.runs.push(run);
+        let nr_forgotten = self.runs.len().saturating_sub(recent_runs);
+        self.runs.drain(..nr_forgotten);
+    }
+
+    /// Returns how many runs ago the fortune was last printed, where 0 is the most recent run,
+    /// or `None` if it was not printed in any of the runs.
+    pub fn runs_since_printed(&self, category: &str, fortune: &str) -> Option<usize> {
+        self.runs.iter().rev().position(|run| {
+            run.fortunes
+                .get(category)
+                .is_some_and(|fortunes| fortunes.contains(fortune))
+        })
+    }
+
+    /// Returns the remembered runs, oldest run first.
+    pub fn runs(&self) -> &[HistoryRun] {
+        &self.runs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::{FortuneHistory, HistoryRun};
+
+    fn run(seed: u64, love: &[&str]) -> HistoryRun {
+        HistoryRun {
+            date: "2025-04-05".to_string(),
+            seed,
+            fortunes: BTreeMap::from([(
+                "love".to_string(),
+                love.iter().map(|x| x.to_string()).collect::<BTreeSet<_>>(),
+            )]),
+        }
+    }
+
+    #[test]
+    fn test_runs_since_printed() {
+        let mut history = FortuneHistory::default();
+        history.add_run(run(1, &["a", "b"]), 3);
+        history.add_run(run(2, &["b"]), 3);
+
+        assert_eq!(history.runs_since_printed("love", "b"), Some(0));
+        assert_eq!(history.runs_since_printed("love", "a"), Some(1));
+        assert_eq!(history.runs_since_printed("love", "c"), None);
+        assert_eq!(history.runs_since_printed("health", "a"), None);
+    }
+
+    #[test]
+    fn test_add_run_forgets_old_runs() {
+        let mut history = FortuneHistory::default();
+
+        for seed in 0..5 {
+            history.add_run(run(seed, &[]), 2);
+        }
+
+        let seeds = history
+            .runs()
+            .iter()
+            .map(|run| run.seed)
+            .collect::<Vec<_>>();
+        assert_eq!(seeds, vec![3, 4]);
+    }
+
+    #[test]
+    fn test_save_open_and_reset() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let path = temp_dir.path().join("history.json");
+        assert_eq!(FortuneHistory::open(&path)?, FortuneHistory::default());
+
+        let mut history = FortuneHistory::default();
+        history.add_run(run(42, &["a"]), 3);
+        history.save(&path)?;
+        assert_eq!(FortuneHistory::open(&path)?, history);
+
+        FortuneHistory::reset(&path)?;
+        assert!(!path.exists());
+        FortuneHistory::reset(&path)?;
+
+        Ok(())
+    }
+}
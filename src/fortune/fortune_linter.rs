@@ -0,0 +1,437 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+use super::{fortune_data::FortuneData, fortune_slip_writer::FortuneSlipWriter};
+
+/// A problem with a single fortune text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintIssue {
+    pub location: Location,
+    pub category: String,
+    pub luck_level: String,
+    pub fortune: String,
+    pub kind: LintKind,
+}
+
+/// Where a fortune text is written in its fortune text file.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub file: PathBuf,
+    /// The line number, starting at 1, if the fortune could be found in the file.
+    pub line: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LintKind {
+    /// The fortune does not fit its text element in the template, even at the minimum font size.
+    Overflow,
+    /// The fortune is the same as an earlier fortune.
+    Duplicate(Location),
+    Empty,
+    TrailingWhitespace,
+    /// The fortunes of the category could not be measured against the template, for the reason
+    /// given. Reported once per category, at its first fortune.
+    Unchecked(String),
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}", self.file.display(), line),
+            None => write!(f, "{}", self.file.display()),
+        }
+    }
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match &self.kind {
+            LintKind::Overflow => "does not fit its text element in the template".to_string(),
+            LintKind::Duplicate(first) => format!("duplicate of the fortune at {first}"),
+            LintKind::Empty => "empty fortune".to_string(),
+            LintKind::TrailingWhitespace => "trailing whitespace".to_string(),
+            LintKind::Unchecked(reason) => {
+                format!("can't be checked against the template: {reason}")
+            }
+        };
+
+        write!(
+            f,
+            "{}: {} ({}): {}: {:?}",
+            self.location, self.category, self.luck_level, message, self.fortune
+        )
+    }
+}
+
+/// A fortune text together with where it comes from.
+struct FortuneEntry<'a> {
+    location: Location,
+    category: &'a str,
+    luck_level: &'a str,
+    fortune: &'a str,
+}
+
+/// Checks every fortune text for problems, ordered by file and line.
+///
+/// Every fortune is measured against its text element in the `template`, opened by
+/// `slip_writer`. When the template can't be opened or measured, that is reported as an issue
+/// instead.
+pub fn lint(
+    fortune_data: &FortuneData,
+    template: &Path,
+    slip_writer: Result<FortuneSlipWriter>,
+) -> Result<Vec<LintIssue>> {
+    let entries = fortune_entries(fortune_data)?;
+    let mut first_locations: HashMap<&str, &Location> = HashMap::new();
+    let mut unchecked_categories = HashSet::new();
+    let mut issues = vec![];
+
+    for entry in &entries {
+        let mut add_issue = |kind| {
+            issues.push(LintIssue {
+                location: entry.location.clone(),
+                category: entry.category.to_string(),
+                luck_level: entry.luck_level.to_string(),
+                fortune: entry.fortune.to_string(),
+                kind,
+            })
+        };
+
+        if entry.fortune.trim().is_empty() {
+            add_issue(LintKind::Empty);
+            continue;
+        }
+
+        if entry.fortune.trim_end() != entry.fortune {
+            add_issue(LintKind::TrailingWhitespace);
+        }
+
+        match first_locations.get(entry.fortune) {
+            Some(&first) => add_issue(LintKind::Duplicate(first.clone())),
+            None => {
+                first_locations.insert(entry.fortune, &entry.location);
+            }
+        }
+
+        if unchecked_categories.contains(entry.category) {
+            continue;
+        }
+        let fits = slip_writer
+            .as_ref()
+            .map_err(|e| anyhow!("{e:#}"))
+            .and_then(|slip_writer| slip_writer.fits(entry.category, entry.fortune, template));
+        match fits {
+            Ok(true) => {}
+            Ok(false) => add_issue(LintKind::Overflow),
+            Err(e) => {
+                add_issue(LintKind::Unchecked(format!("{e:#}")));
+                unchecked_categories.insert(entry.category);
+            }
+        }
+    }
+
+    Ok(issues)
+}
+
+fn fortune_entries(fortune_data: &FortuneData) -> Result<Vec<FortuneEntry<'_>>> {
+    let mut entries = vec![];
+
+    for category in fortune_data.get_categories() {
+        let file = fortune_data.get_fortune_file(category).ok_or(anyhow!(
+            "No fortune text file found for category: {category}"
+        ))?;
+        // Without the text, the fortunes are still checked, only without their line numbers.
+        let content = fs::read_to_string(file).unwrap_or_default();
+
+        for luck_level in fortune_data.get_luck_level_keys() {
+            let fortunes = fortune_data
+                .get_fortune_text(category, luck_level)
+                .unwrap_or_default();
+
+            for (idx, fortune) in fortunes.into_iter().enumerate() {
+                entries.push(FortuneEntry {
+                    location: Location {
+                        file: file.to_path_buf(),
+                        line: fortune_line(&content, luck_level, idx),
+                    },
+                    category,
+                    luck_level,
+                    fortune,
+                });
+            }
+        }
+    }
+
+    entries.sort_by(|a, b| a.location.cmp(&b.location));
+    Ok(entries)
+}
+
+/// Returns the line number of a fortune in the text of a fortune text file, by its luck level and
+/// index.
+///
+/// serde_yaml only reports where a value is through an error, so the fortune is looked up with a
+/// visitor that fails on it on purpose.
+fn fortune_line(content: &str, luck_level: &str, idx: usize) -> Option<usize> {
+    let path = [
+        PathKey::Key("fortunes"),
+        PathKey::Key(luck_level),
+        PathKey::Index(idx),
+    ];
+    let error = PathSeed(&path)
+        .deserialize(serde_yaml::Deserializer::from_str(content))
+        .err()?;
+
+    if !error.to_string().contains(FOUND_MARKER) {
+        return None;
+    }
+    error.location().map(|location| location.line())
+}
+
+/// The message of the error raised at the value that was looked up.
+const FOUND_MARKER: &str = "the value that is looked up";
+
+/// A step towards a value in a YAML document.
+enum PathKey<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+/// Walks along a path of keys and indices, skipping everything else, and fails at its end.
+struct PathSeed<'a>(&'a [PathKey<'a>]);
+
+impl<'de> DeserializeSeed<'de> for PathSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        match self.0.first() {
+            None => deserializer.deserialize_any(FoundVisitor),
+            Some(PathKey::Key(_)) => deserializer.deserialize_map(self),
+            Some(PathKey::Index(_)) => deserializer.deserialize_seq(self),
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for PathSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a fortune text file")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let Some((PathKey::Key(wanted), rest)) = self.0.split_first() else {
+            return Ok(());
+        };
+        while let Some(key) = map.next_key::<String>()? {
+            if key == *wanted {
+                return map.next_value_seed(PathSeed(rest));
+            }
+            map.next_value::<IgnoredAny>()?;
+        }
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let Some((PathKey::Index(wanted), rest)) = self.0.split_first() else {
+            return Ok(());
+        };
+        for _ in 0..*wanted {
+            if seq.next_element::<IgnoredAny>()?.is_none() {
+                return Ok(());
+            }
+        }
+        seq.next_element_seed(PathSeed(rest)).map(|_| ())
+    }
+}
+
+/// Fails at any value, so that the error tells where the value is.
+struct FoundVisitor;
+
+impl<'de> Visitor<'de> for FoundVisitor {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{FOUND_MARKER}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use tempfile::{tempdir, TempDir};
+
+    use crate::FortuneGenerator;
+
+    use super::{fortune_line, LintKind, Location};
+
+    const TEMPLATE_PATH: &str = "test_utils/data/fortune_template/omikuji_frontside_test.svg";
+
+    /// Writes fortune data with the given `love` fortunes, next to fine fortunes for the other
+    /// categories of the test template.
+    fn write_fortune_data(love_fortunes: &str) -> TempDir {
+        let temp_dir = tempdir().unwrap();
+        let template = Path::new(TEMPLATE_PATH).canonicalize().unwrap();
+
+        let settings = format!(
+            "luck_levels:\n  \
+               good_luck:\n    jap: 中吉\n    eng: Good Luck\n\
+             fortune_content_files:\n  - general.yaml\n  - health.yaml\n  - love.yaml\n\
+             template_front: {0}\n\
+             template_back: {0}\n",
+            template.display()
+        );
+        fs::write(temp_dir.path().join("settings.yaml"), settings).unwrap();
+
+        for (category, fortunes) in [
+            ("general", "    - Luck is on your side."),
+            ("health", "    - Sleep well."),
+            ("love", love_fortunes),
+        ] {
+            let content = format!("fortune_key: {category}\nfortunes:\n  good_luck:\n{fortunes}\n");
+            fs::write(temp_dir.path().join(format!("{category}.yaml")), content).unwrap();
+        }
+
+        temp_dir
+    }
+
+    #[rstest]
+    #[case::block("good_luck", 0, Some(5))]
+    #[case::after_a_comment("good_luck", 1, Some(7))]
+    #[case::block_scalar("bad_luck", 0, Some(10))]
+    #[case::after_a_block_scalar("bad_luck", 1, Some(13))]
+    #[case::flow_sequence("great_luck", 1, Some(15))]
+    #[case::past_the_end("good_luck", 2, None)]
+    #[case::unknown_luck_level("no_luck", 0, None)]
+    fn test_fortune_line(
+        #[case] luck_level: &str,
+        #[case] idx: usize,
+        #[case] expected: Option<usize>,
+    ) {
+        let content = "---\nfortune_key: love\nfortunes:\n  good_luck:\n    - One\n    \
+                       # - Not a fortune\n    - Two\n\n  bad_luck:\n    - |\n      Three\n      \
+                       lines\n    - Four\n  great_luck:\n    [Five, Six]\n";
+
+        assert_eq!(fortune_line(content, luck_level, idx), expected);
+    }
+
+    #[test]
+    fn test_lint_test_data_has_no_issues() -> Result<()> {
+        let fortune_gen = FortuneGenerator::open("test_utils/data/fortune_settings.yaml")?;
+
+        assert_eq!(fortune_gen.lint()?, vec![]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_finds_issues() -> Result<()> {
+        let temp_dir = write_fortune_data(
+            "    - Confess your love.\n    - \"\"\n    - \"Be patient. \"\n    - Confess your love.",
+        );
+        let fortune_gen = FortuneGenerator::open(temp_dir.path().join("settings.yaml"))?;
+
+        let issues = fortune_gen.lint()?;
+        let love_file = temp_dir.path().join("love.yaml");
+
+        let kinds = issues
+            .iter()
+            .map(|issue| (issue.location.line, issue.kind.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                (Some(5), LintKind::Empty),
+                (Some(6), LintKind::TrailingWhitespace),
+                (
+                    Some(7),
+                    LintKind::Duplicate(Location {
+                        file: love_file.clone(),
+                        line: Some(4),
+                    })
+                ),
+            ]
+        );
+        assert!(issues.iter().all(|issue| issue.location.file == love_file));
+        assert_eq!(
+            issues[2].to_string(),
+            format!(
+                "{0}:7: love (good_luck): duplicate of the fortune at {0}:4: \"Confess your love.\"",
+                love_file.display()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_finds_overflow() -> Result<()> {
+        let svg = fs::read_to_string(TEMPLATE_PATH)?;
+        let temp_dir = write_fortune_data(
+            "    - Confess your love.\n    - The person you are waiting for will come, but much \
+             later than you hope, so be patient and keep your heart open.",
+        );
+        let template = svg.replace(
+            "font-family=",
+            r#"data-wrap-width="150" data-wrap-height="20" font-family="#,
+        );
+        fs::write(temp_dir.path().join("template.svg"), template)?;
+        let settings_path = temp_dir.path().join("settings.yaml");
+        let settings = fs::read_to_string(&settings_path)?.replace(
+            &format!(
+                "template_front: {}",
+                Path::new(TEMPLATE_PATH).canonicalize()?.display()
+            ),
+            "template_front: template.svg",
+        );
+        fs::write(&settings_path, settings)?;
+
+        let fortune_gen = FortuneGenerator::open(&settings_path)?;
+        let issues = fortune_gen.lint()?;
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, LintKind::Overflow);
+        assert_eq!(issues[0].location.line, Some(5));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_reports_an_unusable_template_once_per_category() -> Result<()> {
+        let temp_dir = write_fortune_data("    - Confess your love.\n    - Be patient.");
+        let settings_path = temp_dir.path().join("settings.yaml");
+        // Without text elements for `love`, the template can't hold the fortunes.
+        let template = fs::read_to_string(TEMPLATE_PATH)?.replace("love", "romance");
+        fs::write(temp_dir.path().join("template.svg"), template)?;
+        let settings = fs::read_to_string(&settings_path)?.replace(
+            &format!(
+                "template_front: {}",
+                Path::new(TEMPLATE_PATH).canonicalize()?.display()
+            ),
+            "template_front: template.svg",
+        );
+        fs::write(&settings_path, settings)?;
+
+        let fortune_gen = FortuneGenerator::open(&settings_path)?;
+        let issues = fortune_gen.lint()?;
+
+        assert_eq!(issues.len(), 3);
+        assert!(issues
+            .iter()
+            .all(|issue| matches!(issue.kind, LintKind::Unchecked(_))));
+        assert!(issues[0]
+            .to_string()
+            .contains("can't be checked against the template"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,420 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+/// The columns of a CSV manifest before the fortunes, which get a column per category.
+const CSV_COLUMNS: [&str; 6] = ["page", "slot", "serial", "luck_level", "header", "seed"];
+
+/// A slip as it was generated, with where it was printed.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct SlipRecord {
+    /// The number of the front page, starting at 1 like `{{page_number}}`. With a file per slip,
+    /// the number of the file.
+    pub page: usize,
+    /// The position of the slip on the page, starting at 1. Always 1 with a file per slip.
+    pub slot: usize,
+    pub serial: String,
+    pub luck_level: String,
+    pub header: String,
+    pub seed: u64,
+    /// The fortune of every category, ordered by category.
+    pub fortunes: IndexMap<String, String>,
+}
+
+/// The file format of a manifest, picked by the extension of its path.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ManifestFormat {
+    Json,
+    Csv,
+}
+
+impl ManifestFormat {
+    /// Returns the format for the extension of the path, e.g. `Csv` for `slips.csv`.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_lowercase);
+
+        match extension.as_deref() {
+            Some("json") => Ok(ManifestFormat::Json),
+            Some("csv") => Ok(ManifestFormat::Csv),
+            _ => bail!(
+                "Unknown manifest format of '{}', expected a .json or .csv file",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// A slip to pick from a manifest, by its serial or by where it was printed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SlipSelector {
+    /// The serial of the slip, e.g. `42`, which also matches a padded serial like `00042`.
+    Serial(String),
+    /// The page and slot of the slip, e.g. `3/2` for the second slip on the third page.
+    Position { page: usize, slot: usize },
+}
+
+impl SlipSelector {
+    /// Returns whether the selector picks the slip of the record.
+    pub fn matches(&self, record: &SlipRecord) -> bool {
+        match self {
+            SlipSelector::Serial(serial) => {
+                serial == &record.serial
+                    || matches!(
+                        (serial.parse::<u64>(), record.serial.parse::<u64>()),
+                        (Ok(a), Ok(b)) if a == b
+                    )
+            }
+            SlipSelector::Position { page, slot } => *page == record.page && *slot == record.slot,
+        }
+    }
+}
+
+impl FromStr for SlipSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() {
+            bail!("expected a serial or PAGE/SLOT, found an empty value");
+        }
+
+        let Some((page, slot)) = s.split_once('/') else {
+            return Ok(SlipSelector::Serial(s.to_string()));
+        };
+
+        let parse = |nr: &str| {
+            nr.trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|&nr| nr > 0)
+                .ok_or(anyhow!("expected PAGE/SLOT counting from 1, found '{s}'"))
+        };
+        Ok(SlipSelector::Position {
+            page: parse(page)?,
+            slot: parse(slot)?,
+        })
+    }
+}
+
+impl fmt::Display for SlipSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlipSelector::Serial(serial) => write!(f, "serial {serial}"),
+            SlipSelector::Position { page, slot } => write!(f, "page {page}, slot {slot}"),
+        }
+    }
+}
+
+/// Returns the records picked by the selectors, in the order of the selectors and without
+/// picking a record twice.
+///
+/// Returns an error when a selector does not match any record.
+pub fn select_records(
+    records: &[SlipRecord],
+    selectors: &[SlipSelector],
+) -> Result<Vec<SlipRecord>> {
+    let mut selected: Vec<SlipRecord> = vec![];
+
+    for selector in selectors {
+        let matching = records
+            .iter()
+            .filter(|record| selector.matches(record))
+            .collect::<Vec<_>>();
+
+        if matching.is_empty() {
+            bail!("No slip with {selector} in the manifest");
+        }
+
+        for record in matching {
+            if !selected.contains(record) {
+                selected.push(record.clone());
+            }
+        }
+    }
+
+    Ok(selected)
+}
+
+/// Reads the records of a manifest file, as JSON or CSV by its extension.
+pub fn read_manifest(path: impl AsRef<Path>) -> Result<Vec<SlipRecord>> {
+    let path = path.as_ref();
+    let format = ManifestFormat::from_path(path)?;
+    let file = File::open(path)
+        .with_context(|| format!("Could not open the manifest '{}'", path.display()))?;
+    let reader = BufReader::new(file);
+
+    match format {
+        ManifestFormat::Json => serde_json::from_reader(reader)
+            .with_context(|| format!("Could not read the manifest '{}'", path.display())),
+        ManifestFormat::Csv => read_csv(reader)
+            .with_context(|| format!("Could not read the manifest '{}'", path.display())),
+    }
+}
+
+/// Writes the records to the manifest file, as JSON or CSV by its extension.
+///
+/// Example:
+/// ```
+/// # use anyhow::Ok;
+/// use fortune_generator::fortune_manifest::{write_manifest, SlipRecord};
+/// use indexmap::indexmap;
+///
+/// let record = SlipRecord {
+///     page: 1,
+///     slot: 1,
+///     serial: "0001".to_string(),
+///     luck_level: "Great Luck".to_string(),
+///     header: "大吉".to_string(),
+///     seed: 42,
+///     fortunes: indexmap! { "love".to_string() => "Be patient.".to_string() },
+/// };
+/// # let temp_dir = tempfile::tempdir()?;
+/// # let manifest_path = temp_dir.path().join("manifest.csv");
+/// write_manifest(&[record], &manifest_path)?;
+/// # Ok(())
+/// ```
+pub fn write_manifest(records: &[SlipRecord], path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let format = ManifestFormat::from_path(path)?;
+    let file = File::create(path)
+        .with_context(|| format!("Could not create the manifest '{}'", path.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    match format {
+        ManifestFormat::Json => serde_json::to_writer_pretty(&mut writer, records)?,
+        ManifestFormat::Csv => write_csv(records, &mut writer)?,
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the records as CSV, with a column for the fortunes of every category.
+fn write_csv(records: &[SlipRecord], writer: impl Write) -> Result<()> {
+    let categories = records
+        .first()
+        .map(|record| record.fortunes.keys().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    csv_writer.write_record(
+        CSV_COLUMNS
+            .iter()
+            .copied()
+            .chain(categories.iter().map(String::as_str)),
+    )?;
+
+    for record in records {
+        let page = record.page.to_string();
+        let slot = record.slot.to_string();
+        let seed = record.seed.to_string();
+        let fortunes = categories
+            .iter()
+            .map(|category| record.fortunes.get(category).map_or("", String::as_str));
+
+        csv_writer.write_record(
+            [
+                page.as_str(),
+                slot.as_str(),
+                &record.serial,
+                &record.luck_level,
+                &record.header,
+                seed.as_str(),
+            ]
+            .into_iter()
+            .chain(fortunes),
+        )?;
+    }
+
+    csv_writer.flush()?;
+    Ok(())
+}
+
+/// Reads the records from CSV, where every column after the `CSV_COLUMNS` is a category.
+fn read_csv(reader: impl Read) -> Result<Vec<SlipRecord>> {
+    let mut csv_reader = csv::Reader::from_reader(reader);
+    let headers = csv_reader.headers()?.clone();
+
+    if headers.len() < CSV_COLUMNS.len() || headers.iter().zip(CSV_COLUMNS).any(|(a, b)| a != b) {
+        bail!(
+            "The CSV has to start with the columns: {}",
+            CSV_COLUMNS.join(",")
+        );
+    }
+
+    csv_reader
+        .records()
+        .map(|row| {
+            let row = row?;
+            let line = row.position().map_or(0, |pos| pos.line());
+            let number = |idx: usize| -> Result<u64> {
+                row[idx].trim().parse().map_err(|_| {
+                    anyhow!(
+                        "The {} on line {line} is not a number: {}",
+                        CSV_COLUMNS[idx],
+                        &row[idx]
+                    )
+                })
+            };
+
+            Ok(SlipRecord {
+                page: number(0)? as usize,
+                slot: number(1)? as usize,
+                serial: row[2].to_string(),
+                luck_level: row[3].to_string(),
+                header: row[4].to_string(),
+                seed: number(5)?,
+                fortunes: headers
+                    .iter()
+                    .zip(row.iter())
+                    .skip(CSV_COLUMNS.len())
+                    .map(|(category, fortune)| (category.to_string(), fortune.to_string()))
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use indexmap::indexmap;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::{read_csv, select_records, write_csv, ManifestFormat, SlipRecord, SlipSelector};
+
+    fn record(slot: usize, love: &str) -> SlipRecord {
+        SlipRecord {
+            page: 2,
+            slot,
+            serial: format!("00{slot}"),
+            luck_level: "Great Luck".to_string(),
+            header: "大吉".to_string(),
+            seed: 42,
+            fortunes: indexmap! {
+                "general".to_string() => "All is well.".to_string(),
+                "love".to_string() => love.to_string(),
+            },
+        }
+    }
+
+    #[rstest]
+    #[case("slips.json", Some(ManifestFormat::Json))]
+    #[case("out/SLIPS.CSV", Some(ManifestFormat::Csv))]
+    #[case("slips.txt", None)]
+    #[case("slips", None)]
+    fn test_manifest_format_from_path(
+        #[case] path: &str,
+        #[case] expected: Option<ManifestFormat>,
+    ) {
+        assert_eq!(ManifestFormat::from_path(path).ok(), expected);
+    }
+
+    #[test]
+    fn test_write_csv() -> Result<()> {
+        let records = [
+            record(1, "Be patient."),
+            record(2, "Say \"yes\", then wait."),
+        ];
+
+        let mut csv = vec![];
+        write_csv(&records, &mut csv)?;
+
+        assert_eq!(
+            String::from_utf8(csv)?,
+            "page,slot,serial,luck_level,header,seed,general,love\n\
+             2,1,001,Great Luck,大吉,42,All is well.,Be patient.\n\
+             2,2,002,Great Luck,大吉,42,All is well.,\"Say \"\"yes\"\", then wait.\"\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_keeps_the_order_of_the_fortunes() -> Result<()> {
+        let json = serde_json::to_string(&record(1, "Be patient."))?;
+
+        assert!(json.contains(r#""fortunes":{"general":"All is well.","love":"Be patient."}"#));
+        assert_eq!(
+            serde_json::from_str::<SlipRecord>(&json)?,
+            record(1, "Be patient.")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_csv_reads_what_was_written() -> Result<()> {
+        let records = vec![
+            record(1, "Be patient."),
+            record(2, "Say \"yes\",\nthen wait."),
+        ];
+
+        let mut csv = vec![];
+        write_csv(&records, &mut csv)?;
+
+        assert_eq!(read_csv(csv.as_slice())?, records);
+        assert!(read_csv("page,slot,luck_level\n".as_bytes()).is_err());
+        assert!(read_csv(
+            "page,slot,serial,luck_level,header,seed\none,1,1,Good Luck,中吉,42\n".as_bytes()
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case("42", SlipSelector::Serial("42".to_string()))]
+    #[case(" A-7 ", SlipSelector::Serial("A-7".to_string()))]
+    #[case("3/2", SlipSelector::Position { page: 3, slot: 2 })]
+    fn test_slip_selector_from_str(#[case] arg: &str, #[case] expected: SlipSelector) {
+        assert_eq!(arg.parse::<SlipSelector>().unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("3/")]
+    #[case("0/1")]
+    #[case("a/b")]
+    fn test_slip_selector_from_str_errors(#[case] arg: &str) {
+        assert!(arg.parse::<SlipSelector>().is_err());
+    }
+
+    #[test]
+    fn test_select_records() -> Result<()> {
+        let records = [record(1, "Be patient."), record(2, "Wait.")];
+        let select = |selectors: &[&str]| {
+            let selectors = selectors
+                .iter()
+                .map(|selector| selector.parse().unwrap())
+                .collect::<Vec<SlipSelector>>();
+            select_records(&records, &selectors)
+        };
+
+        let slots = |selected: Vec<SlipRecord>| {
+            selected
+                .iter()
+                .map(|record| record.slot)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(slots(select(&["2/2", "1"])?), vec![2, 1]);
+        assert_eq!(slots(select(&["001", "2/1"])?), vec![1]);
+        assert!(select(&["3"]).is_err());
+        assert!(select(&["1/1"]).is_err());
+
+        Ok(())
+    }
+}
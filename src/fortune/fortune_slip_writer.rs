@@ -1,40 +1,118 @@
 use std::{collections::HashMap, path::Path};
 
 use crate::{
-    constants::NR_SLIPS_PER_PAGE,
-    fortune::fortune_splitter::FortuneSlipTextRef,
-    svg::svg_editor::{text_elem::TextElem, SvgEditor},
+    fortune::{
+        fortune_data::fortune_settings::{LuckLevelStyle, PrintMarks, SerialSettings},
+        fortune_splitter::FortuneSlipTextRef,
+    },
+    svg::{
+        font_metrics::{format_length, TextStyle},
+        svg_bounds::{Bounds, PageBounds},
+        svg_editor::{text_elem::TextElem, SvgEditor},
+    },
 };
 
 use anyhow::{anyhow, bail, Result};
+use log::warn;
+
+use self::{
+    placeholders::{fill_placeholders, retrieve_placeholder_elems, substitute, PlaceholderElem},
+    qr_code::{retrieve_qr_elems, write_qr_code, QrElem},
+    slip_styler::{apply_style, retrieve_styled_elems, StyledElem},
+    svg_keys::{marked_slip_numbers, measure_slips, retrieve_svg_keys, SvgKeys},
+    text_wrapper::{fit_text, wrap_box, FittedText, WrapBox},
+};
 
-use self::svg_keys::{retrieve_svg_keys, SvgKeys};
-
+pub mod backside_writer;
+pub mod imposition;
+pub mod placeholders;
+pub mod print_marks;
+mod qr_code;
+mod slip_styler;
 pub mod svg_keys;
+mod text_wrapper;
+
+/// The smallest font size of a text element, e.g. `<text data-min-font-size="8">`.
+const MIN_FONT_SIZE_ATTR: &str = "data-min-font-size";
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct FortuneSlipWriter {
     svg_editor: SvgEditor,
     svg_keys_all_slips: Vec<SvgKeys>,
+    /// The text elements as they are in the template, before any font size is lowered.
+    template_text_elems: HashMap<String, TextElem>,
+    /// The elements that are styled by the luck level of their slip.
+    styled_elems: Vec<StyledElem>,
+    /// The elements with `{{variable}}` placeholders, other than the fields of the slips.
+    placeholder_elems: Vec<PlaceholderElem>,
+    /// The boxes to draw the QR code of every slip in.
+    qr_elems: Vec<QrElem>,
+    serials: SerialSettings,
+    /// The variables of every page, such as `event_name` and `date`.
+    variables: HashMap<String, String>,
+    nr_pages_written: usize,
+    nr_slips_written: usize,
+    min_font_size: Option<f32>,
 }
 
 impl FortuneSlipWriter {
+    /// Creates a writer for the template in `svg_editor`.
+    ///
+    /// Without `slips_per_page`, the number of slips is detected from the template.
     pub fn new(
         mut svg_editor: SvgEditor,
         fortune_categories: &[String],
+        slips_per_page: Option<usize>,
     ) -> Result<FortuneSlipWriter> {
-        // Ok(slip_writer)
-
-        let svg_keys = retrieve_svg_keys(&svg_editor, fortune_categories)?;
+        let svg_keys = retrieve_svg_keys(&svg_editor, fortune_categories, slips_per_page)?;
 
         Self::center_relevant_elems(&mut svg_editor, &svg_keys)?;
+        let slip_numbers = marked_slip_numbers(&svg_editor)?;
+        let styled_elems = retrieve_styled_elems(&svg_editor, &svg_keys, &slip_numbers)?;
+        let placeholder_elems = retrieve_placeholder_elems(&svg_editor, &svg_keys, &slip_numbers)?;
+        let qr_elems = retrieve_qr_elems(&svg_editor, &svg_keys, &slip_numbers)?;
+
+        let template_text_elems = svg_editor
+            .get_text_elems_ordered()
+            .into_iter()
+            .map(|text_elem| (text_elem.id.clone(), text_elem.clone()))
+            .collect();
 
         Ok(FortuneSlipWriter {
             svg_keys_all_slips: svg_keys,
             svg_editor,
+            template_text_elems,
+            styled_elems,
+            placeholder_elems,
+            qr_elems,
+            serials: SerialSettings::default(),
+            variables: HashMap::new(),
+            nr_pages_written: 0,
+            nr_slips_written: 0,
+            min_font_size: None,
         })
     }
 
+    /// Sets the smallest font size a fortune may be shrunk to when it does not fit its box.
+    ///
+    /// A `data-min-font-size` on the text element in the template takes precedence.
+    pub fn set_min_font_size(&mut self, min_font_size: Option<f32>) {
+        self.min_font_size = min_font_size;
+    }
+
+    /// Sets the variables to fill the `{{variable}}` placeholders of the template with.
+    ///
+    /// The writer adds `page_number`, and for the elements of a slip `slip_number`, `serial`,
+    /// `luck.eng` and `luck.jap`.
+    pub fn set_variables(&mut self, variables: HashMap<String, String>) {
+        self.variables = variables;
+    }
+
+    /// Sets how the slips are numbered and what their QR codes encode.
+    pub fn set_serials(&mut self, serials: SerialSettings) {
+        self.serials = serials;
+    }
+
     fn center_relevant_elems(
         svg_editor: &mut SvgEditor,
         svg_keys_all_slip: &[SvgKeys],
@@ -72,22 +150,88 @@ impl FortuneSlipWriter {
             .insert("text-anchor".to_string(), "middle".to_string());
     }
 
+    /// Draws the print marks on every page, around the slips of the template at `template`.
+    pub fn add_print_marks(&mut self, template: &Path, print_marks: &PrintMarks) -> Result<()> {
+        print_marks::add_print_marks(&mut self.svg_editor, template, print_marks)
+    }
+
+    /// Returns the number of slips on a single page of the template.
+    pub fn nr_slips_per_page(&self) -> usize {
+        self.svg_keys_all_slips.len()
+    }
+
     pub fn write_page(&mut self, fortune_texts: &[FortuneSlipTextRef]) -> Result<()> {
-        if fortune_texts.len() > NR_SLIPS_PER_PAGE {
+        if fortune_texts.len() > self.nr_slips_per_page() {
             bail!("Too many fortune texts for one page");
         }
 
+        self.fill_placeholders(fortune_texts)?;
+
         for (idx, fortune_text) in fortune_texts.iter().enumerate() {
             self.write_to_slip(idx, fortune_text)?;
         }
 
-        if fortune_texts.len() < NR_SLIPS_PER_PAGE {
+        if fortune_texts.len() < self.nr_slips_per_page() {
             self.write_empty_fortunes(fortune_texts.len())?;
         }
 
+        self.nr_pages_written += 1;
+        self.nr_slips_written += fortune_texts.len();
+
+        Ok(())
+    }
+
+    /// Fills the placeholders and QR codes of the page, with the variables of the slip they
+    /// belong to.
+    fn fill_placeholders(&mut self, fortune_texts: &[FortuneSlipTextRef]) -> Result<()> {
+        let mut page_variables = self.variables.clone();
+        page_variables.insert(
+            "page_number".to_string(),
+            (self.nr_pages_written + 1).to_string(),
+        );
+
+        for placeholder_elem in &self.placeholder_elems {
+            let variables = match placeholder_elem.slip_idx {
+                Some(idx) => self.slip_variables(idx, fortune_texts, &page_variables),
+                None => page_variables.clone(),
+            };
+
+            fill_placeholders(&mut self.svg_editor, placeholder_elem, &variables)?;
+        }
+
+        for qr_elem in &self.qr_elems {
+            let content = match fortune_texts.get(qr_elem.slip_idx) {
+                Some(_) => {
+                    let variables =
+                        self.slip_variables(qr_elem.slip_idx, fortune_texts, &page_variables);
+                    let content = qr_elem.content.as_ref().unwrap_or(&self.serials.qr_content);
+                    substitute(content, &variables)
+                        .map_err(|e| anyhow!("In the QR code '{}': {e}", qr_elem.id))?
+                }
+                None => String::new(),
+            };
+
+            write_qr_code(&mut self.svg_editor, qr_elem, &content)?;
+        }
+
         Ok(())
     }
 
+    /// Returns the variables of the slip at `idx` on the page, which are empty for an empty slip.
+    fn slip_variables(
+        &self,
+        idx: usize,
+        fortune_texts: &[FortuneSlipTextRef],
+        page_variables: &HashMap<String, String>,
+    ) -> HashMap<String, String> {
+        slip_variables(
+            self.nr_slips_written + idx,
+            fortune_texts.get(idx),
+            &self.serials,
+            page_variables,
+        )
+    }
+
     fn write_empty_fortunes(&mut self, start_idx: usize) -> Result<()> {
         let empty_string = "".to_string();
 
@@ -102,13 +246,16 @@ impl FortuneSlipWriter {
             .map(|category| (category, &empty_string))
             .collect::<HashMap<&String, &String>>();
 
+        let no_style = LuckLevelStyle::default();
         let empty_fortune = FortuneSlipTextRef {
             header: "",
             luck_level: "",
+            style: &no_style,
             category_to_fortune,
+            serial: None,
         };
 
-        for idx in start_idx..NR_SLIPS_PER_PAGE {
+        for idx in start_idx..self.nr_slips_per_page() {
             self.write_to_slip(idx, &empty_fortune)?;
         }
 
@@ -116,20 +263,24 @@ impl FortuneSlipWriter {
     }
 
     pub fn write_to_slip(&mut self, idx: usize, fortune_text: &FortuneSlipTextRef) -> Result<()> {
-        let svg_keys: &SvgKeys = self
+        let svg_keys: SvgKeys = self
             .svg_keys_all_slips
             .get(idx)
-            .ok_or(anyhow::anyhow!("No svg_elem_keys found for idx: {}", idx))?;
+            .ok_or(anyhow::anyhow!("No svg_elem_keys found for idx: {}", idx))?
+            .clone();
+        let luck_level = fortune_text.luck_level;
 
-        Self::write_to_elem(
-            &mut self.svg_editor,
+        self.write_to_elem(
             &svg_keys.header_key,
+            "header",
+            luck_level,
             fortune_text.header,
         )?;
-        Self::write_to_elem(
-            &mut self.svg_editor,
+        self.write_to_elem(
             &svg_keys.luck_level_key,
-            fortune_text.luck_level,
+            "luck_level",
+            luck_level,
+            luck_level,
         )?;
 
         for (category, text_elem_key) in &svg_keys.cat_to_fortune_keys {
@@ -137,21 +288,279 @@ impl FortuneSlipWriter {
                 .category_to_fortune
                 .get(category)
                 .ok_or(anyhow!("No fortune text found for category: {}", category))?;
-            Self::write_to_elem(&mut self.svg_editor, text_elem_key, fortune_text)?;
+            self.write_to_elem(text_elem_key, category, luck_level, fortune_text)?;
+        }
+
+        self.style_slip(idx, fortune_text.style)
+    }
+
+    /// Applies the style of the luck level to the styled elements of the slip.
+    fn style_slip(&mut self, idx: usize, style: &LuckLevelStyle) -> Result<()> {
+        for styled_elem in self.styled_elems.iter().filter(|x| x.slip_idx == idx) {
+            apply_style(&mut self.svg_editor, styled_elem, style)?;
         }
+
         Ok(())
     }
 
+    /// Returns whether the fortune fits the `field` text element of every slip.
+    ///
+    /// A text element with a box fits the fortune when it fits the box after wrapping it and
+    /// lowering the font size as far as allowed. Other text elements fit the fortune when it stays
+    /// within their slip, or within the page for a template without `data-slip` markers.
+    /// `template` is where the template is read from, to lay it out.
+    pub fn fits(&self, field: &str, fortune: &str, template: &Path) -> Result<bool> {
+        let mut unboxed_elems = vec![];
+
+        for (slip_idx, svg_keys) in self.svg_keys_all_slips.iter().enumerate() {
+            let elem_id = svg_keys.key_of(field).ok_or(anyhow!(
+                "No text element found in the template for: {field}"
+            ))?;
+            let mut text_elem = self.template_text_elem(elem_id)?.clone();
+
+            match wrap_box(&self.svg_editor, &text_elem)? {
+                Some(wrap_box) => {
+                    if !self.fit_to_box(&mut text_elem, wrap_box, fortune)?.fits {
+                        return Ok(false);
+                    }
+                }
+                None => unboxed_elems.push((slip_idx, elem_id.as_str())),
+            }
+        }
+
+        if unboxed_elems.is_empty() {
+            return Ok(true);
+        }
+        self.fits_in_slips(&unboxed_elems, fortune, template)
+    }
+
+    /// Returns the size of the page and the bounds of every slip, by its index on the page.
+    ///
+    /// A slip is measured by its `data-slip` elements, or by its text elements in a template
+    /// without `data-slip` markers. `template` is where the template is read from, to lay it out.
+    pub fn slip_bounds(&self, template: &Path) -> Result<PageBounds<usize>> {
+        let slip_numbers = marked_slip_numbers(&self.svg_editor)?;
+
+        if !slip_numbers.is_empty() {
+            let slips = measure_slips(&self.svg_editor, template)?;
+            let elems = slips
+                .elems
+                .into_iter()
+                .filter_map(|(number, bounds)| {
+                    let slip_idx = slip_numbers.iter().position(|&nr| nr == number)?;
+                    Some((slip_idx, bounds))
+                })
+                .collect();
+            return Ok(PageBounds { elems, ..slips });
+        }
+
+        let slip_of_elem = self
+            .svg_keys_all_slips
+            .iter()
+            .enumerate()
+            .flat_map(|(slip_idx, svg_keys)| {
+                [&svg_keys.header_key, &svg_keys.luck_level_key]
+                    .into_iter()
+                    .chain(svg_keys.cat_to_fortune_keys.values())
+                    .map(move |elem_id| (elem_id.as_str(), slip_idx))
+            })
+            .collect::<HashMap<_, _>>();
+        let elem_ids = slip_of_elem.keys().copied().collect::<Vec<_>>();
+        let texts = self.svg_editor.get_bounds_of_elems(&elem_ids, template)?;
+
+        let mut slips: Vec<Option<Bounds>> = vec![None; self.nr_slips_per_page()];
+        for (elem_id, bounds) in &texts.elems {
+            let slip = &mut slips[slip_of_elem[elem_id.as_str()]];
+            *slip = Some(slip.map_or(*bounds, |slip| slip.union(bounds)));
+        }
+
+        Ok(PageBounds {
+            width: texts.width,
+            height: texts.height,
+            elems: slips
+                .into_iter()
+                .enumerate()
+                .filter_map(|(slip_idx, bounds)| Some((slip_idx, bounds?)))
+                .collect(),
+        })
+    }
+
+    /// Returns whether the fortune, written in the text elements of the slips as it is, stays
+    /// within the slip of every element.
+    fn fits_in_slips(
+        &self,
+        elems: &[(usize, &str)],
+        fortune: &str,
+        template: &Path,
+    ) -> Result<bool> {
+        // Allows for rounding in the layout of the text.
+        const TOLERANCE: f32 = 0.5;
+
+        let mut svg_editor = self.svg_editor.clone();
+        for (_, elem_id) in elems {
+            let mut text_elem = self.template_text_elem(elem_id)?.clone();
+            text_elem.text = fortune.to_string();
+            svg_editor.update_text_elem_by_id(text_elem)?;
+        }
+
+        let slips = measure_slips(&svg_editor, template)?;
+        let slip_numbers = marked_slip_numbers(&svg_editor)?;
+        let page = Bounds {
+            x: 0.0,
+            y: 0.0,
+            width: slips.width,
+            height: slips.height,
+        };
+        let elem_ids = elems
+            .iter()
+            .map(|(_, elem_id)| *elem_id)
+            .collect::<Vec<_>>();
+        let texts = svg_editor.get_bounds_of_elems(&elem_ids, template)?;
+
+        for (elem_id, text_bounds) in &texts.elems {
+            let slip_bounds = elems
+                .iter()
+                .find(|(_, id)| id == elem_id)
+                .and_then(|(slip_idx, _)| slip_numbers.get(*slip_idx))
+                .and_then(|slip| slips.elems.iter().find(|(number, _)| number == slip))
+                .map_or(page, |(_, bounds)| *bounds);
+
+            if !slip_bounds.contains(text_bounds, TOLERANCE) {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
     pub fn save_to_svg(&self, svg_path: &impl AsRef<Path>) -> Result<()> {
         self.svg_editor.save_to_svg(svg_path)
     }
 
-    fn write_to_elem(svg_editor: &mut SvgEditor, elem_id: &str, fortune: &str) -> Result<()> {
-        let mut new_text_elem = svg_editor.get_elem_with_id(elem_id)?.clone();
-        new_text_elem.text = fortune.into();
-        svg_editor.update_text_elem_by_id(new_text_elem)?;
+    /// Returns the page as it is written, as SVG.
+    pub fn to_svg_string(&self) -> Result<String> {
+        let mut svg = vec![];
+        self.svg_editor.write_svg(&mut svg)?;
+        Ok(String::from_utf8(svg)?)
+    }
+
+    /// Writes the fortune to the text element.
+    ///
+    /// When the template gives the element a box, the fortune is wrapped over multiple lines and
+    /// the font size is lowered until it fits. `field` and `luck_level` are only used to point
+    /// out the fortune when it does not fit.
+    fn write_to_elem(
+        &mut self,
+        elem_id: &str,
+        field: &str,
+        luck_level: &str,
+        fortune: &str,
+    ) -> Result<()> {
+        let mut new_text_elem = self.template_text_elem(elem_id)?.clone();
+
+        new_text_elem.text = match wrap_box(&self.svg_editor, &new_text_elem)? {
+            Some(wrap_box) => {
+                let fitted_text = self.fit_to_box(&mut new_text_elem, wrap_box, fortune)?;
+
+                if !fitted_text.fits {
+                    warn!(
+                        "The {field} fortune of a '{luck_level}' slip does not fit its box, \
+                         even at font size {}: {fortune}",
+                        fitted_text.font_size
+                    );
+                }
+
+                fitted_text.lines.join("\n")
+            }
+            None => fortune.into(),
+        };
+
+        self.svg_editor.update_text_elem_by_id(new_text_elem)?;
         Ok(())
     }
+
+    fn template_text_elem(&self, elem_id: &str) -> Result<&TextElem> {
+        self.template_text_elems.get(elem_id).ok_or(anyhow!(
+            "No text element found in the template with id: {elem_id}"
+        ))
+    }
+
+    /// Wraps the fortune within the box and lowers the font size of the element until it fits.
+    ///
+    /// The font size is not lowered below `data-min-font-size` of the element, or otherwise the
+    /// minimum font size of the writer. Without either, the font size is kept as it is.
+    fn fit_to_box(
+        &self,
+        text_elem: &mut TextElem,
+        wrap_box: WrapBox,
+        fortune: &str,
+    ) -> Result<FittedText> {
+        let text_style = TextStyle::from_attr(&text_elem.attr);
+
+        let min_font_size = match text_elem.attr.get(MIN_FONT_SIZE_ATTR) {
+            Some(size) => size
+                .trim()
+                .parse()
+                .ok()
+                .filter(|size: &f32| size.is_finite() && *size > 0.0)
+                .ok_or(anyhow!(
+                    "The {MIN_FONT_SIZE_ATTR} of '{}' is not a number above zero: {size}",
+                    text_elem.id
+                ))?,
+            None => self.min_font_size.unwrap_or(text_style.font_size),
+        };
+
+        let fitted_text = fit_text(fortune, wrap_box, &text_style, min_font_size)?;
+
+        if fitted_text.font_size != text_style.font_size {
+            let scale = fitted_text.font_size / text_style.font_size;
+            text_elem.attr.insert(
+                "font-size".to_string(),
+                format_length(fitted_text.font_size),
+            );
+
+            if text_elem.attr.contains_key("data-line-height") {
+                text_elem.attr.insert(
+                    "data-line-height".to_string(),
+                    format_length(text_style.line_height * scale),
+                );
+            }
+        }
+
+        Ok(fitted_text)
+    }
+}
+
+/// Returns the variables of a slip, which are empty for an empty slip.
+///
+/// `slip_idx` counts the slips from the first page, for the number and serial of the slip.
+fn slip_variables(
+    slip_idx: usize,
+    fortune_text: Option<&FortuneSlipTextRef>,
+    serials: &SerialSettings,
+    page_variables: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    let (slip_number, serial, luck_level, header) = match fortune_text {
+        Some(fortune_text) => (
+            (slip_idx + 1).to_string(),
+            fortune_text
+                .serial
+                .map(str::to_string)
+                .unwrap_or_else(|| serials.format(slip_idx)),
+            fortune_text.luck_level.to_string(),
+            fortune_text.header.to_string(),
+        ),
+        None => Default::default(),
+    };
+
+    let mut slip_variables = page_variables.clone();
+    slip_variables.extend([
+        ("slip_number".to_string(), slip_number),
+        ("serial".to_string(), serial),
+        ("luck.eng".to_string(), luck_level),
+        ("luck.jap".to_string(), header),
+    ]);
+    slip_variables
 }
 
 #[cfg(test)]
@@ -163,13 +572,34 @@ mod tests {
     use rstest::{fixture, rstest};
 
     use crate::{
-        constants::NR_SLIPS_PER_PAGE,
-        fortune::{fortune_data::FortuneData, fortune_splitter::FortuneSlipTextRef},
+        fortune::{
+            fortune_data::{
+                fortune_settings::{LuckLevelStyle, SerialSettings},
+                FortuneData,
+            },
+            fortune_splitter::FortuneSlipTextRef,
+        },
         svg::svg_editor::SvgEditor,
     };
 
     use super::FortuneSlipWriter;
 
+    const NR_SLIPS_PER_PAGE: usize = 4;
+
+    const TWO_SLIPS_SVG: &str = r#"
+        <svg height='200' width='100'>
+            <text id="header_1"><tspan>header_1</tspan></text>
+            <text id="luck_1"><tspan>luck_level_1</tspan></text>
+            <text id="general_1"><tspan>general_1</tspan></text>
+            <text id="health_1"><tspan>health_1</tspan></text>
+            <text id="love_1"><tspan>love_1</tspan></text>
+            <text id="header_2"><tspan>header_2</tspan></text>
+            <text id="luck_2"><tspan>luck_level_2</tspan></text>
+            <text id="general_2"><tspan>general_2</tspan></text>
+            <text id="health_2"><tspan>health_2</tspan></text>
+            <text id="love_2"><tspan>love_2</tspan></text>
+        </svg>"#;
+
     #[fixture]
     fn svg_editor() -> SvgEditor {
         let svg_file_path = "test_utils/data/fortune_template/omikuji_frontside_test.svg";
@@ -192,22 +622,33 @@ mod tests {
             .cloned()
             .collect::<Vec<String>>();
 
-        FortuneSlipWriter::new(svg_editor, &fc).unwrap()
+        FortuneSlipWriter::new(svg_editor, &fc, None).unwrap()
+    }
+
+    fn fortune_categories() -> Vec<String> {
+        vec![
+            "general".to_string(),
+            "health".to_string(),
+            "love".to_string(),
+        ]
     }
 
     #[derive(Debug, Clone, PartialEq, Eq)]
     struct FortuneSlipTextOwned {
         header: String,
         luck_level: String,
+        style: LuckLevelStyle,
         category_to_fortune: HashMap<String, String>,
     }
 
     impl FortuneSlipTextOwned {
-        fn to_ref(&self) -> FortuneSlipTextRef {
+        fn to_ref(&self) -> FortuneSlipTextRef<'_> {
             FortuneSlipTextRef {
                 header: &self.header,
                 luck_level: &self.luck_level,
+                style: &self.style,
                 category_to_fortune: self.category_to_fortune.iter().collect(),
+                serial: None,
             }
         }
     }
@@ -223,6 +664,7 @@ mod tests {
         FortuneSlipTextOwned {
             header: "header_text".to_string(),
             luck_level: "luck_level_text".to_string(),
+            style: LuckLevelStyle::default(),
             category_to_fortune,
         }
     }
@@ -233,6 +675,327 @@ mod tests {
     #[rstest]
     fn test_nr_fortunes_per_slip(slip_writer: FortuneSlipWriter) {
         assert_eq!(slip_writer.svg_keys_all_slips.len(), NR_SLIPS_PER_PAGE);
+        assert_eq!(slip_writer.nr_slips_per_page(), NR_SLIPS_PER_PAGE);
+    }
+
+    #[test]
+    fn test_nr_slips_detected_from_template() -> Result<()> {
+        let temp_file = test_utils::create_temp_file("two_slips.svg", TWO_SLIPS_SVG);
+        let svg_editor = SvgEditor::open(&temp_file.path)?;
+
+        let slip_writer = FortuneSlipWriter::new(svg_editor, &fortune_categories(), None)?;
+
+        assert_eq!(slip_writer.nr_slips_per_page(), 2);
+        assert_eq!(slip_writer.svg_keys_all_slips[1].header_key, "header_2");
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_write_page_applies_luck_level_style(fortune_text: &FortuneSlipTextOwned) -> Result<()> {
+        let styled_svg = TWO_SLIPS_SVG
+            .replace(
+                r#"<text id="header_1">"#,
+                r#"<text id="header_1" data-style="header">"#,
+            )
+            .replace(
+                r#"<text id="luck_2">"#,
+                r#"<text id="luck_2" data-style="accent">"#,
+            );
+        let temp_file = test_utils::create_temp_file("two_slips.svg", &styled_svg);
+        let svg_editor = SvgEditor::open(&temp_file.path)?;
+        let mut slip_writer = FortuneSlipWriter::new(svg_editor, &fortune_categories(), None)?;
+
+        let mut great_luck = fortune_text.clone();
+        great_luck.style = LuckLevelStyle {
+            header_color: Some("red".to_string()),
+            accent_color: Some("gold".to_string()),
+            ..Default::default()
+        };
+        slip_writer.write_page(&[great_luck.to_ref(), great_luck.to_ref()])?;
+
+        let fill_of = |slip_writer: &FortuneSlipWriter, id| {
+            slip_writer
+                .svg_editor
+                .get_elem_with_id(id)
+                .unwrap()
+                .attr
+                .get("fill")
+                .cloned()
+        };
+        assert_eq!(fill_of(&slip_writer, "header_1"), Some("red".to_string()));
+        assert_eq!(fill_of(&slip_writer, "luck_2"), Some("gold".to_string()));
+
+        slip_writer.write_page(&[fortune_text.to_ref()])?;
+
+        assert_eq!(fill_of(&slip_writer, "header_1"), None);
+        assert_eq!(fill_of(&slip_writer, "luck_2"), None);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_write_page_fills_placeholders(fortune_text: &FortuneSlipTextOwned) -> Result<()> {
+        let svg = TWO_SLIPS_SVG
+            .replace(
+                r#"<text id="love_1"><tspan>love_1</tspan></text>"#,
+                r#"<text id="love_1"><tspan>love_1</tspan></text>
+                   <text id="number_1"><tspan>{{event_name}} #{{slip_number}}</tspan></text>"#,
+            )
+            .replace(
+                r#"<text id="love_2"><tspan>love_2</tspan></text>"#,
+                r#"<text id="love_2"><tspan>love_2</tspan></text>
+                   <text id="number_2"><tspan>{{luck.eng}} #{{slip_number}}</tspan></text>"#,
+            );
+        let temp_file = test_utils::create_temp_file("two_slips.svg", &svg);
+        let svg_editor = SvgEditor::open(&temp_file.path)?;
+        let mut slip_writer = FortuneSlipWriter::new(svg_editor, &fortune_categories(), None)?;
+        slip_writer.set_variables(HashMap::from([(
+            "event_name".to_string(),
+            "Festival".to_string(),
+        )]));
+        let text_of = |slip_writer: &FortuneSlipWriter, id| {
+            slip_writer
+                .svg_editor
+                .get_elem_with_id(id)
+                .unwrap()
+                .text
+                .clone()
+        };
+
+        slip_writer.write_page(&[fortune_text.to_ref(), fortune_text.to_ref()])?;
+        assert_eq!(text_of(&slip_writer, "number_1"), "Festival #1");
+        assert_eq!(text_of(&slip_writer, "number_2"), "luck_level_text #2");
+
+        slip_writer.write_page(&[fortune_text.to_ref()])?;
+        assert_eq!(text_of(&slip_writer, "number_1"), "Festival #3");
+        assert_eq!(text_of(&slip_writer, "number_2"), " #");
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_write_page_numbers_slips_with_serials_and_qr_codes(
+        fortune_text: &FortuneSlipTextOwned,
+    ) -> Result<()> {
+        let slip = |nr: usize| {
+            format!(
+                r#"<g data-slip="{nr}">
+                    <text id="header_{nr}"><tspan>header_{nr}</tspan></text>
+                    <text id="luck_{nr}"><tspan>luck_level_{nr}</tspan></text>
+                    <text id="general_{nr}"><tspan>general_{nr}</tspan></text>
+                    <text id="health_{nr}"><tspan>health_{nr}</tspan></text>
+                    <text id="love_{nr}"><tspan>love_{nr}</tspan></text>
+                    <text id="serial_{nr}"><tspan>No. {{{{serial}}}}</tspan></text>
+                    <rect id="qr_{nr}" data-qr="" x="0" y="0" width="20" height="20"/>
+                </g>"#
+            )
+        };
+        let svg = format!("<svg height='200' width='100'>{}{}</svg>", slip(1), slip(2));
+        let temp_file = test_utils::create_temp_file("serials.svg", &svg);
+        let svg_editor = SvgEditor::open(&temp_file.path)?;
+        let mut slip_writer = FortuneSlipWriter::new(svg_editor, &fortune_categories(), None)?;
+        slip_writer.set_serials(SerialSettings {
+            start: 41,
+            digits: 5,
+            qr_content: "https://example.com/{{serial}}".to_string(),
+        });
+        let text_of = |slip_writer: &FortuneSlipWriter, id| {
+            slip_writer
+                .svg_editor
+                .get_elem_with_id(id)
+                .unwrap()
+                .text
+                .clone()
+        };
+        let qr_path_of = |slip_writer: &FortuneSlipWriter, id| {
+            slip_writer
+                .svg_editor
+                .get_attr_of_elem(id, "d")
+                .unwrap()
+                .to_string()
+        };
+
+        slip_writer.write_page(&[fortune_text.to_ref(), fortune_text.to_ref()])?;
+        assert_eq!(text_of(&slip_writer, "serial_1"), "No. 00041");
+        assert_eq!(text_of(&slip_writer, "serial_2"), "No. 00042");
+        let qr_path_2 = qr_path_of(&slip_writer, "qr_2");
+        assert!(!qr_path_2.is_empty());
+        assert_ne!(qr_path_of(&slip_writer, "qr_1"), qr_path_2);
+
+        slip_writer.write_page(&[fortune_text.to_ref()])?;
+        assert_eq!(text_of(&slip_writer, "serial_1"), "No. 00043");
+        assert_eq!(text_of(&slip_writer, "serial_2"), "No. ");
+        assert_eq!(qr_path_of(&slip_writer, "qr_2"), "");
+
+        // A reprinted slip keeps its serial.
+        let reprinted = FortuneSlipTextRef {
+            serial: Some("00042"),
+            ..fortune_text.to_ref()
+        };
+        slip_writer.write_page(&[reprinted])?;
+        assert_eq!(text_of(&slip_writer, "serial_1"), "No. 00042");
+        assert_eq!(qr_path_of(&slip_writer, "qr_1"), qr_path_2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nr_slips_from_settings_must_split_template_evenly() -> Result<()> {
+        let temp_file = test_utils::create_temp_file("two_slips.svg", TWO_SLIPS_SVG);
+
+        let svg_editor = SvgEditor::open(&temp_file.path)?;
+        assert!(FortuneSlipWriter::new(svg_editor, &fortune_categories(), Some(3)).is_err());
+
+        let svg_editor = SvgEditor::open(&temp_file.path)?;
+        let slip_writer = FortuneSlipWriter::new(svg_editor, &fortune_categories(), Some(2))?;
+        assert_eq!(slip_writer.nr_slips_per_page(), 2);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_write_page_two_slip_template(fortune_text: &FortuneSlipTextOwned) -> Result<()> {
+        let temp_file = test_utils::create_temp_file("two_slips.svg", TWO_SLIPS_SVG);
+        let svg_editor = SvgEditor::open(&temp_file.path)?;
+        let slip_writer = FortuneSlipWriter::new(svg_editor, &fortune_categories(), None)?;
+
+        test_write_page_x_fortunes(slip_writer, fortune_text, 1)
+    }
+
+    #[test]
+    fn test_write_slip_wraps_long_fortune() -> Result<()> {
+        let svg = TWO_SLIPS_SVG.replace(
+            r#"<text id="love_1">"#,
+            r#"<text id="love_1" font-family="Dosis" font-size="12" data-wrap-width="60">"#,
+        );
+        let temp_file = test_utils::create_temp_file("two_slips.svg", &svg);
+        let svg_editor = SvgEditor::open(&temp_file.path)?;
+        let mut slip_writer = FortuneSlipWriter::new(svg_editor, &fortune_categories(), None)?;
+
+        let long_fortune = "The person you are waiting for will come, but later than you hope";
+        let fortune_text = FortuneSlipTextOwned {
+            header: "header".to_string(),
+            luck_level: "luck".to_string(),
+            style: LuckLevelStyle::default(),
+            category_to_fortune: fortune_categories()
+                .into_iter()
+                .map(|category| (category, long_fortune.to_string()))
+                .collect(),
+        };
+        slip_writer.write_to_slip(0, &fortune_text.to_ref())?;
+
+        let love_text = &slip_writer.svg_editor.get_elem_with_id("love_1")?.text;
+        assert!(love_text.lines().count() > 1);
+        assert_eq!(love_text.replace('\n', " "), long_fortune);
+
+        let general_text = &slip_writer.svg_editor.get_elem_with_id("general_1")?.text;
+        assert_eq!(general_text, long_fortune);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_slip_shrinks_long_fortune() -> Result<()> {
+        let svg = TWO_SLIPS_SVG.replace(
+            r#"<text id="love_1">"#,
+            r#"<text id="love_1" font-family="Dosis" font-size="12" data-wrap-width="80"
+                data-wrap-height="30" data-min-font-size="4">"#,
+        );
+        let temp_file = test_utils::create_temp_file("two_slips.svg", &svg);
+        let svg_editor = SvgEditor::open(&temp_file.path)?;
+        let mut slip_writer = FortuneSlipWriter::new(svg_editor, &fortune_categories(), None)?;
+
+        let fortune_with_love = |love: &str| FortuneSlipTextOwned {
+            header: "header".to_string(),
+            luck_level: "luck".to_string(),
+            style: LuckLevelStyle::default(),
+            category_to_fortune: fortune_categories()
+                .into_iter()
+                .map(|category| (category, love.to_string()))
+                .collect(),
+        };
+
+        let long_fortune = "The person you are waiting for will come, but later than you hope";
+        slip_writer.write_to_slip(0, &fortune_with_love(long_fortune).to_ref())?;
+
+        let love_elem = slip_writer.svg_editor.get_elem_with_id("love_1")?;
+        let font_size: f32 = love_elem.attr["font-size"].parse()?;
+        assert!(font_size < 12.0);
+        assert!(font_size >= 4.0);
+
+        // The next fortune starts again from the font size in the template.
+        slip_writer.write_to_slip(0, &fortune_with_love("Soon").to_ref())?;
+
+        let love_elem = slip_writer.svg_editor.get_elem_with_id("love_1")?;
+        assert_eq!(love_elem.attr["font-size"], "12");
+        assert_eq!(love_elem.text, "Soon");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_slip_min_font_size_from_settings() -> Result<()> {
+        let svg = TWO_SLIPS_SVG.replace(
+            r#"<text id="love_1">"#,
+            r#"<text id="love_1" font-family="Dosis" font-size="12" data-wrap-width="80"
+                data-wrap-height="12">"#,
+        );
+        let temp_file = test_utils::create_temp_file("two_slips.svg", &svg);
+        let svg_editor = SvgEditor::open(&temp_file.path)?;
+        let mut slip_writer = FortuneSlipWriter::new(svg_editor, &fortune_categories(), None)?;
+        slip_writer.set_min_font_size(Some(10.0));
+
+        let long_fortune = "The person you are waiting for will come, but later than you hope";
+        let fortune_text = FortuneSlipTextOwned {
+            header: "header".to_string(),
+            luck_level: "luck".to_string(),
+            style: LuckLevelStyle::default(),
+            category_to_fortune: fortune_categories()
+                .into_iter()
+                .map(|category| (category, long_fortune.to_string()))
+                .collect(),
+        };
+
+        // The fortune does not fit, so it is written at the minimum font size.
+        slip_writer.write_to_slip(0, &fortune_text.to_ref())?;
+
+        let love_elem = slip_writer.svg_editor.get_elem_with_id("love_1")?;
+        assert_eq!(love_elem.attr["font-size"], "10");
+        assert_eq!(love_elem.text.replace('\n', " "), long_fortune);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fits() -> Result<()> {
+        let svg = TWO_SLIPS_SVG
+            .replace(
+                r#"<text id="love_2">"#,
+                r#"<text id="love_2" font-family="Dosis" font-size="12" data-wrap-width="80"
+                    data-wrap-height="12">"#,
+            )
+            .replace(
+                r#"<text id="health_1">"#,
+                r#"<text id="health_1" x="10" y="50" font-family="Dosis" font-size="12">"#,
+            )
+            .replace(
+                r#"<text id="health_2">"#,
+                r#"<text id="health_2" x="10" y="150" font-family="Dosis" font-size="12">"#,
+            );
+        let temp_file = test_utils::create_temp_file("two_slips.svg", &svg);
+        let svg_editor = SvgEditor::open(&temp_file.path)?;
+        let slip_writer = FortuneSlipWriter::new(svg_editor, &fortune_categories(), None)?;
+
+        let long_fortune = "The person you are waiting for will come, but later than you hope";
+
+        assert!(slip_writer.fits("love", "Soon", &temp_file.path)?);
+        assert!(!slip_writer.fits("love", long_fortune, &temp_file.path)?);
+        assert!(slip_writer.fits("health", "Soon", &temp_file.path)?);
+        assert!(!slip_writer.fits("health", long_fortune, &temp_file.path)?);
+        assert!(slip_writer.fits("wealth", "Soon", &temp_file.path).is_err());
+
+        Ok(())
     }
 
     #[rstest]
@@ -292,7 +1055,7 @@ mod tests {
             .map(|_| fortune_text.to_ref())
             .collect::<Vec<_>>();
 
-        for i in 0..NR_SLIPS_PER_PAGE {
+        for i in 0..slip_writer.nr_slips_per_page() {
             let fortune_text_slip = retrieve_text_for_slip_id(&slip_writer, i)?;
             assert_ne!(fortune_text_slip, *fortune_text);
         }
@@ -304,6 +1067,11 @@ mod tests {
             assert_eq!(fortune_text_slip, *fortune_text);
         }
 
+        for i in nr_fortunes..slip_writer.nr_slips_per_page() {
+            let fortune_text_slip = retrieve_text_for_slip_id(&slip_writer, i)?;
+            assert_eq!(fortune_text_slip.header, "");
+        }
+
         Ok(())
     }
 
@@ -330,6 +1098,7 @@ mod tests {
         Ok(FortuneSlipTextOwned {
             header: header_elem.text.clone(),
             luck_level: luck_level_elem.text.clone(),
+            style: LuckLevelStyle::default(),
             category_to_fortune,
         })
     }
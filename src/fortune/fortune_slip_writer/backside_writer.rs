@@ -0,0 +1,469 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    fortune::{
+        fortune_data::fortune_settings::{DuplexMode, LuckLevelStyle, SerialSettings},
+        fortune_splitter::FortuneSlipTextRef,
+    },
+    svg::{
+        font_metrics::format_length,
+        svg_bounds::{Bounds, PageBounds, UNITS_PER_MM},
+        svg_editor::SvgEditor,
+    },
+};
+
+use super::{
+    placeholders::{fill_placeholders, retrieve_placeholder_elems, substitute, PlaceholderElem},
+    qr_code::{retrieve_qr_elems, write_qr_code, QrElem},
+    slip_styler::{apply_style, retrieve_styled_elems, StyledElem},
+    slip_variables,
+    svg_keys::{measure_slips, SLIP_ATTR},
+};
+
+/// Writes the backside of every front page, with the content of every slip, such as its serial
+/// or luck stamp, behind the front of that slip.
+///
+/// The slots of the backside are marked with `data-slip`, behind the slips of the front once the
+/// sheet is turned over. Every slip of the front gets the slot its centre ends up in, so the slot
+/// on the left of the backside gets the slip on the right of the front and the other way around.
+#[derive(Debug)]
+pub struct BacksideWriter {
+    svg_editor: SvgEditor,
+    placeholder_elems: Vec<PlaceholderElem>,
+    styled_elems: Vec<StyledElem>,
+    qr_elems: Vec<QrElem>,
+    /// For every slot of the backside, the index of the slip on the front page it is behind, if
+    /// any.
+    slot_to_slip: Vec<Option<usize>>,
+    serials: SerialSettings,
+    variables: HashMap<String, String>,
+}
+
+impl BacksideWriter {
+    /// Opens a writer for the backside template, or returns `None` when the backside is the same
+    /// for every page, without placeholders, styled elements or QR codes.
+    ///
+    /// `front_slips` are the bounds of the slips of the front page, by their index, and `duplex`
+    /// is how the sheet is turned over to print the backside.
+    pub fn open(
+        template: &Path,
+        front_slips: &PageBounds<usize>,
+        duplex: DuplexMode,
+    ) -> Result<Option<BacksideWriter>> {
+        let svg_editor = SvgEditor::open(template)?;
+
+        let page_bounds = measure_slips(&svg_editor, template)?;
+        check_same_page(&page_bounds, front_slips)
+            .map_err(|e| anyhow!("In the backside '{}': {e}", template.display()))?;
+        let (slip_numbers, slots): (Vec<_>, Vec<_>) = page_bounds.elems.into_iter().unzip();
+        let placeholder_elems = retrieve_placeholder_elems(&svg_editor, &[], &slip_numbers)?;
+        let styled_elems = retrieve_styled_elems(&svg_editor, &[], &slip_numbers)?;
+        let qr_elems = retrieve_qr_elems(&svg_editor, &[], &slip_numbers)?;
+
+        if placeholder_elems.is_empty() && styled_elems.is_empty() && qr_elems.is_empty() {
+            return Ok(None);
+        }
+
+        let slot_to_slip = slots_behind_slips(&slots, page_bounds.width, front_slips, duplex)
+            .map_err(|e| anyhow!("In the backside '{}': {e}", template.display()))?;
+
+        Ok(Some(BacksideWriter {
+            svg_editor,
+            placeholder_elems,
+            styled_elems,
+            qr_elems,
+            slot_to_slip,
+            serials: SerialSettings::default(),
+            variables: HashMap::new(),
+        }))
+    }
+
+    /// Sets the variables to fill the `{{variable}}` placeholders of the template with.
+    pub fn set_variables(&mut self, variables: HashMap<String, String>) {
+        self.variables = variables;
+    }
+
+    /// Sets how the slips are numbered and what their QR codes encode.
+    pub fn set_serials(&mut self, serials: SerialSettings) {
+        self.serials = serials;
+    }
+
+    /// Writes the backside of a front page with the slips.
+    ///
+    /// `first_slip_idx` counts the slips before the page, for the number and serial of the
+    /// slips, and `page_number` is the number of the front page.
+    pub fn write_page(
+        &mut self,
+        fortune_texts: &[FortuneSlipTextRef],
+        first_slip_idx: usize,
+        page_number: usize,
+    ) -> Result<()> {
+        let mut page_variables = self.variables.clone();
+        page_variables.insert("page_number".to_string(), page_number.to_string());
+
+        let slip_of_slot = |slot: usize| {
+            self.slot_to_slip[slot]
+                .and_then(|slip_idx| Some((slip_idx, fortune_texts.get(slip_idx)?)))
+        };
+        let slot_variables = |slot: usize| {
+            let slip = slip_of_slot(slot);
+            slip_variables(
+                first_slip_idx + slip.map_or(0, |(slip_idx, _)| slip_idx),
+                slip.map(|(_, fortune_text)| fortune_text),
+                &self.serials,
+                &page_variables,
+            )
+        };
+
+        for placeholder_elem in &self.placeholder_elems {
+            let variables = match placeholder_elem.slip_idx {
+                Some(slot) => slot_variables(slot),
+                None => page_variables.clone(),
+            };
+            fill_placeholders(&mut self.svg_editor, placeholder_elem, &variables)?;
+        }
+
+        for qr_elem in &self.qr_elems {
+            let content = match slip_of_slot(qr_elem.slip_idx) {
+                Some(_) => {
+                    let content = qr_elem.content.as_ref().unwrap_or(&self.serials.qr_content);
+                    substitute(content, &slot_variables(qr_elem.slip_idx))
+                        .map_err(|e| anyhow!("In the QR code '{}': {e}", qr_elem.id))?
+                }
+                None => String::new(),
+            };
+            write_qr_code(&mut self.svg_editor, qr_elem, &content)?;
+        }
+
+        let no_style = LuckLevelStyle::default();
+        for styled_elem in &self.styled_elems {
+            let style = slip_of_slot(styled_elem.slip_idx)
+                .map_or(&no_style, |(_, fortune_text)| fortune_text.style);
+            apply_style(&mut self.svg_editor, styled_elem, style)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn save_to_svg(&self, svg_path: &impl AsRef<Path>) -> Result<()> {
+        self.svg_editor.save_to_svg(svg_path)
+    }
+}
+
+/// How far the centre of a slip may lie outside the slot behind it, in user units.
+const SLOT_TOLERANCE: f32 = 1.0;
+
+/// Checks that the backside is as large as the front page and has a slot for every slip, if it
+/// has slots at all, e.g. when the front is laid out on other paper than the backside is drawn on.
+fn check_same_page(back: &PageBounds<usize>, front_slips: &PageBounds<usize>) -> Result<()> {
+    let same_size = (back.width - front_slips.width).abs() <= SLOT_TOLERANCE
+        && (back.height - front_slips.height).abs() <= SLOT_TOLERANCE;
+    if !same_size {
+        let in_mm = |length: f32| format_length(length / UNITS_PER_MM);
+        bail!(
+            "The page is {} × {} mm, but the front pages are {} × {} mm. Draw the backside for \
+             the same paper as the front.",
+            in_mm(back.width),
+            in_mm(back.height),
+            in_mm(front_slips.width),
+            in_mm(front_slips.height)
+        );
+    }
+
+    let (nr_slots, nr_slips) = (back.elems.len(), front_slips.elems.len());
+    if nr_slots > 0 && nr_slots != nr_slips {
+        bail!(
+            "There are {nr_slots} slots with {SLIP_ATTR}, but the front pages have {nr_slips} \
+             slips. Draw a slot behind every slip."
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns for every slot of the backside the index of the slip of the front it is behind, if
+/// any.
+///
+/// The centre of every slip of the front is turned over to the backside, and has to end up in a
+/// slot of its own.
+fn slots_behind_slips(
+    slots: &[Bounds],
+    back_width: f32,
+    front_slips: &PageBounds<usize>,
+    duplex: DuplexMode,
+) -> Result<Vec<Option<usize>>> {
+    let distance = |a: (f32, f32), b: (f32, f32)| (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2);
+    let mut slot_to_slip = vec![None; slots.len()];
+
+    for (slip_idx, slip) in &front_slips.elems {
+        let (x, y) = turn_over(slip.center(), back_width, duplex);
+        let point = Bounds {
+            x,
+            y,
+            width: 0.0,
+            height: 0.0,
+        };
+
+        let slot = (0..slots.len())
+            .filter(|&slot| slots[slot].contains(&point, SLOT_TOLERANCE))
+            .min_by(|&a, &b| {
+                let distance_a = distance(slots[a].center(), (x, y));
+                let distance_b = distance(slots[b].center(), (x, y));
+                distance_a.total_cmp(&distance_b)
+            })
+            .ok_or(anyhow!(
+                "No slot with {SLIP_ATTR} is behind slip {} of the front, which ends up at ({x}, \
+                 {y}) on the backside",
+                slip_idx + 1
+            ))?;
+
+        if let Some(other_idx) = slot_to_slip[slot] {
+            bail!(
+                "Slip {} and {} of the front are both behind the same slot with {SLIP_ATTR}",
+                other_idx + 1,
+                slip_idx + 1
+            );
+        }
+        slot_to_slip[slot] = Some(*slip_idx);
+    }
+
+    Ok(slot_to_slip)
+}
+
+/// Returns where a point of the front page ends up on the backside template, once the sheet is
+/// turned over.
+///
+/// Every mode swaps left and right. The printer turns the sheet over its long edge, left to
+/// right, and the stack of a manual duplex is turned the same way. Over its short edge, the sheet
+/// is turned top to bottom, but its back is turned 180° in the PDF as well, see
+/// `arrange_for_duplex`, so the two cancel out vertically.
+fn turn_over((x, y): (f32, f32), width: f32, duplex: DuplexMode) -> (f32, f32) {
+    match duplex {
+        DuplexMode::LongEdge
+        | DuplexMode::ShortEdge
+        | DuplexMode::Manual
+        | DuplexMode::FrontsOnly => (width - x, y),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use crate::{
+        fortune::{
+            fortune_data::fortune_settings::{DuplexMode, LuckLevelStyle, SerialSettings},
+            fortune_splitter::FortuneSlipTextRef,
+        },
+        svg::{
+            svg_bounds::{Bounds, PageBounds},
+            svg_editor::SvgEditor,
+        },
+    };
+
+    use super::{check_same_page, slots_behind_slips, turn_over, BacksideWriter};
+
+    /// Four slots in two rows of two, with the serial and luck stamp of every slip.
+    const BACKSIDE_SVG: &str = r#"
+        <svg width="200" height="200" xmlns="http://www.w3.org/2000/svg">
+            <g data-slip="0">
+                <rect id="stamp_0" data-style="accent" x="10" y="10" width="80" height="80"/>
+                <text id="serial_0" x="20" y="50"><tspan>{{serial}}</tspan></text>
+            </g>
+            <g data-slip="1">
+                <rect id="stamp_1" data-style="accent" x="110" y="10" width="80" height="80"/>
+                <text id="serial_1" x="120" y="50"><tspan>{{serial}}</tspan></text>
+            </g>
+            <g data-slip="2">
+                <rect id="stamp_2" data-style="accent" x="10" y="110" width="80" height="80"/>
+                <text id="serial_2" x="20" y="150"><tspan>{{serial}}</tspan></text>
+            </g>
+            <g data-slip="3">
+                <rect id="stamp_3" data-style="accent" x="110" y="110" width="80" height="80"/>
+                <text id="serial_3" x="120" y="150"><tspan>{{serial}}</tspan></text>
+            </g>
+            <text id="page"><tspan>Page {{page_number}}</tspan></text>
+        </svg>"#;
+
+    fn bounds(x: f32, y: f32, width: f32, height: f32) -> Bounds {
+        Bounds {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// A front page of 200 by 200 with the slips at the given bounds.
+    fn front(slips: Vec<Bounds>) -> PageBounds<usize> {
+        PageBounds {
+            width: 200.0,
+            height: 200.0,
+            elems: slips.into_iter().enumerate().collect(),
+        }
+    }
+
+    #[rstest]
+    #[case::long_edge(DuplexMode::LongEdge)]
+    #[case::short_edge(DuplexMode::ShortEdge)]
+    #[case::manual(DuplexMode::Manual)]
+    fn test_turn_over_mirrors_left_to_right(#[case] duplex: DuplexMode) {
+        // The short edge back is turned 180° in the PDF, which undoes turning it top to bottom.
+        assert_eq!(turn_over((30.0, 50.0), 200.0, duplex), (170.0, 50.0));
+    }
+
+    #[rstest]
+    #[case::long_edge(DuplexMode::LongEdge)]
+    #[case::short_edge(DuplexMode::ShortEdge)]
+    fn test_slots_behind_asymmetric_slips(#[case] duplex: DuplexMode) -> Result<()> {
+        // A narrow and a wide slip on the first row, and the other way around on the second.
+        let front = front(vec![
+            bounds(0.0, 0.0, 60.0, 100.0),
+            bounds(60.0, 0.0, 140.0, 100.0),
+            bounds(0.0, 100.0, 140.0, 100.0),
+            bounds(140.0, 100.0, 60.0, 100.0),
+        ]);
+        // The backside has the slots the other way around.
+        let slots = [
+            bounds(0.0, 0.0, 140.0, 100.0),
+            bounds(140.0, 0.0, 60.0, 100.0),
+            bounds(0.0, 100.0, 60.0, 100.0),
+            bounds(60.0, 100.0, 140.0, 100.0),
+        ];
+
+        let slot_to_slip = slots_behind_slips(&slots, 200.0, &front, duplex)?;
+
+        assert_eq!(slot_to_slip, vec![Some(1), Some(0), Some(3), Some(2)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_slots_behind_slips_without_slot_expect_error() {
+        let front = front(vec![
+            bounds(0.0, 0.0, 60.0, 200.0),
+            bounds(60.0, 0.0, 80.0, 200.0),
+        ]);
+        // The slots are mirrored around the middle of the page, so the middle slip has no slot.
+        let slots = [
+            bounds(0.0, 0.0, 60.0, 200.0),
+            bounds(140.0, 0.0, 60.0, 200.0),
+        ];
+
+        let error = slots_behind_slips(&slots, 200.0, &front, DuplexMode::LongEdge).unwrap_err();
+
+        assert!(error.to_string().contains("behind slip 2 of the front"));
+    }
+
+    #[test]
+    fn test_slots_behind_slips_sharing_a_slot_expect_error() {
+        let front = front(vec![
+            bounds(0.0, 0.0, 100.0, 100.0),
+            bounds(0.0, 100.0, 100.0, 100.0),
+        ]);
+        let slots = [bounds(100.0, 0.0, 100.0, 200.0)];
+
+        assert!(slots_behind_slips(&slots, 200.0, &front, DuplexMode::LongEdge).is_err());
+    }
+
+    #[rstest]
+    #[case::other_page_size(210.0, 200.0, 2)]
+    #[case::fewer_slots(200.0, 200.0, 1)]
+    #[case::more_slots(200.0, 200.0, 3)]
+    fn test_check_same_page_expect_error(
+        #[case] width: f32,
+        #[case] height: f32,
+        #[case] nr_slots: usize,
+    ) {
+        let front = front(vec![
+            bounds(0.0, 0.0, 100.0, 200.0),
+            bounds(100.0, 0.0, 100.0, 200.0),
+        ]);
+        let back = PageBounds {
+            width,
+            height,
+            elems: (0..nr_slots)
+                .map(|slot| (slot, bounds(0.0, 0.0, 10.0, 10.0)))
+                .collect(),
+        };
+
+        assert!(check_same_page(&back, &front).is_err());
+        // A backside without slots only has to be as large as the front.
+        let back = PageBounds {
+            elems: vec![],
+            ..back
+        };
+        assert_eq!(check_same_page(&back, &front).is_ok(), width == 200.0);
+    }
+
+    #[test]
+    fn test_open_backside_without_slip_content() -> Result<()> {
+        let a4 = PageBounds {
+            width: 595.0,
+            height: 842.0,
+            elems: vec![],
+        };
+        let backside = BacksideWriter::open(
+            "test_utils/data/fortune_template/omikuji_backside_long.svg".as_ref(),
+            &a4,
+            DuplexMode::LongEdge,
+        )?;
+        assert!(backside.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_page_mirrors_slots() -> Result<()> {
+        let temp_file = test_utils::create_temp_file("backside.svg", BACKSIDE_SVG);
+        // Two rows of two slips, at the same places as the slots.
+        let front = front(vec![
+            bounds(10.0, 10.0, 80.0, 80.0),
+            bounds(110.0, 10.0, 80.0, 80.0),
+            bounds(10.0, 110.0, 80.0, 80.0),
+            bounds(110.0, 110.0, 80.0, 80.0),
+        ]);
+        let mut backside =
+            BacksideWriter::open(&temp_file.path, &front, DuplexMode::LongEdge)?.unwrap();
+        backside.set_serials(SerialSettings {
+            start: 11,
+            ..Default::default()
+        });
+
+        let red = LuckLevelStyle {
+            accent_color: Some("red".to_string()),
+            ..Default::default()
+        };
+        let no_style = LuckLevelStyle::default();
+        let slip = |luck_level, style| FortuneSlipTextRef {
+            header: "",
+            luck_level,
+            style,
+            category_to_fortune: HashMap::new(),
+            serial: None,
+        };
+        // The third slip is missing, so its slot stays empty.
+        let slips = [slip("Great Luck", &red), slip("Bad Luck", &no_style)];
+
+        backside.write_page(&slips, 4, 3)?;
+        let svg_path = temp_file.dir.path().join("written.svg");
+        backside.save_to_svg(&svg_path)?;
+
+        let svg_editor = SvgEditor::open(svg_path)?;
+        let text_of = |id| svg_editor.get_elem_with_id(id).unwrap().text.clone();
+        assert_eq!(
+            ["serial_0", "serial_1", "serial_2", "serial_3"].map(text_of),
+            ["16", "15", "", ""]
+        );
+        assert_eq!(text_of("page"), "Page 3");
+        assert_eq!(svg_editor.get_attr_of_elem("stamp_1", "fill"), Some("red"));
+        assert_eq!(svg_editor.get_attr_of_elem("stamp_0", "fill"), None);
+
+        Ok(())
+    }
+}
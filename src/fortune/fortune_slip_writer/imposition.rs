@@ -0,0 +1,268 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+
+use crate::{
+    fortune::fortune_data::fortune_settings::{Imposition, Orientation},
+    svg::{font_metrics::format_length, svg_bounds::UNITS_PER_MM, svg_editor::SvgEditor},
+};
+
+use super::{
+    svg_keys::{marked_slip_numbers, SLIP_ATTR},
+    text_wrapper::WRAP_RECT_ATTR,
+};
+
+/// Lays out the template with a single slip at `template` on the paper of the imposition.
+///
+/// Returns a template with a copy of the slip, marked with `data-slip`, at every place on the
+/// paper. The slips are centred on the paper.
+pub fn impose_template(template: &Path, imposition: &Imposition) -> Result<SvgEditor> {
+    let svg_editor = SvgEditor::open(template)?;
+    svg_editor.check_units_are_points(template)?;
+
+    let nr_slips = marked_slip_numbers(&svg_editor)?.len();
+    if nr_slips > 1 {
+        bail!(
+            "The template '{}' has {nr_slips} slips, but only a template with a single slip can \
+             be laid out on the paper.",
+            template.display()
+        );
+    }
+
+    let slip_size = svg_editor.get_page_size(template)?;
+    let layout = layout(slip_size, imposition)?;
+
+    svg_editor.impose(
+        layout.paper_size,
+        slip_size,
+        &layout.positions,
+        SLIP_ATTR,
+        &[WRAP_RECT_ATTR],
+    )
+}
+
+/// The slips on the paper, in the units of the template.
+#[derive(Debug, PartialEq)]
+struct Layout {
+    paper_size: (f32, f32),
+    /// The top left corner of every slip, row by row.
+    positions: Vec<(f32, f32)>,
+}
+
+/// Returns how the slips are laid out on the paper.
+///
+/// With `Orientation::Auto`, the paper is turned to landscape only when more slips fit on it.
+fn layout(slip_size: (f32, f32), imposition: &Imposition) -> Result<Layout> {
+    let (width, height) = imposition.paper.size_in_mm();
+    let (short, long) = (
+        width.min(height) * UNITS_PER_MM,
+        width.max(height) * UNITS_PER_MM,
+    );
+    let orientations = match imposition.orientation {
+        Orientation::Portrait => vec![(short, long)],
+        Orientation::Landscape => vec![(long, short)],
+        Orientation::Auto => vec![(short, long), (long, short)],
+    };
+
+    let mut layouts = vec![];
+    let mut first_error = None;
+    for paper_size in orientations {
+        match grid(paper_size, slip_size, imposition) {
+            Ok(positions) => layouts.push(Layout {
+                paper_size,
+                positions,
+            }),
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    // The first of the layouts with the most slips, so upright wins a tie.
+    layouts.reverse();
+    match layouts
+        .into_iter()
+        .max_by_key(|layout| layout.positions.len())
+    {
+        Some(layout) => Ok(layout),
+        None => Err(first_error.expect("at least one orientation")),
+    }
+}
+
+/// Returns the top left corner of every slip in the grid of the imposition, on the paper.
+fn grid(
+    paper_size: (f32, f32),
+    slip_size: (f32, f32),
+    imposition: &Imposition,
+) -> Result<Vec<(f32, f32)>> {
+    let margin = imposition.margin * UNITS_PER_MM;
+    let gutter = imposition.gutter * UNITS_PER_MM;
+    let (room_x, room_y) = (paper_size.0 - 2.0 * margin, paper_size.1 - 2.0 * margin);
+
+    // Allows for rounding, e.g. of slips that are exactly as wide as the room.
+    let tolerance = 0.01;
+
+    let fit = |room: f32, slip_length: f32| {
+        ((room + gutter + tolerance) / (slip_length + gutter)).max(0.0)
+    };
+    let columns = imposition
+        .columns
+        .unwrap_or_else(|| fit(room_x, slip_size.0) as usize);
+    let rows = imposition
+        .rows
+        .unwrap_or_else(|| fit(room_y, slip_size.1) as usize);
+
+    let length_of = |count: usize, slip_length: f32| {
+        count as f32 * slip_length + count.saturating_sub(1) as f32 * gutter
+    };
+    let (used_x, used_y) = (
+        length_of(columns, slip_size.0),
+        length_of(rows, slip_size.1),
+    );
+
+    if columns == 0 || rows == 0 || used_x > room_x + tolerance || used_y > room_y + tolerance {
+        let in_mm = |length: f32| format_length(length / UNITS_PER_MM);
+        bail!(
+            "{columns} × {rows} slips of {} × {} mm do not fit on paper of {} × {} mm with a \
+             margin of {} mm and a gutter of {} mm",
+            in_mm(slip_size.0),
+            in_mm(slip_size.1),
+            in_mm(paper_size.0),
+            in_mm(paper_size.1),
+            format_length(imposition.margin),
+            format_length(imposition.gutter)
+        );
+    }
+
+    let left = (paper_size.0 - used_x) / 2.0;
+    let top = (paper_size.1 - used_y) / 2.0;
+
+    Ok((0..rows)
+        .flat_map(|row| {
+            (0..columns).map(move |column| {
+                (
+                    left + column as f32 * (slip_size.0 + gutter),
+                    top + row as f32 * (slip_size.1 + gutter),
+                )
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use crate::{
+        fortune::{
+            fortune_data::fortune_settings::{Imposition, Orientation, PaperName, PaperSize},
+            fortune_slip_writer::svg_keys::measure_slips,
+        },
+        svg::svg_bounds::UNITS_PER_MM,
+    };
+
+    use super::{grid, impose_template, layout};
+
+    const SINGLE_SLIP_TEMPLATE: &str =
+        "test_utils/data/fortune_template/omikuji_single_slip_test.svg";
+
+    /// A paper of 100 × 50 mm, without margins.
+    fn imposition(columns: Option<usize>, rows: Option<usize>, gutter: f32) -> Imposition {
+        Imposition {
+            paper: PaperSize::Custom {
+                width: 100.0,
+                height: 50.0,
+            },
+            columns,
+            rows,
+            gutter,
+            margin: 0.0,
+            ..Default::default()
+        }
+    }
+
+    fn mm(position: (f32, f32)) -> (f32, f32) {
+        let round = |length: f32| (length / UNITS_PER_MM * 10.0).round() / 10.0;
+        (round(position.0), round(position.1))
+    }
+
+    #[rstest]
+    #[case(imposition(None, None, 0.0), vec![(5.0, 0.0), (35.0, 0.0), (65.0, 0.0)])]
+    #[case(imposition(None, None, 5.0), vec![(0.0, 0.0), (35.0, 0.0), (70.0, 0.0)])]
+    #[case(imposition(Some(2), None, 10.0), vec![(15.0, 0.0), (55.0, 0.0)])]
+    fn test_grid(#[case] imposition: Imposition, #[case] expected: Vec<(f32, f32)>) {
+        let slip_size = (30.0 * UNITS_PER_MM, 50.0 * UNITS_PER_MM);
+        let paper_size = (100.0 * UNITS_PER_MM, 50.0 * UNITS_PER_MM);
+
+        let positions = grid(paper_size, slip_size, &imposition).unwrap();
+
+        assert_eq!(positions.into_iter().map(mm).collect::<Vec<_>>(), expected);
+    }
+
+    #[rstest]
+    #[case(imposition(Some(4), None, 0.0))]
+    #[case(imposition(None, Some(0), 0.0))]
+    fn test_grid_that_does_not_fit_expect_error(#[case] imposition: Imposition) {
+        let slip_size = (30.0 * UNITS_PER_MM, 50.0 * UNITS_PER_MM);
+        let paper_size = (100.0 * UNITS_PER_MM, 50.0 * UNITS_PER_MM);
+
+        assert!(grid(paper_size, slip_size, &imposition).is_err());
+    }
+
+    #[rstest]
+    #[case(Orientation::Auto, (210.0, 297.0), 4)]
+    #[case(Orientation::Portrait, (210.0, 297.0), 4)]
+    #[case(Orientation::Landscape, (297.0, 210.0), 2)]
+    fn test_layout_orientation(
+        #[case] orientation: Orientation,
+        #[case] expected_paper: (f32, f32),
+        #[case] expected_slips: usize,
+    ) {
+        let imposition = Imposition {
+            paper: PaperSize::Named(PaperName::A4),
+            orientation,
+            margin: 0.0,
+            ..Default::default()
+        };
+        // Two slips fit across A4 and two slips fit down, or one down on landscape.
+        let slip_size = (100.0 * UNITS_PER_MM, 140.0 * UNITS_PER_MM);
+
+        let layout = layout(slip_size, &imposition).unwrap();
+
+        assert_eq!(mm(layout.paper_size), expected_paper);
+        assert_eq!(layout.positions.len(), expected_slips);
+    }
+
+    #[rstest]
+    #[case(PaperName::A4, 8)]
+    #[case(PaperName::Letter, 6)]
+    #[case(PaperName::A3, 16)]
+    fn test_impose_template(#[case] paper: PaperName, #[case] expected_slips: usize) -> Result<()> {
+        let imposition = Imposition {
+            paper: PaperSize::Named(paper),
+            gutter: 5.0,
+            ..Default::default()
+        };
+
+        let svg_editor = impose_template(SINGLE_SLIP_TEMPLATE.as_ref(), &imposition)?;
+
+        let slips = measure_slips(&svg_editor, SINGLE_SLIP_TEMPLATE.as_ref())?;
+        assert_eq!(slips.elems.len(), expected_slips);
+        // A3 is turned to landscape, where more slips fit.
+        let (short, long) = PaperSize::Named(paper).size_in_mm();
+        let paper_size = (slips.width.min(slips.height), slips.width.max(slips.height));
+        assert_eq!(mm(paper_size), (short, long));
+
+        // Every copy has its own ids, which its references follow.
+        let love = svg_editor.get_elem_with_id("love_1")?;
+        assert_eq!(love.attr["data-wrap-rect"], "love_box_1");
+        assert_eq!(
+            svg_editor.get_attr_of_elem("slip_1", "clip-path"),
+            Some("url(#slip_clip_1)")
+        );
+
+        Ok(())
+    }
+}
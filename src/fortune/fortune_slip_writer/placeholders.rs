@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+use crate::svg::svg_editor::SvgEditor;
+
+use super::{
+    qr_code::QR_ATTR,
+    svg_keys::{slip_of_elem, SvgKeys, SLIP_ATTR},
+};
+
+const PLACEHOLDER_START: &str = "{{";
+const PLACEHOLDER_END: &str = "}}";
+
+/// An element of the template with placeholders in its text or attributes.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PlaceholderElem {
+    pub id: String,
+    /// The slip the element belongs to, or `None` for an element of the whole page.
+    pub slip_idx: Option<usize>,
+    /// The text of a text element as it is in the template, if it has placeholders.
+    pub text: Option<String>,
+    /// The attributes with placeholders as they are in the template.
+    pub attrs: Vec<(String, String)>,
+}
+
+/// Returns whether the text has a placeholder, e.g. `Slip {{slip_number}}`.
+pub fn has_placeholder(text: &str) -> bool {
+    text.contains(PLACEHOLDER_START)
+}
+
+/// Returns the name of the placeholder when the text is nothing but a placeholder, e.g. `header`
+/// for `{{ header }}`.
+pub fn placeholder_name(text: &str) -> Option<&str> {
+    let name = text
+        .trim()
+        .strip_prefix(PLACEHOLDER_START)?
+        .strip_suffix(PLACEHOLDER_END)?
+        .trim();
+
+    (!name.contains(PLACEHOLDER_START) && !name.contains(PLACEHOLDER_END)).then_some(name)
+}
+
+/// Replaces every placeholder in the text by the value of its variable.
+///
+/// Returns an error when a placeholder has no variable, or when it is not closed.
+pub fn substitute(text: &str, variables: &HashMap<String, String>) -> Result<String> {
+    let mut substituted = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find(PLACEHOLDER_START) {
+        let after_start = &rest[start + PLACEHOLDER_START.len()..];
+        let end = after_start.find(PLACEHOLDER_END).ok_or(anyhow!(
+            "Placeholder is not closed with {PLACEHOLDER_END}: {text}"
+        ))?;
+
+        let name = after_start[..end].trim();
+        let value = variables.get(name).ok_or(anyhow!(
+            "Unknown variable '{name}' in: {text}. Add it to the variables in the settings file, \
+             or pass it with --var {name}=..."
+        ))?;
+
+        substituted.push_str(&rest[..start]);
+        substituted.push_str(value);
+        rest = &after_start[end + PLACEHOLDER_END.len()..];
+    }
+
+    substituted.push_str(rest);
+    Ok(substituted)
+}
+
+/// Retrieves the elements with placeholders, except the text elements that hold a field and the
+/// content of the QR codes.
+///
+/// `slip_numbers` are the `data-slip` numbers of the slips, in the order of their indexes.
+pub fn retrieve_placeholder_elems(
+    svg_editor: &SvgEditor,
+    svg_keys_all_slips: &[SvgKeys],
+    slip_numbers: &[usize],
+) -> Result<Vec<PlaceholderElem>> {
+    let is_field = |id: &str| svg_keys_all_slips.iter().any(|x| x.is_field_key(id));
+    let mut placeholder_elems: Vec<PlaceholderElem> = vec![];
+
+    for (text_elem, slip) in svg_editor.get_text_elems_with_inherited_attr(SLIP_ATTR)? {
+        if has_placeholder(&text_elem.text) && !is_field(&text_elem.id) {
+            placeholder_elems.push(PlaceholderElem {
+                id: text_elem.id.clone(),
+                slip_idx: slip_of_elem(&text_elem.id, slip, slip_numbers, svg_keys_all_slips)?,
+                text: Some(text_elem.text.clone()),
+                attrs: vec![],
+            });
+        }
+    }
+
+    for (id, slip) in svg_editor.get_elems_with_attr_containing(PLACEHOLDER_START, SLIP_ATTR)? {
+        let mut attrs: Vec<_> = svg_editor
+            .get_attrs_of_elem(id)
+            .into_iter()
+            .flatten()
+            .filter(|(name, value)| *name != QR_ATTR && has_placeholder(value))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        attrs.sort();
+
+        if attrs.is_empty() {
+            continue;
+        }
+
+        match placeholder_elems.iter_mut().find(|elem| elem.id == id) {
+            Some(placeholder_elem) => placeholder_elem.attrs = attrs,
+            None => placeholder_elems.push(PlaceholderElem {
+                id: id.to_string(),
+                slip_idx: slip_of_elem(id, slip, slip_numbers, svg_keys_all_slips)?,
+                text: None,
+                attrs,
+            }),
+        }
+    }
+
+    Ok(placeholder_elems)
+}
+
+/// Fills in the placeholders of the element, starting from the element as it is in the template.
+pub fn fill_placeholders(
+    svg_editor: &mut SvgEditor,
+    placeholder_elem: &PlaceholderElem,
+    variables: &HashMap<String, String>,
+) -> Result<()> {
+    let in_elem = |text: &str| {
+        substitute(text, variables)
+            .map_err(|e| anyhow!("In the template element '{}': {e}", placeholder_elem.id))
+    };
+
+    if let Some(text) = &placeholder_elem.text {
+        let mut text_elem = svg_editor.get_elem_with_id(&placeholder_elem.id)?.clone();
+        text_elem.text = in_elem(text)?;
+        svg_editor.update_text_elem_by_id(text_elem)?;
+    }
+
+    for (name, value) in &placeholder_elem.attrs {
+        let value = in_elem(value)?;
+        svg_editor.set_attr_of_elem(&placeholder_elem.id, name, Some(&value))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use anyhow::Result;
+    use maplit::hashmap;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use crate::{
+        fortune::fortune_slip_writer::svg_keys::{marked_slip_numbers, retrieve_svg_keys},
+        svg::svg_editor::SvgEditor,
+    };
+
+    use super::{
+        fill_placeholders, placeholder_name, retrieve_placeholder_elems, substitute,
+        PlaceholderElem,
+    };
+
+    fn variables() -> HashMap<String, String> {
+        hashmap! {
+            "event_name".to_string() => "Spring Festival".to_string(),
+            "slip_number".to_string() => "7".to_string(),
+        }
+    }
+
+    #[rstest]
+    #[case("No placeholders", "No placeholders")]
+    #[case("{{event_name}}", "Spring Festival")]
+    #[case(
+        "Slip {{ slip_number }} of {{event_name}}!",
+        "Slip 7 of Spring Festival!"
+    )]
+    fn test_substitute(#[case] text: &str, #[case] expected: &str) {
+        assert_eq!(substitute(text, &variables()).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("{{unknown}}")]
+    #[case("Slip {{slip_number")]
+    fn test_substitute_errors(#[case] text: &str) {
+        assert!(substitute(text, &variables()).is_err());
+    }
+
+    #[rstest]
+    #[case("{{header}}", Some("header"))]
+    #[case(" {{ love }} ", Some("love"))]
+    #[case("Slip {{slip_number}}", None)]
+    #[case("{{a}} {{b}}", None)]
+    fn test_placeholder_name(#[case] text: &str, #[case] expected: Option<&str>) {
+        assert_eq!(placeholder_name(text), expected);
+    }
+
+    #[test]
+    fn test_retrieve_and_fill_placeholders() -> Result<()> {
+        let temp_file = test_utils::create_temp_file(
+            "placeholders.svg",
+            r#"<svg>
+                <text id="title"><tspan>{{event_name}}</tspan></text>
+                <g data-slip="0">
+                    <text id="header_1"><tspan>{{header}}</tspan></text>
+                    <text id="luck_1"><tspan>luck_level_1</tspan></text>
+                    <text id="number_1"><tspan>No. {{slip_number}}</tspan></text>
+                    <a id="link_1" href="https://example.com/{{slip_number}}"/>
+                </g>
+            </svg>"#,
+        );
+        let mut svg_editor = SvgEditor::open(temp_file.path)?;
+        let svg_keys = retrieve_svg_keys(&svg_editor, &[], None)?;
+
+        let placeholder_elems =
+            retrieve_placeholder_elems(&svg_editor, &svg_keys, &marked_slip_numbers(&svg_editor)?)?;
+
+        assert_eq!(svg_keys[0].header_key, "header_1");
+        assert_eq!(
+            placeholder_elems,
+            vec![
+                PlaceholderElem {
+                    id: "title".to_string(),
+                    slip_idx: None,
+                    text: Some("{{event_name}}".to_string()),
+                    attrs: vec![],
+                },
+                PlaceholderElem {
+                    id: "number_1".to_string(),
+                    slip_idx: Some(0),
+                    text: Some("No. {{slip_number}}".to_string()),
+                    attrs: vec![],
+                },
+                PlaceholderElem {
+                    id: "link_1".to_string(),
+                    slip_idx: Some(0),
+                    text: None,
+                    attrs: vec![(
+                        "href".to_string(),
+                        "https://example.com/{{slip_number}}".to_string()
+                    )],
+                },
+            ]
+        );
+
+        for placeholder_elem in &placeholder_elems {
+            fill_placeholders(&mut svg_editor, placeholder_elem, &variables())?;
+        }
+
+        assert_eq!(
+            svg_editor.get_elem_with_id("title")?.text,
+            "Spring Festival"
+        );
+        assert_eq!(svg_editor.get_elem_with_id("number_1")?.text, "No. 7");
+        assert_eq!(
+            svg_editor.get_attr_of_elem("link_1", "href"),
+            Some("https://example.com/7")
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,364 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{bail, Result};
+use log::warn;
+
+use crate::{
+    fortune::fortune_data::fortune_settings::PrintMarks,
+    svg::{
+        font_metrics::format_length,
+        svg_bounds::{Bounds, UNITS_PER_MM},
+        svg_editor::SvgEditor,
+    },
+};
+
+use super::svg_keys::{measure_slips, SLIP_ATTR};
+
+/// Marks the box a slip is cut along, e.g. `<rect data-cut="" fill="none" .../>`, instead of
+/// the bounds of the slip group.
+pub const CUT_ATTR: &str = "data-cut";
+
+/// Distance between the artwork of the slips and the marks.
+const MARK_GAP_MM: f32 = 2.0;
+const CROP_MARK_LENGTH_MM: f32 = 5.0;
+const CROP_MARK_STROKE_WIDTH: f32 = 0.25;
+const REGISTRATION_MARK_SIZE_MM: f32 = 5.0;
+
+/// The print marks around the slips of a page, in the units of the template.
+#[derive(Debug, PartialEq)]
+struct MarkShapes {
+    /// The crop marks, from one point to the other.
+    crop_lines: Vec<((f32, f32), (f32, f32))>,
+    /// The squares of the registration marks.
+    registration_squares: Vec<Bounds>,
+}
+
+/// Draws the print marks on top of the page, around the slips of the template at `template`.
+///
+/// The slips are cut along the boxes marked with `data-cut`, or otherwise along the slip groups
+/// marked with `data-slip`. The artwork of a slip group runs `bleed` past where it is cut.
+pub fn add_print_marks(
+    svg_editor: &mut SvgEditor,
+    template: &Path,
+    print_marks: &PrintMarks,
+) -> Result<()> {
+    svg_editor.check_units_are_points(template)?;
+    let bleed = print_marks.bleed * UNITS_PER_MM;
+
+    let cut_bounds = svg_editor.get_bounds_of_elems_with_attr(CUT_ATTR, template)?;
+    let (page_width, page_height, cut_boxes) = if cut_bounds.elems.is_empty() {
+        let slip_bounds = measure_slips(svg_editor, template)?;
+        let cut_boxes = slip_bounds
+            .elems
+            .iter()
+            .map(|(_, bounds)| shrink(bounds, bleed))
+            .collect::<Vec<_>>();
+        (slip_bounds.width, slip_bounds.height, cut_boxes)
+    } else {
+        let cut_boxes = cut_bounds.elems.iter().map(|(_, bounds)| *bounds).collect();
+        (cut_bounds.width, cut_bounds.height, cut_boxes)
+    };
+
+    if cut_boxes.is_empty() {
+        bail!(
+            "Can't tell where to cut the slips of '{}'. Mark the slips with {SLIP_ATTR}, or \
+             draw a <rect {CUT_ATTR}=\"\"> where every slip is cut.",
+            template.display()
+        );
+    }
+
+    let shapes = mark_shapes(&cut_boxes, bleed, print_marks);
+    let page = Bounds {
+        x: 0.0,
+        y: 0.0,
+        width: page_width,
+        height: page_height,
+    };
+    if !fits_on_page(&shapes, &page) {
+        let margin = print_marks.bleed + MARK_GAP_MM + CROP_MARK_LENGTH_MM;
+        warn!(
+            "The print marks run off the page of '{}'. Leave a margin of {} mm around the \
+             slips to print them.",
+            template.display(),
+            format_length(margin)
+        );
+    }
+
+    if !shapes.crop_lines.is_empty() {
+        let path_data = shapes
+            .crop_lines
+            .iter()
+            .map(|((x1, y1), (x2, y2))| {
+                let [x1, y1, x2, y2] = [x1, y1, x2, y2].map(|length| format_length(*length));
+                format!("M{x1} {y1}L{x2} {y2}")
+            })
+            .collect::<String>();
+        svg_editor.append_elem(
+            "path",
+            HashMap::from([
+                ("id".to_string(), "crop_marks".to_string()),
+                ("d".to_string(), path_data),
+                ("fill".to_string(), "none".to_string()),
+                ("stroke".to_string(), "black".to_string()),
+                (
+                    "stroke-width".to_string(),
+                    format_length(CROP_MARK_STROKE_WIDTH),
+                ),
+            ]),
+        );
+    }
+
+    if !shapes.registration_squares.is_empty() {
+        let path_data = shapes
+            .registration_squares
+            .iter()
+            .map(|square| {
+                let [x, y, size] = [square.x, square.y, square.width].map(format_length);
+                format!("M{x} {y}h{size}v{size}h-{size}z")
+            })
+            .collect::<String>();
+        svg_editor.append_elem(
+            "path",
+            HashMap::from([
+                ("id".to_string(), "registration_marks".to_string()),
+                ("d".to_string(), path_data),
+                ("fill".to_string(), "black".to_string()),
+            ]),
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the crop marks and registration marks around the cut boxes.
+///
+/// Every line a slip is cut along gets a crop mark at both ends, in the margin around all
+/// slips, so the marks are not printed on the slips next to it. The registration marks are
+/// squares in the top left, top right and bottom left corners of the margin.
+fn mark_shapes(cut_boxes: &[Bounds], bleed: f32, print_marks: &PrintMarks) -> MarkShapes {
+    let artwork = cut_boxes
+        .iter()
+        .map(|cut_box| grow(cut_box, bleed))
+        .reduce(|a, b| a.union(&b))
+        .expect("at least one cut box");
+    let gap = MARK_GAP_MM * UNITS_PER_MM;
+    let length = CROP_MARK_LENGTH_MM * UNITS_PER_MM;
+    let (top, bottom) = (artwork.y - gap, artwork.bottom() + gap);
+    let (left, right) = (artwork.x - gap, artwork.right() + gap);
+
+    let mut crop_lines = vec![];
+    if print_marks.crop_marks {
+        for x in cut_lines(cut_boxes.iter().flat_map(|b| [b.x, b.right()])) {
+            crop_lines.push(((x, top), (x, top - length)));
+            crop_lines.push(((x, bottom), (x, bottom + length)));
+        }
+        for y in cut_lines(cut_boxes.iter().flat_map(|b| [b.y, b.bottom()])) {
+            crop_lines.push(((left, y), (left - length, y)));
+            crop_lines.push(((right, y), (right + length, y)));
+        }
+    }
+
+    let mut registration_squares = vec![];
+    if print_marks.registration_marks {
+        let size = REGISTRATION_MARK_SIZE_MM * UNITS_PER_MM;
+        let square = |x, y| Bounds {
+            x,
+            y,
+            width: size,
+            height: size,
+        };
+        registration_squares = vec![
+            square(left - size, top - size),
+            square(right, top - size),
+            square(left - size, bottom),
+        ];
+    }
+
+    MarkShapes {
+        crop_lines,
+        registration_squares,
+    }
+}
+
+/// Returns the distinct positions of the cut lines, in order. Slips that touch share a line.
+fn cut_lines(positions: impl Iterator<Item = f32>) -> Vec<f32> {
+    let mut positions = positions.collect::<Vec<_>>();
+    positions.sort_by(f32::total_cmp);
+    positions.dedup_by(|a, b| (*a - *b).abs() < 0.01);
+    positions
+}
+
+fn fits_on_page(shapes: &MarkShapes, page: &Bounds) -> bool {
+    let fits = |(x, y): (f32, f32)| {
+        (page.x..=page.right()).contains(&x) && (page.y..=page.bottom()).contains(&y)
+    };
+
+    shapes
+        .crop_lines
+        .iter()
+        .all(|&(start, end)| fits(start) && fits(end))
+        && shapes
+            .registration_squares
+            .iter()
+            .all(|square| fits((square.x, square.y)) && fits((square.right(), square.bottom())))
+}
+
+fn grow(bounds: &Bounds, margin: f32) -> Bounds {
+    Bounds {
+        x: bounds.x - margin,
+        y: bounds.y - margin,
+        width: bounds.width + 2.0 * margin,
+        height: bounds.height + 2.0 * margin,
+    }
+}
+
+fn shrink(bounds: &Bounds, margin: f32) -> Bounds {
+    grow(bounds, -margin)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        fortune::fortune_data::fortune_settings::PrintMarks,
+        svg::{
+            svg_bounds::{Bounds, UNITS_PER_MM},
+            svg_editor::SvgEditor,
+        },
+    };
+
+    use super::{add_print_marks, cut_lines, mark_shapes};
+
+    /// Two slips side by side in the middle of the page, with 3 mm of bleed.
+    const TWO_SLIPS_SVG: &str = r#"
+        <svg width="600" height="400" xmlns="http://www.w3.org/2000/svg">
+            <g data-slip="0"><rect x="100" y="100" width="200" height="200"/></g>
+            <g data-slip="1"><rect x="300" y="100" width="200" height="200"/></g>
+        </svg>"#;
+
+    fn square(x: f32, y: f32, size: f32) -> Bounds {
+        Bounds {
+            x,
+            y,
+            width: size,
+            height: size,
+        }
+    }
+
+    #[test]
+    fn test_cut_lines() {
+        assert_eq!(
+            cut_lines([10.0, 0.0, 10.001, 20.0].into_iter()),
+            vec![0.0, 10.0, 20.0]
+        );
+    }
+
+    #[test]
+    fn test_mark_shapes_of_touching_slips() {
+        let print_marks = PrintMarks {
+            crop_marks: true,
+            ..Default::default()
+        };
+        let cut_boxes = [square(100.0, 100.0, 100.0), square(200.0, 100.0, 100.0)];
+
+        let shapes = mark_shapes(&cut_boxes, 0.0, &print_marks);
+
+        // Three vertical cuts with a mark at the top and bottom, two horizontal cuts with a mark
+        // at the left and right.
+        assert_eq!(shapes.crop_lines.len(), 3 * 2 + 2 * 2);
+        let gap = 2.0 * UNITS_PER_MM;
+        assert_eq!(
+            shapes.crop_lines[0],
+            (
+                (100.0, 100.0 - gap),
+                (100.0, 100.0 - gap - 5.0 * UNITS_PER_MM)
+            )
+        );
+        assert!(shapes.registration_squares.is_empty());
+    }
+
+    #[test]
+    fn test_mark_shapes_with_bleed_and_registration_marks() {
+        let print_marks = PrintMarks {
+            crop_marks: false,
+            bleed: 3.0,
+            registration_marks: true,
+        };
+        let bleed = 3.0 * UNITS_PER_MM;
+
+        let shapes = mark_shapes(&[square(100.0, 100.0, 100.0)], bleed, &print_marks);
+
+        let (gap, size) = (2.0 * UNITS_PER_MM, 5.0 * UNITS_PER_MM);
+        let near = 100.0 - bleed - gap - size;
+        let far = 200.0 + bleed + gap;
+        assert!(shapes.crop_lines.is_empty());
+        assert_eq!(
+            shapes.registration_squares,
+            vec![
+                square(near, near, size),
+                square(far, near, size),
+                square(near, far, size)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_add_print_marks() -> Result<()> {
+        let temp_file = test_utils::create_temp_file("two_slips.svg", TWO_SLIPS_SVG);
+        let mut svg_editor = SvgEditor::open(&temp_file.path)?;
+        let print_marks = PrintMarks {
+            crop_marks: true,
+            bleed: 3.0,
+            registration_marks: true,
+        };
+
+        add_print_marks(&mut svg_editor, &temp_file.path, &print_marks)?;
+
+        // The slip groups include the bleed, so the first cut is 3 mm inside the artwork.
+        let crop_marks = svg_editor.get_attr_of_elem("crop_marks", "d").unwrap();
+        assert!(crop_marks.starts_with("M108.504 94.331L108.504 80.157"));
+        // Without their bleed the slips no longer touch, so they have four vertical cuts.
+        assert_eq!(crop_marks.matches('M').count(), 4 * 2 + 2 * 2);
+        let registration_marks = svg_editor.get_attr_of_elem("registration_marks", "d");
+        assert_eq!(registration_marks.unwrap().matches('z').count(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_print_marks_to_cut_boxes() -> Result<()> {
+        let svg = TWO_SLIPS_SVG.replace(
+            "</svg>",
+            r#"<rect data-cut="" x="110" y="110" width="380" height="180" fill="none"/></svg>"#,
+        );
+        let temp_file = test_utils::create_temp_file("cut_box.svg", &svg);
+        let mut svg_editor = SvgEditor::open(&temp_file.path)?;
+        let print_marks = PrintMarks {
+            crop_marks: true,
+            ..Default::default()
+        };
+
+        add_print_marks(&mut svg_editor, &temp_file.path, &print_marks)?;
+
+        let crop_marks = svg_editor.get_attr_of_elem("crop_marks", "d").unwrap();
+        assert!(crop_marks.starts_with("M110 104.331L110 90.157"));
+        assert_eq!(crop_marks.matches('M').count(), 2 * 2 + 2 * 2);
+        assert_eq!(svg_editor.get_attr_of_elem("registration_marks", "d"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_print_marks_without_slips_expect_error() {
+        let temp_file = test_utils::create_temp_file("empty.svg", "<svg width='10' height='10'/>");
+        let mut svg_editor = SvgEditor::open(&temp_file.path).unwrap();
+        let print_marks = PrintMarks {
+            crop_marks: true,
+            ..Default::default()
+        };
+
+        assert!(add_print_marks(&mut svg_editor, &temp_file.path, &print_marks).is_err());
+    }
+}
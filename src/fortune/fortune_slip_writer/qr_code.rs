@@ -0,0 +1,247 @@
+use std::{collections::HashMap, fmt::Write};
+
+use anyhow::{anyhow, Result};
+use qrcode::{Color, QrCode};
+
+use crate::svg::{font_metrics::format_length, svg_editor::SvgEditor};
+
+use super::svg_keys::{slip_of_elem, SvgKeys, SLIP_ATTR};
+
+/// Marks the box of a QR code, e.g. `<rect data-qr="" x="10" y="10" width="40" height="40">`.
+///
+/// A value other than empty is encoded instead of the `qr_content` of the settings.
+pub const QR_ATTR: &str = "data-qr";
+
+/// Attributes of the placeholder that are kept on the QR code.
+const KEPT_ATTRS: [&str; 4] = ["fill", "opacity", "style", "class"];
+
+/// A box in the template to draw a QR code in.
+#[derive(Debug, PartialEq, Clone)]
+pub struct QrElem {
+    pub id: String,
+    pub slip_idx: usize,
+    /// What to encode, with placeholders, when the template gives it.
+    pub content: Option<String>,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    /// The transform of the box, which the QR code is drawn in as well.
+    transform: Option<String>,
+    kept_attrs: HashMap<String, String>,
+}
+
+impl QrElem {
+    /// Returns the size of the QR code, which is square and as large as fits the box.
+    fn size(&self) -> f32 {
+        self.width.min(self.height)
+    }
+}
+
+/// Retrieves the boxes marked with `data-qr`, which all have to belong to a slip.
+pub fn retrieve_qr_elems(
+    svg_editor: &SvgEditor,
+    svg_keys_all_slips: &[SvgKeys],
+    slip_numbers: &[usize],
+) -> Result<Vec<QrElem>> {
+    svg_editor
+        .get_marked_elems_with_inherited_attr(QR_ATTR, SLIP_ATTR)?
+        .into_iter()
+        .map(|(id, content, slip)| {
+            let slip_idx =
+                slip_of_elem(id, slip, slip_numbers, svg_keys_all_slips)?.ok_or(anyhow!(
+                    "Can't tell which slip the QR code '{id}' belongs to. Put it in a group \
+                     with {SLIP_ATTR}, or add {SLIP_ATTR} to the element."
+                ))?;
+
+            let length_of = |attr_name| -> Result<f32> {
+                let length = svg_editor.get_attr_of_elem(id, attr_name).unwrap_or("0");
+                length
+                    .trim()
+                    .trim_end_matches("px")
+                    .parse()
+                    .map_err(|_| anyhow!("The {attr_name} of QR code '{id}' is not a number"))
+            };
+
+            let (width, height) = (length_of("width")?, length_of("height")?);
+            if width.min(height) <= 0.0 {
+                return Err(anyhow!(
+                    "The QR code '{id}' needs a width and height for the size of its box"
+                ));
+            }
+
+            let kept_attrs = KEPT_ATTRS
+                .into_iter()
+                .filter_map(|name| {
+                    let value = svg_editor.get_attr_of_elem(id, name)?;
+                    Some((name.to_string(), value.to_string()))
+                })
+                .collect();
+
+            Ok(QrElem {
+                id: id.to_string(),
+                slip_idx,
+                content: (!content.trim().is_empty()).then(|| content.to_string()),
+                x: length_of("x")?,
+                y: length_of("y")?,
+                width,
+                height,
+                transform: svg_editor
+                    .get_attr_of_elem(id, "transform")
+                    .map(str::to_string),
+                kept_attrs,
+            })
+        })
+        .collect()
+}
+
+/// Replaces the box by a QR code of the content, drawn as a single path in the middle of the box.
+///
+/// An empty content, e.g. of an empty slip, leaves the box empty.
+pub fn write_qr_code(svg_editor: &mut SvgEditor, qr_elem: &QrElem, content: &str) -> Result<()> {
+    let mut attrs = qr_elem.kept_attrs.clone();
+
+    if content.is_empty() {
+        attrs.insert("d".to_string(), String::new());
+    } else {
+        let qr_code = QrCode::new(content.as_bytes())
+            .map_err(|e| anyhow!("Could not create the QR code '{}': {e}", qr_elem.id))?;
+        let size = qr_elem.size();
+        let scale = size / qr_code.width() as f32;
+        let placement = format!(
+            "translate({} {}) scale({})",
+            format_length(qr_elem.x + (qr_elem.width - size) / 2.0),
+            format_length(qr_elem.y + (qr_elem.height - size) / 2.0),
+            scale
+        );
+
+        attrs.insert("d".to_string(), qr_path_data(&qr_code));
+        attrs.insert(
+            "transform".to_string(),
+            match &qr_elem.transform {
+                Some(transform) => format!("{transform} {placement}"),
+                None => placement,
+            },
+        );
+    }
+
+    attrs.insert(
+        QR_ATTR.to_string(),
+        qr_elem.content.clone().unwrap_or_default(),
+    );
+    svg_editor.replace_elem(&qr_elem.id, "path", attrs)
+}
+
+/// Returns the path of the dark modules, with one unit per module and a rectangle for every
+/// horizontal run of dark modules.
+fn qr_path_data(qr_code: &QrCode) -> String {
+    let width = qr_code.width();
+    let mut path_data = String::new();
+
+    for y in 0..width {
+        let mut x = 0;
+        while x < width {
+            if qr_code[(x, y)] == Color::Light {
+                x += 1;
+                continue;
+            }
+
+            let run_start = x;
+            while x < width && qr_code[(x, y)] == Color::Dark {
+                x += 1;
+            }
+            let run = x - run_start;
+            write!(path_data, "M{run_start} {y}h{run}v1h-{run}z").unwrap();
+        }
+    }
+
+    path_data
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+    use qrcode::QrCode;
+
+    use crate::{
+        fortune::fortune_slip_writer::svg_keys::{marked_slip_numbers, retrieve_svg_keys},
+        svg::svg_editor::SvgEditor,
+    };
+
+    use super::{qr_path_data, retrieve_qr_elems, write_qr_code};
+
+    const QR_SVG: &str = r#"
+        <svg height='200' width='100'>
+            <g data-slip="0">
+                <text id="header_1"><tspan>header_1</tspan></text>
+                <text id="luck_1"><tspan>luck_level_1</tspan></text>
+                <rect id="qr_1" data-qr="https://example.com/{{serial}}" x="10" y="20"
+                      width="42" height="50" fill="navy" class="qr" style="opacity:0.9"
+                      transform="rotate(-5 30 45)"/>
+            </g>
+        </svg>"#;
+
+    #[test]
+    fn test_qr_path_data_covers_dark_modules() -> Result<()> {
+        let qr_code = QrCode::new(b"00042")?;
+
+        let path_data = qr_path_data(&qr_code);
+
+        // Every rectangle is `M{x} {y}h{run}v1h-{run}z`.
+        let nr_dark_modules: usize = path_data
+            .split('M')
+            .skip(1)
+            .map(|rect| {
+                let run = rect.split(['h', 'v']).nth(1).unwrap();
+                run.parse::<usize>().unwrap()
+            })
+            .sum();
+        let expected = qr_code
+            .to_colors()
+            .into_iter()
+            .filter(|color| *color == qrcode::Color::Dark)
+            .count();
+        assert_eq!(nr_dark_modules, expected);
+        assert!(path_data.starts_with("M0 0h7v1h-7z"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_qr_code() -> Result<()> {
+        let temp_file = test_utils::create_temp_file("qr.svg", QR_SVG);
+        let mut svg_editor = SvgEditor::open(&temp_file.path)?;
+        let svg_keys = retrieve_svg_keys(&svg_editor, &[], None)?;
+
+        let qr_elems =
+            retrieve_qr_elems(&svg_editor, &svg_keys, &marked_slip_numbers(&svg_editor)?)?;
+        assert_eq!(qr_elems.len(), 1);
+        assert_eq!(
+            qr_elems[0].content.as_deref(),
+            Some("https://example.com/{{serial}}")
+        );
+
+        write_qr_code(&mut svg_editor, &qr_elems[0], "https://example.com/00042")?;
+
+        let path_data = svg_editor.get_attr_of_elem("qr_1", "d").unwrap();
+        assert!(path_data.starts_with("M0 0h7v1h-7z"));
+        assert_eq!(svg_editor.get_attr_of_elem("qr_1", "fill"), Some("navy"));
+        assert_eq!(svg_editor.get_attr_of_elem("qr_1", "class"), Some("qr"));
+        assert_eq!(
+            svg_editor.get_attr_of_elem("qr_1", "style"),
+            Some("opacity:0.9")
+        );
+        // Centred in the box, which is higher than it is wide, and turned with the box.
+        assert_eq!(
+            svg_editor.get_attr_of_elem("qr_1", "transform"),
+            Some("rotate(-5 30 45) translate(10 24) scale(1.68)")
+        );
+
+        // The box can be written again, e.g. for the next page.
+        write_qr_code(&mut svg_editor, &qr_elems[0], "")?;
+        assert_eq!(svg_editor.get_attr_of_elem("qr_1", "d"), Some(""));
+
+        Ok(())
+    }
+}
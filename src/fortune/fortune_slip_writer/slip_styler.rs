@@ -0,0 +1,253 @@
+use anyhow::{anyhow, bail, Result};
+
+use crate::{fortune::fortune_data::fortune_settings::LuckLevelStyle, svg::svg_editor::SvgEditor};
+
+use super::svg_keys::{slip_of_elem, SvgKeys, SLIP_ATTR};
+
+/// Marks an element to style by the luck level of its slip, e.g. `<rect data-style="header">`.
+const STYLE_ATTR: &str = "data-style";
+
+/// Which part of the luck level style an element shows.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StyleKind {
+    /// The `fill` is the `header_color`.
+    Header,
+    /// The `fill` is the `accent_color`.
+    Accent,
+    /// The `href` is the `icon`. An `xlink:href` in the template is read and written as `href`,
+    /// so it is replaced as well.
+    Icon,
+}
+
+impl StyleKind {
+    fn attr_name(self) -> &'static str {
+        match self {
+            StyleKind::Header | StyleKind::Accent => "fill",
+            StyleKind::Icon => "href",
+        }
+    }
+}
+
+/// An element of the template that is styled by the luck level of its slip.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StyledElem {
+    pub id: String,
+    pub slip_idx: usize,
+    pub kind: StyleKind,
+    /// The value of the styled attribute in the template, used when a slip has no style.
+    template_value: Option<String>,
+}
+
+/// Retrieves the elements marked with `data-style` and the slip every element belongs to.
+pub fn retrieve_styled_elems(
+    svg_editor: &SvgEditor,
+    svg_keys_all_slips: &[SvgKeys],
+    slip_numbers: &[usize],
+) -> Result<Vec<StyledElem>> {
+    svg_editor
+        .get_marked_elems_with_inherited_attr(STYLE_ATTR, SLIP_ATTR)?
+        .into_iter()
+        .map(|(id, kind, slip)| {
+            let kind = match kind.trim() {
+                "header" => StyleKind::Header,
+                "accent" => StyleKind::Accent,
+                "icon" => StyleKind::Icon,
+                other => {
+                    bail!("Unknown {STYLE_ATTR} of '{id}': {other}. Allowed: header, accent, icon")
+                }
+            };
+
+            let slip_idx =
+                slip_of_elem(id, slip, slip_numbers, svg_keys_all_slips)?.ok_or(anyhow!(
+                    "Can't tell which slip '{id}' with {STYLE_ATTR} belongs to. Put it in a \
+                     group with {SLIP_ATTR}, or add {SLIP_ATTR} to the element."
+                ))?;
+
+            Ok(StyledElem {
+                id: id.to_string(),
+                slip_idx,
+                kind,
+                template_value: svg_editor
+                    .get_attr_of_elem(id, kind.attr_name())
+                    .map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+/// Applies the style to the element, or restores the element as it is in the template when the
+/// style leaves its part out.
+pub fn apply_style(
+    svg_editor: &mut SvgEditor,
+    styled_elem: &StyledElem,
+    style: &LuckLevelStyle,
+) -> Result<()> {
+    let icon = style.icon.as_ref().map(|path| path.display().to_string());
+
+    let value = match styled_elem.kind {
+        StyleKind::Header => style.header_color.as_ref(),
+        StyleKind::Accent => style.accent_color.as_ref(),
+        StyleKind::Icon => icon.as_ref(),
+    }
+    .or(styled_elem.template_value.as_ref());
+
+    svg_editor.set_attr_of_elem(
+        &styled_elem.id,
+        styled_elem.kind.attr_name(),
+        value.map(String::as_str),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        fortune::{
+            fortune_data::fortune_settings::LuckLevelStyle,
+            fortune_slip_writer::svg_keys::{marked_slip_numbers, retrieve_svg_keys},
+        },
+        svg::svg_editor::SvgEditor,
+    };
+
+    use super::{apply_style, retrieve_styled_elems, StyleKind};
+
+    const MARKED_SVG: &str = r#"
+        <svg height='200' width='100'>
+            <g data-slip="1">
+                <rect id="banner_1" data-style="header" fill="black"/>
+                <image id="icon_1" data-style="icon"/>
+                <text id="header_1"><tspan>header_1</tspan></text>
+                <text id="luck_1" data-style="accent"><tspan>luck_level_1</tspan></text>
+            </g>
+            <g data-slip="2">
+                <rect id="banner_2" data-style="header" fill="black"/>
+                <text id="header_2"><tspan>header_2</tspan></text>
+                <text id="luck_2"><tspan>luck_level_2</tspan></text>
+            </g>
+        </svg>"#;
+
+    fn open_marked_svg() -> Result<SvgEditor> {
+        let temp_file = test_utils::create_temp_file("marked.svg", MARKED_SVG);
+        SvgEditor::open(temp_file.path)
+    }
+
+    #[test]
+    fn test_retrieve_styled_elems() -> Result<()> {
+        let svg_editor = open_marked_svg()?;
+        let svg_keys = retrieve_svg_keys(&svg_editor, &[], None)?;
+
+        let styled_elems =
+            retrieve_styled_elems(&svg_editor, &svg_keys, &marked_slip_numbers(&svg_editor)?)?;
+
+        let found = styled_elems
+            .iter()
+            .map(|elem| (elem.id.as_str(), elem.slip_idx, elem.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                ("banner_1", 0, StyleKind::Header),
+                ("icon_1", 0, StyleKind::Icon),
+                ("luck_1", 0, StyleKind::Accent),
+                ("banner_2", 1, StyleKind::Header),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_style_and_restore() -> Result<()> {
+        let mut svg_editor = open_marked_svg()?;
+        let svg_keys = retrieve_svg_keys(&svg_editor, &[], None)?;
+        let styled_elems =
+            retrieve_styled_elems(&svg_editor, &svg_keys, &marked_slip_numbers(&svg_editor)?)?;
+        let style = LuckLevelStyle {
+            header_color: Some("red".to_string()),
+            accent_color: Some("gold".to_string()),
+            icon: Some(PathBuf::from("/icons/great_luck.svg")),
+            template_back: None,
+        };
+
+        for styled_elem in &styled_elems {
+            apply_style(&mut svg_editor, styled_elem, &style)?;
+        }
+        assert_eq!(svg_editor.get_attr_of_elem("banner_1", "fill"), Some("red"));
+        assert_eq!(svg_editor.get_elem_with_id("luck_1")?.attr["fill"], "gold");
+        assert_eq!(
+            svg_editor.get_attr_of_elem("icon_1", "href"),
+            Some("/icons/great_luck.svg")
+        );
+
+        for styled_elem in &styled_elems {
+            apply_style(&mut svg_editor, styled_elem, &LuckLevelStyle::default())?;
+        }
+        assert_eq!(
+            svg_editor.get_attr_of_elem("banner_1", "fill"),
+            Some("black")
+        );
+        assert_eq!(svg_editor.get_attr_of_elem("luck_1", "fill"), None);
+        assert_eq!(svg_editor.get_attr_of_elem("icon_1", "href"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_style_replaces_xlink_href() -> Result<()> {
+        let temp_file = test_utils::create_temp_file(
+            "xlink.svg",
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">
+                <g data-slip="1">
+                    <image id="icon_1" data-style="icon" xlink:href="/icons/default.svg"/>
+                    <text id="header_1"><tspan>header_1</tspan></text>
+                    <text id="luck_1"><tspan>luck_level_1</tspan></text>
+                </g>
+            </svg>"#,
+        );
+        let mut svg_editor = SvgEditor::open(temp_file.path)?;
+        let svg_keys = retrieve_svg_keys(&svg_editor, &[], None)?;
+        let styled_elems =
+            retrieve_styled_elems(&svg_editor, &svg_keys, &marked_slip_numbers(&svg_editor)?)?;
+        let style = LuckLevelStyle {
+            icon: Some(PathBuf::from("/icons/great_luck.svg")),
+            ..LuckLevelStyle::default()
+        };
+
+        apply_style(&mut svg_editor, &styled_elems[0], &style)?;
+
+        let mut svg_data = vec![];
+        svg_editor.write_svg(&mut svg_data)?;
+        let svg_data = String::from_utf8(svg_data)?;
+        assert!(svg_data.contains(r#"href="/icons/great_luck.svg""#));
+        assert!(!svg_data.contains("/icons/default.svg"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_styled_elem_needs_a_slip() -> Result<()> {
+        let temp_file = test_utils::create_temp_file(
+            "unmarked.svg",
+            r#"<svg>
+                <rect id="banner" data-style="header"/>
+                <text id="header_1"><tspan>header_1</tspan></text>
+                <text id="luck_1"><tspan>luck_level_1</tspan></text>
+                <text id="header_2"><tspan>header_2</tspan></text>
+                <text id="luck_2"><tspan>luck_level_2</tspan></text>
+            </svg>"#,
+        );
+        let svg_editor = SvgEditor::open(temp_file.path)?;
+        let svg_keys = retrieve_svg_keys(&svg_editor, &[], None)?;
+
+        assert!(
+            retrieve_styled_elems(&svg_editor, &svg_keys, &marked_slip_numbers(&svg_editor)?)
+                .is_err()
+        );
+
+        Ok(())
+    }
+}
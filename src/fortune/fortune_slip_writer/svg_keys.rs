@@ -1,29 +1,190 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
 
-use crate::{
-    constants::NR_SLIPS_PER_PAGE,
-    svg::svg_editor::{text_elem::TextElem, SvgEditor},
+use crate::svg::{
+    svg_bounds::{Bounds, PageBounds},
+    svg_editor::{text_elem::TextElem, SvgEditor},
 };
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
+
+use super::placeholders::{has_placeholder, placeholder_name};
 
 const MANDATORY_TAGS: [&str; 2] = ["header", "luck_level"];
 
+/// Marks the slip of a group or text element, e.g. `<g data-slip="0">`.
+pub const SLIP_ATTR: &str = "data-slip";
+/// Marks which text a text element holds, e.g. `<text data-field="love">`.
+const FIELD_ATTR: &str = "data-field";
+
 /// Struct that holds the keys for the text elements in the svg file.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct SvgKeys {
     pub header_key: String,
     pub luck_level_key: String,
     pub cat_to_fortune_keys: HashMap<String, String>,
+    /// The other text elements of the slip, such as a `{{slip_number}}`.
+    pub other_keys: Vec<String>,
+}
+
+impl SvgKeys {
+    /// Returns the key of the text element for a field, i.e. `header`, `luck_level` or a category.
+    pub fn key_of(&self, field: &str) -> Option<&String> {
+        match field {
+            "header" => Some(&self.header_key),
+            "luck_level" => Some(&self.luck_level_key),
+            category => self.cat_to_fortune_keys.get(category),
+        }
+    }
+
+    /// Returns whether the text element with the given key is filled with a field of the slip.
+    pub fn is_field_key(&self, key: &str) -> bool {
+        self.header_key == key
+            || self.luck_level_key == key
+            || self.cat_to_fortune_keys.values().any(|x| x == key)
+    }
+
+    /// Returns whether the text element with the given key is part of this slip.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.is_field_key(key) || self.other_keys.iter().any(|x| x == key)
+    }
 }
 
+/// Retrieves the keys of every slip in the template.
+///
+/// When the template marks its slips with `data-slip`, only the text elements inside a slip
+/// are used. Otherwise, the text elements are split in order into equally sized slips.
+/// Without `slips_per_page`, the number of slips is then the number of `header` text elements.
 pub fn retrieve_svg_keys(
     svg_editor: &SvgEditor,
     fortune_categories: &[String],
+    slips_per_page: Option<usize>,
+) -> Result<Vec<SvgKeys>> {
+    let marked_slips = retrieve_marked_slips(svg_editor)?;
+
+    if marked_slips.is_empty() {
+        return retrieve_svg_keys_by_text_order(svg_editor, fortune_categories, slips_per_page);
+    }
+
+    if let Some(slips_per_page) = slips_per_page {
+        if slips_per_page != marked_slips.len() {
+            bail!(
+                "The settings expect {} slips per page, but the template marks {} slips",
+                slips_per_page,
+                marked_slips.len()
+            );
+        }
+    }
+
+    marked_slips
+        .values()
+        .map(|text_elems| retrieve_svg_elem_keys_for_chunk(text_elems, fortune_categories))
+        .collect()
+}
+
+/// Returns the `data-slip` numbers of the slips in the template, in the order of their keys.
+pub fn marked_slip_numbers(svg_editor: &SvgEditor) -> Result<Vec<usize>> {
+    Ok(retrieve_marked_slips(svg_editor)?.into_keys().collect())
+}
+
+/// Returns the size of the page and the bounds of every slip marked with `data-slip`, ordered by
+/// slip number. A slip marked on several elements gets the bounds around all of them.
+///
+/// `svg_path` is where the template is read from, to find its images.
+pub fn measure_slips(svg_editor: &SvgEditor, svg_path: &Path) -> Result<PageBounds<usize>> {
+    let page_bounds = svg_editor.get_bounds_of_elems_with_attr(SLIP_ATTR, svg_path)?;
+
+    let mut slips: BTreeMap<usize, Bounds> = BTreeMap::new();
+    for (slip, bounds) in &page_bounds.elems {
+        let slip = slip
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("The {SLIP_ATTR} is not a number: {slip}"))?;
+        slips
+            .entry(slip)
+            .and_modify(|slip_bounds| *slip_bounds = slip_bounds.union(bounds))
+            .or_insert(*bounds);
+    }
+
+    Ok(PageBounds {
+        width: page_bounds.width,
+        height: page_bounds.height,
+        elems: slips.into_iter().collect(),
+    })
+}
+
+/// Returns the index of the slip an element belongs to, if any.
+///
+/// An element belongs to the slip of its inherited `data-slip`, or otherwise to the slip that
+/// holds it as a text element. In a template with a single slip that is not marked with
+/// `data-slip`, every element belongs to that slip.
+pub fn slip_of_elem(
+    elem_id: &str,
+    slip: Option<&str>,
+    slip_numbers: &[usize],
+    svg_keys_all_slips: &[SvgKeys],
+) -> Result<Option<usize>> {
+    if let Some(slip) = slip {
+        let slip_idx = slip
+            .trim()
+            .parse()
+            .ok()
+            .and_then(|slip: usize| slip_numbers.iter().position(|&nr| nr == slip))
+            .ok_or(anyhow!(
+                "The {SLIP_ATTR} of '{elem_id}' is not a slip: {slip}"
+            ))?;
+        return Ok(Some(slip_idx));
+    }
+
+    let slip_idx = svg_keys_all_slips
+        .iter()
+        .position(|svg_keys| svg_keys.contains_key(elem_id));
+
+    let single_slip = slip_numbers.is_empty() && svg_keys_all_slips.len() == 1;
+    Ok(slip_idx.or(single_slip.then_some(0)))
+}
+
+fn retrieve_marked_slips(svg_editor: &SvgEditor) -> Result<BTreeMap<usize, Vec<&TextElem>>> {
+    let mut marked_slips: BTreeMap<usize, Vec<&TextElem>> = BTreeMap::new();
+
+    for (text_elem, slip) in svg_editor.get_text_elems_with_inherited_attr(SLIP_ATTR)? {
+        if let Some(slip) = slip {
+            let slip_idx = slip.trim().parse().map_err(|_| {
+                anyhow!(
+                    "The {SLIP_ATTR} of '{}' is not a number: {slip}",
+                    text_elem.id
+                )
+            })?;
+            marked_slips.entry(slip_idx).or_default().push(text_elem);
+        }
+    }
+
+    Ok(marked_slips)
+}
+
+fn retrieve_svg_keys_by_text_order(
+    svg_editor: &SvgEditor,
+    fortune_categories: &[String],
+    slips_per_page: Option<usize>,
 ) -> Result<Vec<SvgKeys>> {
     let text_elems = svg_editor.get_text_elems_ordered();
 
-    let elems_per_slip = text_elems.len() / NR_SLIPS_PER_PAGE;
+    let nr_slips = slips_per_page.unwrap_or_else(|| count_slips(&text_elems, fortune_categories));
+    if nr_slips == 0 {
+        bail!("No fortune slips found in the template, it needs at least one header");
+    }
+
+    if !text_elems.len().is_multiple_of(nr_slips) {
+        bail!(
+            "The {} text elements in the template can't be split evenly over {} slips",
+            text_elems.len(),
+            nr_slips
+        );
+    }
+
+    let elems_per_slip = text_elems.len() / nr_slips;
 
     text_elems
         .chunks_exact(elems_per_slip)
@@ -31,6 +192,13 @@ pub fn retrieve_svg_keys(
         .collect()
 }
 
+fn count_slips(text_elems: &[&TextElem], fortune_categories: &[String]) -> usize {
+    text_elems
+        .iter()
+        .filter(|text_elem| field_of(text_elem, fortune_categories).as_deref() == Some("header"))
+        .count()
+}
+
 fn retrieve_svg_elem_keys_for_chunk(
     text_elem_chunk: &[&TextElem],
     fortune_categories: &[String],
@@ -43,27 +211,30 @@ fn retrieve_svg_elem_keys_for_chunk(
         header_key: "".to_string(),
         luck_level_key: "".to_string(),
         cat_to_fortune_keys: HashMap::new(),
+        other_keys: vec![],
     };
 
-    text_elem_chunk
-        .iter()
-        .filter(|x| is_relevant_text_elem(x, fortune_categories))
-        .for_each(|text_elem| add_text_elem_to_keys(&mut svg_elem_keys, text_elem));
+    for text_elem in text_elem_chunk {
+        match field_of(text_elem, fortune_categories) {
+            Some(field) => add_text_elem_to_keys(&mut svg_elem_keys, &field, text_elem),
+            None => svg_elem_keys.other_keys.push(text_elem.id.clone()),
+        }
+    }
 
     no_empty_keys(&svg_elem_keys, fortune_categories)?;
     Ok(svg_elem_keys)
 }
 
 fn check_missing_tags(text_elem_chunk: &[&TextElem], fortune_categories: &[String]) -> Result<()> {
-    let mut allowed_tags: Vec<_> = MANDATORY_TAGS.into_iter().map(|x| x.to_string()).collect();
-    allowed_tags.extend_from_slice(fortune_categories);
-
     for text_elem in text_elem_chunk {
-        if text_elem.text.contains('_') && !is_relevant_text_elem(text_elem, fortune_categories) {
+        let looks_like_tag = text_elem.attr.contains_key(FIELD_ATTR)
+            || (text_elem.text.contains('_') && !has_placeholder(&text_elem.text));
+
+        if looks_like_tag && field_of(text_elem, fortune_categories).is_none() {
             bail!(
                 "Missing tag in fortune slip: {}. Allowed tags: {:?}",
-                text_elem.text,
-                allowed_tags
+                text_elem.attr.get(FIELD_ATTR).unwrap_or(&text_elem.text),
+                allowed_tags(fortune_categories)
             )
         }
     }
@@ -71,33 +242,56 @@ fn check_missing_tags(text_elem_chunk: &[&TextElem], fortune_categories: &[Strin
     Ok(())
 }
 
-fn is_relevant_text_elem(text_elem: &TextElem, fortune_categories: &[String]) -> bool {
+fn allowed_tags(fortune_categories: &[String]) -> Vec<String> {
     let mut allowed_tags: Vec<_> = MANDATORY_TAGS.into_iter().map(|x| x.to_string()).collect();
     allowed_tags.extend_from_slice(fortune_categories);
+    allowed_tags
+}
 
-    let result = allowed_tags.iter().any(|x| text_elem.text.contains(x));
+/// Returns which tag the text element should be filled with, if any.
+///
+/// The `data-field` attribute, or a text that is a single placeholder such as `{{love}}`, has to
+/// match a tag exactly. Without either, the tag is derived from the text of the element, e.g.
+/// `love_1` for the `love` category. Other placeholders are not a tag.
+fn field_of(text_elem: &TextElem, fortune_categories: &[String]) -> Option<String> {
+    let allowed_tags = allowed_tags(fortune_categories);
 
-    result
-}
+    if let Some(field) = text_elem.attr.get(FIELD_ATTR) {
+        return allowed_tags.contains(field).then(|| field.clone());
+    }
+
+    if let Some(name) = placeholder_name(&text_elem.text.replace("[center]", "")) {
+        return allowed_tags.iter().find(|tag| *tag == name).cloned();
+    }
+
+    if has_placeholder(&text_elem.text) {
+        return None;
+    }
 
-fn add_text_elem_to_keys(svg_elem_keys: &mut SvgKeys, text_elem: &TextElem) {
     match &text_elem.text {
-        s if s.contains("header") => svg_elem_keys.header_key.clone_from(&text_elem.id),
-        s if s.contains("luck_level") => svg_elem_keys.luck_level_key.clone_from(&text_elem.id),
-        s if s.contains('_') => {
-            let (category, _) = s.split_once('_').unwrap();
+        s if !allowed_tags.iter().any(|x| s.contains(x)) => None,
+        s if s.contains("header") => Some("header".to_string()),
+        s if s.contains("luck_level") => Some("luck_level".to_string()),
+        s => s
+            .split_once('_')
+            .map(|(category, _)| category.to_string())
+            .filter(|category| fortune_categories.contains(category)),
+    }
+}
+
+fn add_text_elem_to_keys(svg_elem_keys: &mut SvgKeys, field: &str, text_elem: &TextElem) {
+    match field {
+        "header" => svg_elem_keys.header_key.clone_from(&text_elem.id),
+        "luck_level" => svg_elem_keys.luck_level_key.clone_from(&text_elem.id),
+        category => {
             svg_elem_keys
                 .cat_to_fortune_keys
                 .insert(category.to_string(), text_elem.id.clone());
         }
-        _ => unreachable!("Should not be possible to get here"),
     }
 }
 
 fn no_empty_keys(svg_elem_keys: &SvgKeys, fortune_categories: &[String]) -> Result<()> {
-    let mut allowed_tags: Vec<_> = MANDATORY_TAGS.into_iter().map(|x| x.to_string()).collect();
-    allowed_tags.extend_from_slice(fortune_categories);
-
     if svg_elem_keys.header_key.is_empty() {
         bail!("Missing header in fortune slip");
     }
@@ -114,3 +308,71 @@ fn no_empty_keys(svg_elem_keys: &SvgKeys, fortune_categories: &[String]) -> Resu
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    use crate::svg::svg_editor::SvgEditor;
+
+    use super::retrieve_svg_keys;
+
+    const MARKED_SLIPS_SVG: &str = r#"
+        <svg height='200' width='100'>
+            <text id="title"><tspan>Omikuji of the year</tspan></text>
+            <g data-slip="1">
+                <text id="header_b" data-field="header"><tspan>Header</tspan></text>
+                <text id="luck_b" data-field="luck_level"><tspan>Luck</tspan></text>
+                <text id="love_b" data-field="love"><tspan>Love</tspan></text>
+            </g>
+            <g data-slip="0">
+                <text id="decoration"><tspan>Kinjin</tspan></text>
+                <text id="header_a"><tspan>header_1</tspan></text>
+                <text id="luck_a"><tspan>luck_level_1</tspan></text>
+                <text id="love_a"><tspan>love_1</tspan></text>
+            </g>
+        </svg>"#;
+
+    fn categories() -> Vec<String> {
+        vec!["love".to_string()]
+    }
+
+    #[test]
+    fn test_retrieve_svg_keys_from_marked_slips() -> Result<()> {
+        let temp_file = test_utils::create_temp_file("marked.svg", MARKED_SLIPS_SVG);
+        let svg_editor = SvgEditor::open(&temp_file.path)?;
+
+        let svg_keys = retrieve_svg_keys(&svg_editor, &categories(), None)?;
+
+        assert_eq!(svg_keys.len(), 2);
+        assert_eq!(svg_keys[0].header_key, "header_a");
+        assert_eq!(svg_keys[0].cat_to_fortune_keys["love"], "love_a");
+        assert_eq!(svg_keys[1].header_key, "header_b");
+        assert_eq!(svg_keys[1].luck_level_key, "luck_b");
+        assert_eq!(svg_keys[1].cat_to_fortune_keys["love"], "love_b");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_retrieve_svg_keys_marked_slips_wrong_slips_per_page() -> Result<()> {
+        let temp_file = test_utils::create_temp_file("marked.svg", MARKED_SLIPS_SVG);
+        let svg_editor = SvgEditor::open(&temp_file.path)?;
+
+        assert!(retrieve_svg_keys(&svg_editor, &categories(), Some(4)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_retrieve_svg_keys_unknown_field() -> Result<()> {
+        let svg = MARKED_SLIPS_SVG.replace(r#"data-field="love""#, r#"data-field="wealth""#);
+        let temp_file = test_utils::create_temp_file("marked.svg", &svg);
+        let svg_editor = SvgEditor::open(&temp_file.path)?;
+
+        assert!(retrieve_svg_keys(&svg_editor, &categories(), None).is_err());
+
+        Ok(())
+    }
+}
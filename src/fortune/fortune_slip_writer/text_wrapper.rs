@@ -0,0 +1,270 @@
+use anyhow::{anyhow, Result};
+
+use crate::svg::{
+    font_metrics::TextStyle,
+    svg_editor::{text_elem::TextElem, SvgEditor},
+};
+
+/// The maximum width of a text element, e.g. `<text data-wrap-width="120">`.
+const WRAP_WIDTH_ATTR: &str = "data-wrap-width";
+/// The maximum height of a text element, e.g. `<text data-wrap-height="40">`.
+const WRAP_HEIGHT_ATTR: &str = "data-wrap-height";
+/// The id of a `<rect>` whose size is the maximum size, e.g. `<text data-wrap-rect="box_1">`.
+pub const WRAP_RECT_ATTR: &str = "data-wrap-rect";
+
+/// How much the font size is lowered at a time when the text does not fit.
+const FONT_SIZE_STEP: f32 = 0.5;
+
+/// The box a text element should fit in. Without a height, any number of lines fits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WrapBox {
+    pub width: f32,
+    pub height: Option<f32>,
+}
+
+/// The lines of a text that is fitted in a [`WrapBox`], and the font size it needs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FittedText {
+    pub lines: Vec<String>,
+    pub font_size: f32,
+    pub fits: bool,
+}
+
+/// Returns the box the text of the element should be wrapped within, if the template sets one.
+///
+/// The size comes from `data-wrap-width` and `data-wrap-height`, or else from the `<rect>` that
+/// `data-wrap-rect` refers to.
+pub fn wrap_box(svg_editor: &SvgEditor, text_elem: &TextElem) -> Result<Option<WrapBox>> {
+    let rect_size = |attr_name| -> Result<Option<f32>> {
+        let Some(rect_id) = text_elem.attr.get(WRAP_RECT_ATTR) else {
+            return Ok(None);
+        };
+
+        let size = svg_editor
+            .get_attr_of_elem(rect_id, attr_name)
+            .ok_or(anyhow!(
+                "The {WRAP_RECT_ATTR} of '{}' refers to '{rect_id}', which has no {attr_name}",
+                text_elem.id
+            ))?;
+
+        parse_size(size, &text_elem.id).map(Some)
+    };
+
+    let width = match text_elem.attr.get(WRAP_WIDTH_ATTR) {
+        Some(width) => Some(parse_size(width, &text_elem.id)?),
+        None => rect_size("width")?,
+    };
+
+    let Some(width) = width else {
+        return Ok(None);
+    };
+
+    let height = match text_elem.attr.get(WRAP_HEIGHT_ATTR) {
+        Some(height) => Some(parse_size(height, &text_elem.id)?),
+        None => rect_size("height")?,
+    };
+
+    Ok(Some(WrapBox { width, height }))
+}
+
+fn parse_size(size: &str, elem_id: &str) -> Result<f32> {
+    size.trim()
+        .trim_end_matches("px")
+        .parse()
+        .map_err(|_| anyhow!("The wrap size of '{elem_id}' is not a number: {size}"))
+}
+
+/// Wraps `text` within the box, lowering the font size until it fits.
+///
+/// The font size is never lowered below `min_font_size`. When the text does not fit at that
+/// size either, the result is wrapped at `min_font_size` and marked as not fitting.
+pub fn fit_text(
+    text: &str,
+    wrap_box: WrapBox,
+    text_style: &TextStyle,
+    min_font_size: f32,
+) -> Result<FittedText> {
+    text_style.with_measure(|measure| {
+        let mut font_size = text_style.font_size;
+
+        loop {
+            // Widths and line heights scale with the font size, so the text is only measured
+            // at the original size.
+            let scale = font_size / text_style.font_size;
+            let measure_scaled = |line: &str| measure(line) * scale;
+
+            let lines = wrap_text(text, wrap_box.width, &measure_scaled);
+            let fits = fits_in_box(&lines, wrap_box, font_size, text_style.line_height * scale)
+                && lines
+                    .iter()
+                    .all(|line| measure_scaled(line) <= wrap_box.width);
+
+            let next_font_size = font_size - FONT_SIZE_STEP;
+            if fits || next_font_size < min_font_size {
+                return FittedText {
+                    lines,
+                    font_size,
+                    fits,
+                };
+            }
+
+            font_size = next_font_size;
+        }
+    })
+}
+
+fn fits_in_box(lines: &[String], wrap_box: WrapBox, font_size: f32, line_height: f32) -> bool {
+    let text_height = (lines.len() - 1) as f32 * line_height + font_size;
+    wrap_box.height.is_none_or(|height| text_height <= height)
+}
+
+/// Splits `text` into lines that are at most `max_width` wide, breaking between words.
+///
+/// Existing line breaks are kept. A single word wider than `max_width` gets a line of its own.
+pub fn wrap_text(text: &str, max_width: f32, measure: &dyn Fn(&str) -> f32) -> Vec<String> {
+    let mut lines = vec![];
+
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{line} {word}")
+            };
+
+            if line.is_empty() || measure(&candidate) <= max_width {
+                line = candidate;
+            } else {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            }
+        }
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    use maplit::hashmap;
+
+    use crate::svg::{font_metrics::TextStyle, svg_editor::SvgEditor};
+
+    use super::{fit_text, wrap_box, wrap_text, WrapBox};
+
+    const WRAP_SVG: &str = r#"
+        <svg height='200' width='100'>
+            <rect id="box" x="0" y="0" width="80" height="40"/>
+            <text id="fixed" data-wrap-width="120" data-wrap-height="30"><tspan>fixed</tspan></text>
+            <text id="boxed" data-wrap-rect="box"><tspan>boxed</tspan></text>
+            <text id="free"><tspan>free</tspan></text>
+        </svg>"#;
+
+    fn char_count(text: &str) -> f32 {
+        text.chars().count() as f32
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        let lines = wrap_text("Good things come to those who wait", 12.0, &char_count);
+
+        assert_eq!(lines, vec!["Good things", "come to", "those who", "wait"]);
+    }
+
+    #[test]
+    fn test_wrap_text_keeps_line_breaks_and_long_words() {
+        let lines = wrap_text("Short\nUnbelievable luck", 8.0, &char_count);
+
+        assert_eq!(lines, vec!["Short", "Unbelievable", "luck"]);
+    }
+
+    #[test]
+    fn test_wrap_text_empty() {
+        assert_eq!(wrap_text("", 8.0, &char_count), vec![""]);
+    }
+
+    #[test]
+    fn test_wrap_box() -> Result<()> {
+        let temp_file = test_utils::create_temp_file("wrap.svg", WRAP_SVG);
+        let svg_editor = SvgEditor::open(&temp_file.path)?;
+
+        let box_of = |id| wrap_box(&svg_editor, svg_editor.get_elem_with_id(id).unwrap());
+
+        let fixed_box = WrapBox {
+            width: 120.0,
+            height: Some(30.0),
+        };
+        let rect_box = WrapBox {
+            width: 80.0,
+            height: Some(40.0),
+        };
+        assert_eq!(box_of("fixed")?, Some(fixed_box));
+        assert_eq!(box_of("boxed")?, Some(rect_box));
+        assert_eq!(box_of("free")?, None);
+
+        Ok(())
+    }
+
+    fn text_style(font_size: &str) -> TextStyle {
+        TextStyle::from_attr(&hashmap! {
+            "font-family".to_string() => "Dosis".to_string(),
+            "font-size".to_string() => font_size.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_fit_text_keeps_font_size_when_it_fits() -> Result<()> {
+        let wrap_box = WrapBox {
+            width: 200.0,
+            height: Some(20.0),
+        };
+
+        let fitted_text = fit_text("Great luck", wrap_box, &text_style("12"), 6.0)?;
+
+        assert!(fitted_text.fits);
+        assert_eq!(fitted_text.font_size, 12.0);
+        assert_eq!(fitted_text.lines, vec!["Great luck"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fit_text_shrinks_font_size() -> Result<()> {
+        let text = "The person you are waiting for will come, but later than you hope";
+        let wrap_box = WrapBox {
+            width: 100.0,
+            height: Some(30.0),
+        };
+
+        let fitted_text = fit_text(text, wrap_box, &text_style("12"), 4.0)?;
+
+        assert!(fitted_text.fits);
+        assert!(fitted_text.font_size < 12.0);
+        assert!(fitted_text.font_size >= 4.0);
+        assert_eq!(fitted_text.lines.join(" "), text);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fit_text_stops_at_min_font_size() -> Result<()> {
+        let text = "The person you are waiting for will come, but later than you hope";
+        let wrap_box = WrapBox {
+            width: 100.0,
+            height: Some(12.0),
+        };
+
+        let fitted_text = fit_text(text, wrap_box, &text_style("12"), 10.0)?;
+
+        assert!(!fitted_text.fits);
+        assert_eq!(fitted_text.font_size, 10.0);
+
+        Ok(())
+    }
+}
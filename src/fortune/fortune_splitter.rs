@@ -1,37 +1,104 @@
-use std::collections::HashMap;
+use std::{cmp::Reverse, collections::HashMap};
 
 use anyhow::{anyhow, Result};
-use rand::{seq::SliceRandom, thread_rng};
+use indexmap::IndexMap;
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    thread_rng, Rng, SeedableRng,
+};
 
-use super::{fortune_data::fortune_settings::FortuneSettings, FortuneData};
+use self::{
+    combinations::{cycle_shuffled, make_rows_unique},
+    luck_distribution::slips_per_luck_level,
+};
+
+use super::{
+    fortune_data::fortune_settings::{FortuneSettings, GenerationMode, LuckLevelStyle},
+    fortune_history::FortuneHistory,
+    FortuneData,
+};
+
+mod combinations;
+mod luck_distribution;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct FortuneSlipTextRef<'a> {
     pub header: &'a str,
     pub luck_level: &'a str,
+    pub style: &'a LuckLevelStyle,
     pub category_to_fortune: HashMap<&'a String, &'a String>,
+    /// The serial of a reprinted slip. Otherwise the writer numbers the slips in order.
+    pub serial: Option<&'a str>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct FortuneSplitter<'a> {
     fortune_data: &'a FortuneData,
     fortune_settings: &'a FortuneSettings,
+    seed: u64,
+    generation_mode: GenerationMode,
+    history: Option<&'a FortuneHistory>,
 }
 
 impl<'a> FortuneSplitter<'a> {
+    /// Creates a splitter with a random seed.
     pub fn new(fortune_data: &'a FortuneData) -> Self {
+        Self::with_seed(fortune_data, thread_rng().gen())
+    }
+
+    /// Creates a splitter that always shuffles the same way for the same seed and fortune data.
+    ///
+    /// Example:
+    /// ```
+    /// # use anyhow::Ok;
+    /// use fortune_generator::fortune_data::FortuneData;
+    /// use fortune_generator::fortune_splitter::FortuneSplitter;
+    ///
+    /// let fortune_data = FortuneData::open("test_utils/data/fortune_settings.yaml")?;
+    /// let first_run = FortuneSplitter::with_seed(&fortune_data, 42).shuffle_and_split()?;
+    /// let second_run = FortuneSplitter::with_seed(&fortune_data, 42).shuffle_and_split()?;
+    ///
+    /// assert_eq!(first_run, second_run);
+    /// # Ok(())
+    /// ```
+    pub fn with_seed(fortune_data: &'a FortuneData, seed: u64) -> Self {
         FortuneSplitter {
             fortune_data,
             fortune_settings: fortune_data.get_settings(),
+            seed,
+            generation_mode: fortune_data.get_settings().generation_mode,
+            history: None,
         }
     }
 
+    /// Sets the fortunes of earlier runs, so fortunes that were not printed recently are picked
+    /// first.
+    ///
+    /// With a history, the same seed only gives the same slips for the same history.
+    pub fn set_history(&mut self, history: &'a FortuneHistory) {
+        self.history = Some(history);
+    }
+
+    /// Sets whether fortunes may be reused to reach the number of slips.
+    ///
+    /// By default, the mode is picked by the `generation_mode` key in the fortune settings.
+    pub fn set_generation_mode(&mut self, generation_mode: GenerationMode) {
+        self.generation_mode = generation_mode;
+    }
+
+    /// Returns the seed used to shuffle the fortunes.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
     /// Creates and returns a randomized vector of fortune slips.
     ///
     /// The fortune slips themselves are groups of references for each slip.
     ///
-    /// The factory creates as many slips as possible without duplication by finding
-    /// for every luck level the topic with the least fortunes.
+    /// By default, the factory creates as many slips as possible without duplication by finding
+    /// for every luck level the topic with the least fortunes. The `count` and `weight` of the
+    /// luck levels and `total_slips` in the settings change how many slips every luck level gets.
     ///
     /// Example:
     /// ```
@@ -48,55 +115,146 @@ impl<'a> FortuneSplitter<'a> {
     /// # Ok(())
     /// ```
     pub fn shuffle_and_split(&self) -> Result<Vec<FortuneSlipTextRef<'a>>> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+
+        // In rank order, so the slips are shuffled in the same order every run and the best
+        // luck comes first in the output.
         let luck_level_keys = self.fortune_data.get_luck_level_keys();
 
+        let nr_slips_per_luck_level = self.nr_slips_per_luck_level()?;
         let mut all_slips = vec![];
 
         for key in luck_level_keys {
-            let slips = self.create_random_slips_for_luck(key)?;
+            let nr_slips = nr_slips_per_luck_level
+                .get(key)
+                .copied()
+                .unwrap_or_default();
+            let slips = self.create_random_slips_for_luck(key, nr_slips, &mut rng)?;
             all_slips.extend(slips);
         }
 
-        // Not necessary, but output PDFs are more pleasing this way.
-        all_slips.sort_by_key(|key| key.luck_level);
-
         Ok(all_slips)
     }
 
+    /// Draws a single slip of the luck level, or otherwise of a random luck level, weighted by
+    /// how many slips every luck level gets.
+    ///
+    /// Example:
+    /// ```
+    /// # use anyhow::Ok;
+    /// use fortune_generator::fortune_data::FortuneData;
+    /// use fortune_generator::fortune_splitter::FortuneSplitter;
+    ///
+    /// let fortune_data = FortuneData::open("test_utils/data/fortune_settings.yaml")?;
+    /// let fortune_slip = FortuneSplitter::new(&fortune_data).draw(Some("bad_luck"))?;
+    ///
+    /// assert_eq!(fortune_slip.luck_level, "Bad Luck");
+    /// # Ok(())
+    /// ```
+    pub fn draw(&self, luck_level_key: Option<&str>) -> Result<FortuneSlipTextRef<'a>> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+
+        let luck_level_key = match luck_level_key {
+            Some(key) => key.to_string(),
+            None => {
+                let nr_slips_per_luck_level = self.nr_slips_per_luck_level()?;
+                let weights = WeightedIndex::new(nr_slips_per_luck_level.values())
+                    .map_err(|_| anyhow!("None of the luck levels has any slips to draw."))?;
+                let (key, _) = nr_slips_per_luck_level
+                    .get_index(weights.sample(&mut rng))
+                    .expect("there is a weight for every luck level");
+                key.clone()
+            }
+        };
+
+        self.create_random_slips_for_luck(&luck_level_key, 1, &mut rng)?
+            .pop()
+            .ok_or(anyhow!(
+                "No fortunes to draw for the luck level {luck_level_key}."
+            ))
+    }
+
+    /// Returns how many slips every luck level gets, following the settings.
+    ///
+    /// Without reuse, a luck level can have as many slips as its category with the least
+    /// fortunes. With reuse, it can have a slip for every combination of fortunes.
+    fn nr_slips_per_luck_level(&self) -> Result<IndexMap<String, usize>> {
+        let mut default_slips = HashMap::new();
+        let mut max_slips = HashMap::new();
+
+        for key in self.fortune_data.get_luck_level_keys() {
+            let nr_fortunes = self.nr_fortunes_per_category(key);
+            let unique_slips = nr_fortunes.iter().copied().min().unwrap_or_default();
+
+            let max = match self.generation_mode {
+                GenerationMode::Unique => unique_slips,
+                GenerationMode::Reuse => nr_fortunes
+                    .iter()
+                    .fold(1usize, |product, &nr| product.saturating_mul(nr)),
+            };
+
+            default_slips.insert(key.clone(), unique_slips);
+            max_slips.insert(key.clone(), max);
+        }
+
+        slips_per_luck_level(
+            &self.fortune_settings.luck_levels,
+            self.fortune_settings.total_slips,
+            &default_slips,
+            &max_slips,
+        )
+    }
+
     fn create_random_slips_for_luck(
         &self,
         luck_level_key: &String,
+        nr_slips: usize,
+        rng: &mut StdRng,
     ) -> Result<Vec<FortuneSlipTextRef<'a>>> {
-        let slips_per_category = self.get_shuffled_fortunes_per_category(luck_level_key)?;
-
-        let nr_fortunes = self.max_nr_of_fortunes(&slips_per_category)?;
+        let slips_per_category =
+            self.get_shuffled_fortunes_per_category(luck_level_key, nr_slips, rng)?;
 
-        (0..nr_fortunes)
+        (0..nr_slips)
             .map(|idx| self.get_slip_for_idx(idx, &slips_per_category, luck_level_key))
             .collect()
     }
 
+    /// Returns `nr_slips` shuffled fortunes for every category.
+    ///
+    /// When a category has fewer fortunes than `nr_slips`, its fortunes are repeated as evenly as
+    /// possible, and the fortunes are rearranged so no two slips get the exact same fortunes.
     fn get_shuffled_fortunes_per_category(
         &self,
         luck_level_key: &String,
+        nr_slips: usize,
+        rng: &mut StdRng,
     ) -> Result<HashMap<&'a String, Vec<&'a String>>> {
-        let luck_categories = self.fortune_data.get_categories();
-        let mut category_to_slips = HashMap::new();
+        let mut luck_categories = self.fortune_data.get_categories();
+        luck_categories.sort();
 
-        for &luck_category in &luck_categories {
-            let fortune_slips = self.get_fortunes_shuffled(luck_category, luck_level_key)?;
-            category_to_slips.insert(luck_category, fortune_slips);
-        }
+        let mut fortunes_per_category = luck_categories
+            .iter()
+            .map(|luck_category| {
+                self.get_fortunes_shuffled(luck_category, luck_level_key, nr_slips, rng)
+            })
+            .collect::<Result<Vec<_>>>()?;
 
-        Ok(category_to_slips)
+        make_rows_unique(&mut fortunes_per_category)?;
+
+        Ok(luck_categories
+            .into_iter()
+            .zip(fortunes_per_category)
+            .collect())
     }
 
     fn get_fortunes_shuffled(
         &self,
         luck_category: &String,
         luck_level_key: &String,
+        nr_slips: usize,
+        rng: &mut StdRng,
     ) -> Result<Vec<&'a String>> {
-        let mut fortune_slips = self
+        let fortunes = self
             .fortune_data
             .get_fortune_text(luck_category, luck_level_key)
             .ok_or(anyhow!(
@@ -104,23 +262,30 @@ impl<'a> FortuneSplitter<'a> {
                 luck_category,
                 luck_level_key
             ))?;
-        fortune_slips.shuffle(&mut thread_rng());
 
-        Ok(fortune_slips)
+        // Fortunes that were never printed come first, then the ones printed longest ago.
+        let runs_since_printed = |fortune: &&String| {
+            self.history
+                .and_then(|history| history.runs_since_printed(luck_category, fortune))
+                .unwrap_or(usize::MAX)
+        };
+
+        Ok(cycle_shuffled(&fortunes, nr_slips, rng, |fortune| {
+            Reverse(runs_since_printed(fortune))
+        }))
     }
 
-    fn max_nr_of_fortunes(
-        &self,
-        slips_per_category: &HashMap<&String, Vec<&String>>,
-    ) -> Result<usize> {
-        slips_per_category
-            .values()
-            .map(|x| x.len())
-            .min()
-            .ok_or(anyhow!(
-                "Gathered slips are empty: {:?}.",
-                slips_per_category
-            ))
+    /// Returns the number of fortunes of every category for a luck level.
+    fn nr_fortunes_per_category(&self, luck_level_key: &str) -> Vec<usize> {
+        self.fortune_data
+            .get_categories()
+            .into_iter()
+            .map(|category| {
+                self.fortune_data
+                    .get_fortune_text(category, luck_level_key)
+                    .map_or(0, |fortunes| fortunes.len())
+            })
+            .collect()
     }
 
     fn get_slip_for_idx(
@@ -140,7 +305,9 @@ impl<'a> FortuneSplitter<'a> {
         Ok(FortuneSlipTextRef {
             header: &luck_level_info.jap,
             luck_level: &luck_level_info.eng,
+            style: &luck_level_info.style,
             category_to_fortune: cat_to_fort,
+            serial: None,
         })
     }
 
@@ -165,7 +332,18 @@ impl<'a> FortuneSplitter<'a> {
 
 #[cfg(test)]
 mod tests {
-    use crate::fortune::{fortune_data::FortuneData, fortune_splitter::FortuneSplitter};
+    use std::collections::{HashMap, HashSet};
+
+    use itertools::Itertools;
+
+    use crate::fortune::{
+        fortune_data::{
+            fortune_settings::{GenerationMode, LuckLevelStyle},
+            FortuneData,
+        },
+        fortune_history::{FortuneHistory, HistoryRun},
+        fortune_splitter::{FortuneSlipTextRef, FortuneSplitter},
+    };
 
     #[test]
     fn test_create_fortune_slips() -> anyhow::Result<()> {
@@ -192,4 +370,149 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_create_fortune_slips_prefers_fortunes_not_in_history() -> anyhow::Result<()> {
+        let mut fortune_data = FortuneData::open("test_utils/data/fortune_settings.yaml")?;
+        fortune_data.get_settings_mut().total_slips = Some(2);
+        let love = "love".to_string();
+        let good_luck = "good_luck".to_string();
+        let good_luck_love = fortune_data.get_fortune_text(&love, &good_luck).unwrap();
+
+        // All but one of the fortunes were printed, the first one longest ago.
+        let mut history = FortuneHistory::default();
+        for printed in &good_luck_love[..2] {
+            let slip = FortuneSlipTextRef {
+                header: "",
+                luck_level: "",
+                style: &LuckLevelStyle::default(),
+                category_to_fortune: HashMap::from([(&love, *printed)]),
+                serial: None,
+            };
+            history.add_run(HistoryRun::new("2025-04-05", 0, &[slip]), 3);
+        }
+
+        for seed in 0..10 {
+            let mut fortune_splitter = FortuneSplitter::with_seed(&fortune_data, seed);
+            fortune_splitter.set_history(&history);
+            let fortune_slips = fortune_splitter.shuffle_and_split()?;
+
+            let good_luck_slip = fortune_slips
+                .iter()
+                .find(|x| x.luck_level == "Good Luck")
+                .unwrap();
+            assert_eq!(good_luck_slip.category_to_fortune[&love], good_luck_love[2]);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_fortune_slips_with_total() -> anyhow::Result<()> {
+        let mut fortune_data = FortuneData::open("test_utils/data/fortune_settings.yaml")?;
+        let settings = fortune_data.get_settings_mut();
+        settings.total_slips = Some(4);
+        settings.luck_levels.get_mut("bad_luck").unwrap().count = Some(1);
+
+        let fortune_slips = FortuneSplitter::new(&fortune_data).shuffle_and_split()?;
+
+        assert_eq!(fortune_slips.len(), 4);
+        assert_eq!(
+            fortune_slips
+                .iter()
+                .filter(|x| x.luck_level == "Bad Luck")
+                .count(),
+            1
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_fortune_slips_in_rank_order() -> anyhow::Result<()> {
+        let mut fortune_data = FortuneData::open("test_utils/data/fortune_settings.yaml")?;
+        let luck_levels: Vec<_> = FortuneSplitter::new(&fortune_data)
+            .shuffle_and_split()?
+            .iter()
+            .map(|slip| slip.luck_level)
+            .dedup()
+            .collect();
+        assert_eq!(luck_levels, vec!["Good Luck", "Bad Luck"]);
+
+        let settings = fortune_data.get_settings_mut();
+        settings.luck_levels.move_index(1, 0);
+        let luck_levels: Vec<_> = FortuneSplitter::new(&fortune_data)
+            .shuffle_and_split()?
+            .iter()
+            .map(|slip| slip.luck_level)
+            .dedup()
+            .collect();
+        assert_eq!(luck_levels, vec!["Bad Luck", "Good Luck"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_fortune_slips_total_too_high() -> anyhow::Result<()> {
+        let mut fortune_data = FortuneData::open("test_utils/data/fortune_settings.yaml")?;
+        fortune_data.get_settings_mut().total_slips = Some(20);
+
+        let result = FortuneSplitter::new(&fortune_data).shuffle_and_split();
+
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_fortune_slips_with_reuse() -> anyhow::Result<()> {
+        let mut fortune_data = FortuneData::open("test_utils/data/fortune_settings.yaml")?;
+        fortune_data.get_settings_mut().total_slips = Some(20);
+
+        let mut fortune_splitter = FortuneSplitter::with_seed(&fortune_data, 5);
+        fortune_splitter.set_generation_mode(GenerationMode::Reuse);
+        let fortune_slips = fortune_splitter.shuffle_and_split()?;
+
+        assert_eq!(fortune_slips.len(), 20);
+
+        // No two slips have the exact same fortunes.
+        let combinations: HashSet<_> = fortune_slips
+            .iter()
+            .map(|slip| {
+                let mut fortunes: Vec<_> = slip.category_to_fortune.iter().collect();
+                fortunes.sort();
+                fortunes
+            })
+            .collect();
+        assert_eq!(combinations.len(), 20);
+
+        // Every love fortune of a luck level is used about as often as the others.
+        for luck_level in ["Good Luck", "Bad Luck"] {
+            let mut uses: HashMap<&String, usize> = HashMap::new();
+            for slip in fortune_slips.iter().filter(|x| x.luck_level == luck_level) {
+                *uses
+                    .entry(slip.category_to_fortune[&"love".to_string()])
+                    .or_default() += 1;
+            }
+            let min = uses.values().min().unwrap();
+            let max = uses.values().max().unwrap();
+            assert!(max - min <= 1, "{luck_level}: {uses:?}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_same_seed_same_fortune_slips() -> anyhow::Result<()> {
+        let fortune_data = FortuneData::open("test_utils/data/fortune_settings.yaml")?;
+
+        let fortune_splitter = FortuneSplitter::with_seed(&fortune_data, 1234);
+        assert_eq!(fortune_splitter.seed(), 1234);
+
+        let first_run = fortune_splitter.shuffle_and_split()?;
+        let second_run = FortuneSplitter::with_seed(&fortune_data, 1234).shuffle_and_split()?;
+        assert_eq!(first_run, second_run);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,281 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+use anyhow::{anyhow, bail, Result};
+use rand::{rngs::StdRng, seq::SliceRandom};
+
+/// Returns `nr` items from the pool, going through the whole pool before repeating an item.
+///
+/// Every round through the pool is shuffled again, so every item is used either
+/// `nr / pool.len()` times or once more. Every round starts with the items with the lowest key,
+/// so a round that does not go through the whole pool prefers those items.
+pub fn cycle_shuffled<T: Clone, K: Ord>(
+    pool: &[T],
+    nr: usize,
+    rng: &mut StdRng,
+    key: impl Fn(&T) -> K,
+) -> Vec<T> {
+    let mut items = Vec::with_capacity(nr);
+
+    while items.len() < nr && !pool.is_empty() {
+        let mut round = pool.to_vec();
+        round.shuffle(rng);
+        // A stable sort, so the items with the same key stay shuffled.
+        round.sort_by_key(|item| key(item));
+        items.extend(round.into_iter().take(nr - items.len()));
+    }
+
+    items
+}
+
+/// Rearranges the items within the columns so that no two rows have the exact same items.
+///
+/// Row `i` consists of the `i`-th item of every column. Rearranging within a column keeps how
+/// often every item is used, so repeats stay as evenly spread as they are. Rows that are already
+/// unique keep their place as far as possible.
+pub fn make_rows_unique<T: Clone + Eq + Hash>(columns: &mut [Vec<T>]) -> Result<()> {
+    let nr_rows = columns.iter().map(Vec::len).min().unwrap_or_default();
+    let (items, ids): (Vec<_>, Vec<_>) = columns
+        .iter()
+        .map(|column| item_ids(&column[..nr_rows]))
+        .unzip();
+    let rows = (0..nr_rows)
+        .map(|row_idx| ids.iter().map(|column| column[row_idx]).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    if rows.iter().collect::<HashSet<_>>().len() == nr_rows {
+        return Ok(());
+    }
+
+    let Some(unique_rows) = distinct_rows(&ids, nr_rows) else {
+        let nr_combinations = items
+            .iter()
+            .try_fold(1usize, |product, column| product.checked_mul(column.len()));
+        match nr_combinations {
+            Some(nr_combinations) if nr_combinations < nr_rows => bail!(
+                "Could not create {nr_rows} slips without repeating the exact same fortunes on \
+                 two slips: the fortunes only make {nr_combinations} different slips"
+            ),
+            _ => bail!(
+                "Could not create {nr_rows} slips without repeating the exact same fortunes on \
+                 two slips, as some fortunes are repeated too often"
+            ),
+        }
+    };
+
+    // The rows that were already unique stay where they are, the others are filled in order.
+    let mut unused: HashSet<&Vec<usize>> = unique_rows.iter().collect();
+    let kept = rows
+        .iter()
+        .map(|row| unused.remove(row))
+        .collect::<Vec<_>>();
+    let mut leftovers = unique_rows.iter().filter(|row| unused.contains(row));
+    let new_rows = rows
+        .iter()
+        .zip(kept)
+        .map(|(row, kept)| if kept { Some(row) } else { leftovers.next() })
+        .collect::<Option<Vec<_>>>()
+        .ok_or(anyhow!("Not every slip got a combination of fortunes"))?;
+
+    for (col, column) in columns.iter_mut().enumerate() {
+        for (row_idx, row) in new_rows.iter().enumerate() {
+            column[row_idx] = items[col][row[col]].clone();
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the distinct items of the column, in order of appearance, and the index of the item
+/// at every position.
+fn item_ids<T: Clone + Eq + Hash>(column: &[T]) -> (Vec<T>, Vec<usize>) {
+    let mut items = vec![];
+    let mut id_of: HashMap<&T, usize> = HashMap::new();
+    let ids = column
+        .iter()
+        .map(|item| {
+            *id_of.entry(item).or_insert_with(|| {
+                items.push(item.clone());
+                items.len() - 1
+            })
+        })
+        .collect();
+
+    (items, ids)
+}
+
+/// Returns `nr_rows` distinct rows of item ids, which use every item of a column as often as
+/// `ids` does, or `None` when it finds no such rows.
+///
+/// The rows are built up one column at a time. The rows with the same items so far form a group,
+/// and every group takes the items that are left most often, each item once before any item
+/// twice. For the last column, this finds distinct rows whenever they exist (Gale-Ryser); the
+/// earlier columns split the groups as evenly as they can, which is enough for items that are
+/// repeated evenly.
+fn distinct_rows(ids: &[Vec<usize>], nr_rows: usize) -> Option<Vec<Vec<usize>>> {
+    let mut groups: Vec<(Vec<usize>, usize)> = vec![(vec![], nr_rows)];
+
+    for column in ids {
+        let nr_items = column.iter().max().map_or(0, |max_id| max_id + 1);
+        let mut remaining = vec![0; nr_items];
+        for &id in column {
+            remaining[id] += 1;
+        }
+
+        // A stable sort, so the groups of the same size keep the order of the items.
+        groups.sort_by_key(|(_, size)| Reverse(*size));
+        let mut new_groups = vec![];
+
+        for (prefix, size) in groups {
+            let mut taken = vec![0; nr_items];
+            for _ in 0..size {
+                let id = (0..nr_items)
+                    .filter(|&id| remaining[id] > 0)
+                    .min_by_key(|&id| (taken[id], Reverse(remaining[id])))?;
+                taken[id] += 1;
+                remaining[id] -= 1;
+            }
+
+            for (id, &nr_taken) in taken.iter().enumerate().filter(|(_, &nr)| nr > 0) {
+                let mut row = prefix.clone();
+                row.push(id);
+                new_groups.push((row, nr_taken));
+            }
+        }
+
+        groups = new_groups;
+    }
+
+    groups
+        .into_iter()
+        .map(|(row, size)| (size == 1).then_some(row))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use pretty_assertions::assert_eq;
+    use rand::{rngs::StdRng, SeedableRng};
+    use rstest::rstest;
+
+    use super::{cycle_shuffled, make_rows_unique};
+
+    #[test]
+    fn test_cycle_shuffled_spreads_evenly() {
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let items = cycle_shuffled(&[1, 2, 3], 8, &mut rng, |_| ());
+
+        assert_eq!(items.len(), 8);
+        let mut counts: HashMap<i32, usize> = HashMap::new();
+        for item in items {
+            *counts.entry(item).or_default() += 1;
+        }
+        assert!(counts.values().all(|&count| count == 2 || count == 3));
+    }
+
+    #[test]
+    fn test_cycle_shuffled_no_repeats_within_pool() {
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let items = cycle_shuffled(&[1, 2, 3, 4], 3, &mut rng, |_| ());
+
+        assert_eq!(items.iter().collect::<HashSet<_>>().len(), 3);
+    }
+
+    #[test]
+    fn test_cycle_shuffled_prefers_lowest_keys() {
+        for seed in 0..10 {
+            let mut rng = StdRng::seed_from_u64(seed);
+
+            let items = cycle_shuffled(&[1, 2, 3, 4, 5], 7, &mut rng, |&item| item > 3);
+
+            let mut first_round = items[..5].to_vec();
+            first_round.sort();
+            assert_eq!(first_round, vec![1, 2, 3, 4, 5]);
+            assert!(items[5..].iter().all(|&item| item <= 3));
+        }
+    }
+
+    #[test]
+    fn test_make_rows_unique() {
+        // All 6 combinations of 2 x 3 items, but the first four rows are only 2 combinations.
+        let mut columns = vec![vec![1, 2, 1, 2, 1, 2], vec![5, 6, 5, 6, 7, 7]];
+        let mut sorted_before = columns.clone();
+
+        make_rows_unique(&mut columns).unwrap();
+
+        let rows: HashSet<_> = (0..6)
+            .map(|idx| (columns[0][idx], columns[1][idx]))
+            .collect();
+        assert_eq!(rows.len(), 6);
+
+        // Every item is still used as often as before.
+        let mut sorted_after = columns.clone();
+        sorted_after.iter_mut().for_each(|column| column.sort());
+        sorted_before.iter_mut().for_each(|column| column.sort());
+        assert_eq!(sorted_after, sorted_before);
+    }
+
+    #[rstest]
+    #[case::two_columns(&[3, 5])]
+    #[case::three_columns(&[2, 3, 4])]
+    #[case::shared_factors(&[2, 4, 6])]
+    fn test_make_rows_unique_up_to_all_combinations(#[case] pool_sizes: &[usize]) {
+        let nr_combinations: usize = pool_sizes.iter().product();
+
+        for nr_rows in 1..=nr_combinations {
+            // Every column cycles through its pool, so the rows repeat after the least common
+            // multiple of the pool sizes, long before all combinations are used.
+            let mut columns = pool_sizes
+                .iter()
+                .map(|&pool_size| (0..nr_rows).map(|idx| idx % pool_size).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            let mut sorted_before = columns.clone();
+
+            make_rows_unique(&mut columns).unwrap();
+
+            let rows: HashSet<Vec<usize>> = (0..nr_rows)
+                .map(|idx| columns.iter().map(|column| column[idx]).collect())
+                .collect();
+            assert_eq!(rows.len(), nr_rows);
+
+            let mut sorted_after = columns.clone();
+            sorted_after.iter_mut().for_each(|column| column.sort());
+            sorted_before.iter_mut().for_each(|column| column.sort());
+            assert_eq!(sorted_after, sorted_before);
+        }
+    }
+
+    #[test]
+    fn test_make_rows_unique_keeps_unique_rows_in_place() {
+        let mut columns = vec![vec![1, 2, 1, 2, 3], vec![5, 6, 5, 6, 7]];
+
+        make_rows_unique(&mut columns).unwrap();
+
+        assert_eq!((columns[0][0], columns[1][0]), (1, 5));
+        assert_eq!((columns[0][1], columns[1][1]), (2, 6));
+        assert_eq!((columns[0][4], columns[1][4]), (3, 7));
+    }
+
+    #[test]
+    fn test_make_rows_unique_impossible() {
+        let mut columns = vec![vec![1, 1], vec![5, 5]];
+
+        assert!(make_rows_unique(&mut columns).is_err());
+    }
+
+    #[test]
+    fn test_make_rows_unique_too_few_combinations() {
+        let mut columns = vec![vec![1, 2, 1, 2, 1], vec![5, 6, 5, 6, 5]];
+
+        let error = make_rows_unique(&mut columns).unwrap_err();
+
+        assert!(error.to_string().contains("only make 4 different slips"));
+    }
+}
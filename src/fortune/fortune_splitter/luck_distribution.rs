@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use indexmap::IndexMap;
+
+use crate::fortune::fortune_data::fortune_settings::LuckLevelInfo;
+
+/// Weights may add up to slightly more than 1 due to rounding, e.g. three times `0.333334`.
+const WEIGHT_TOLERANCE: f64 = 1e-6;
+
+/// Returns the number of slips to create for every luck level.
+///
+/// `max_slips` is the number of different slips a luck level can have, and `default_slips` the
+/// number of slips it gets when nothing else is set.
+///
+/// Without `total_slips`, a luck level gets its `count`, or otherwise its `default_slips`.
+/// With `total_slips`, the slips are divided so they add up exactly:
+///
+/// 1. Luck levels with a `count` get exactly that many slips.
+/// 2. Luck levels with a `weight` get that share of `total_slips`.
+/// 3. The other luck levels share the remaining slips equally.
+///
+/// The result is in the order of `luck_levels`, and when slips are left after rounding, the
+/// earlier luck levels get them first.
+///
+/// Returns an error when this is not possible, e.g. when the counts add up to more than
+/// `total_slips` or when a luck level needs more slips than its fortunes allow.
+pub fn slips_per_luck_level(
+    luck_levels: &IndexMap<String, LuckLevelInfo>,
+    total_slips: Option<usize>,
+    default_slips: &HashMap<String, usize>,
+    max_slips: &HashMap<String, usize>,
+) -> Result<IndexMap<String, usize>> {
+    let nr_slips = match total_slips {
+        Some(total_slips) => divide_total(luck_levels, total_slips)?,
+        None => without_total(luck_levels, default_slips)?,
+    };
+
+    for (key, &nr) in &nr_slips {
+        let max = max_slips.get(key).copied().unwrap_or_default();
+        if nr > max {
+            bail!(
+                "Luck level '{key}' needs {nr} slips, but its fortunes only allow {max} different \
+                 slips. Add more fortunes, lower its count or weight, or use the reuse \
+                 generation_mode."
+            );
+        }
+    }
+
+    Ok(nr_slips)
+}
+
+fn without_total(
+    luck_levels: &IndexMap<String, LuckLevelInfo>,
+    default_slips: &HashMap<String, usize>,
+) -> Result<IndexMap<String, usize>> {
+    luck_levels
+        .iter()
+        .map(|(key, info)| {
+            if info.weight.is_some() {
+                bail!("Luck level '{key}' has a weight, which needs total_slips in the settings");
+            }
+
+            let nr = info
+                .count
+                .unwrap_or_else(|| default_slips.get(key).copied().unwrap_or_default());
+            Ok((key.clone(), nr))
+        })
+        .collect()
+}
+
+fn divide_total(
+    luck_levels: &IndexMap<String, LuckLevelInfo>,
+    total_slips: usize,
+) -> Result<IndexMap<String, usize>> {
+    let counted: usize = luck_levels.values().filter_map(|info| info.count).sum();
+    if counted > total_slips {
+        bail!(
+            "The counts of the luck levels add up to {counted}, \
+             more than the {total_slips} total slips"
+        );
+    }
+
+    let mut weight_sum = 0.0;
+    for (key, info) in luck_levels {
+        match (info.count, info.weight) {
+            (Some(_), Some(_)) => bail!("Luck level '{key}' has both a count and a weight"),
+            (None, Some(weight)) if !weight.is_finite() || weight < 0.0 => {
+                bail!("Luck level '{key}' needs a weight of zero or more, not {weight}")
+            }
+            (None, Some(weight)) => weight_sum += weight,
+            _ => {}
+        }
+    }
+
+    let nr_unspecified = luck_levels
+        .values()
+        .filter(|info| info.count.is_none() && info.weight.is_none())
+        .count();
+
+    let remaining = (total_slips - counted) as f64;
+    let weighted = weight_sum * total_slips as f64;
+
+    if weighted > remaining + WEIGHT_TOLERANCE * total_slips as f64 {
+        bail!(
+            "The weights of the luck levels add up to {weighted} of the {total_slips} total slips, \
+             but only {remaining} slips are left after the counts"
+        );
+    }
+
+    let unspecified_share = if nr_unspecified > 0 {
+        (remaining - weighted).max(0.0) / nr_unspecified as f64
+    } else if (remaining - weighted).abs() > WEIGHT_TOLERANCE * total_slips as f64 {
+        bail!(
+            "The counts and weights of the luck levels add up to {} of the {total_slips} total \
+             slips. Change them, or leave out the count and weight of a luck level to fill up \
+             the rest.",
+            counted as f64 + weighted
+        );
+    } else {
+        0.0
+    };
+
+    let shares: IndexMap<String, f64> = luck_levels
+        .iter()
+        .filter(|(_, info)| info.count.is_none())
+        .map(|(key, info)| {
+            let share = match info.weight {
+                Some(weight) => weight * total_slips as f64,
+                None => unspecified_share,
+            };
+            (key.clone(), share)
+        })
+        .collect();
+
+    let mut rounded = round_to_total(&shares, total_slips - counted);
+    let nr_slips = luck_levels
+        .iter()
+        .map(|(key, info)| {
+            let nr = info
+                .count
+                .unwrap_or_else(|| rounded.swap_remove(key).unwrap_or_default());
+            (key.clone(), nr)
+        })
+        .collect();
+
+    Ok(nr_slips)
+}
+
+/// Rounds the shares down, then gives the slips that are left to the largest remainders.
+fn round_to_total(shares: &IndexMap<String, f64>, total: usize) -> IndexMap<String, usize> {
+    let mut rounded: IndexMap<String, usize> = shares
+        .iter()
+        .map(|(key, share)| (key.clone(), share.floor() as usize))
+        .collect();
+
+    let mut by_remainder: Vec<_> = shares.iter().collect();
+    // Stable sort, so equal remainders are handed out in rank order.
+    by_remainder.sort_by(|(_, a), (_, b)| (b.fract()).total_cmp(&a.fract()));
+
+    let nr_left = total.saturating_sub(rounded.values().sum());
+    for (key, _) in by_remainder.into_iter().cycle().take(nr_left) {
+        *rounded.get_mut(key).unwrap() += 1;
+    }
+
+    rounded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use indexmap::indexmap;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use crate::fortune::fortune_data::fortune_settings::{LuckLevelInfo, LuckLevelStyle};
+
+    use super::slips_per_luck_level;
+
+    fn luck_level(count: Option<usize>, weight: Option<f64>) -> LuckLevelInfo {
+        LuckLevelInfo {
+            jap: "吉".to_string(),
+            eng: "Luck".to_string(),
+            count,
+            weight,
+            rank: None,
+            style: LuckLevelStyle::default(),
+        }
+    }
+
+    fn for_all_keys(nr: usize) -> HashMap<String, usize> {
+        ["great", "good", "bad"]
+            .into_iter()
+            .map(|key| (key.to_string(), nr))
+            .collect()
+    }
+
+    #[test]
+    fn test_without_total_as_many_as_possible() {
+        let luck_levels = indexmap! {
+            "great".to_string() => luck_level(Some(2), None),
+            "good".to_string() => luck_level(None, None),
+            "bad".to_string() => luck_level(None, None),
+        };
+
+        let nr_slips =
+            slips_per_luck_level(&luck_levels, None, &for_all_keys(10), &for_all_keys(10)).unwrap();
+
+        assert_eq!(
+            nr_slips,
+            indexmap! {
+                "great".to_string() => 2,
+                "good".to_string() => 10,
+                "bad".to_string() => 10,
+            }
+        );
+    }
+
+    #[test]
+    fn test_weights_and_counts_meet_total() {
+        let luck_levels = indexmap! {
+            "great".to_string() => luck_level(None, Some(0.1)),
+            "good".to_string() => luck_level(None, None),
+            "bad".to_string() => luck_level(Some(5), None),
+        };
+
+        let nr_slips = slips_per_luck_level(
+            &luck_levels,
+            Some(100),
+            &for_all_keys(100),
+            &for_all_keys(100),
+        )
+        .unwrap();
+
+        assert_eq!(
+            nr_slips,
+            indexmap! {
+                "great".to_string() => 10,
+                "good".to_string() => 85,
+                "bad".to_string() => 5,
+            }
+        );
+    }
+
+    #[test]
+    fn test_rounding_meets_total() {
+        let luck_levels = indexmap! {
+            "great".to_string() => luck_level(None, None),
+            "good".to_string() => luck_level(None, None),
+            "bad".to_string() => luck_level(None, None),
+        };
+
+        let nr_slips =
+            slips_per_luck_level(&luck_levels, Some(10), &for_all_keys(10), &for_all_keys(10))
+                .unwrap();
+
+        assert_eq!(nr_slips.values().sum::<usize>(), 10);
+        assert!(nr_slips.values().all(|&nr| nr == 3 || nr == 4));
+    }
+
+    #[test]
+    fn test_rounding_favours_rank_order() {
+        let luck_levels = indexmap! {
+            "great".to_string() => luck_level(None, None),
+            "good".to_string() => luck_level(None, None),
+            "bad".to_string() => luck_level(None, None),
+        };
+
+        let nr_slips =
+            slips_per_luck_level(&luck_levels, Some(11), &for_all_keys(10), &for_all_keys(10))
+                .unwrap();
+
+        let ordered: Vec<_> = nr_slips.into_iter().collect();
+        assert_eq!(
+            ordered,
+            vec![
+                ("great".to_string(), 4),
+                ("good".to_string(), 4),
+                ("bad".to_string(), 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_total_cannot_be_met() {
+        let all_weighted = indexmap! {
+            "great".to_string() => luck_level(None, Some(0.5)),
+            "good".to_string() => luck_level(None, Some(0.2)),
+            "bad".to_string() => luck_level(Some(5), None),
+        };
+        let too_many_counts = indexmap! {
+            "great".to_string() => luck_level(Some(60), None),
+            "good".to_string() => luck_level(Some(60), None),
+            "bad".to_string() => luck_level(None, None),
+        };
+        let too_much_weight = indexmap! {
+            "great".to_string() => luck_level(None, Some(0.7)),
+            "good".to_string() => luck_level(None, Some(0.7)),
+            "bad".to_string() => luck_level(None, None),
+        };
+
+        for luck_levels in [all_weighted, too_many_counts, too_much_weight] {
+            assert!(slips_per_luck_level(
+                &luck_levels,
+                Some(100),
+                &for_all_keys(100),
+                &for_all_keys(100)
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn test_not_enough_fortunes() {
+        let luck_levels = indexmap! {
+            "great".to_string() => luck_level(None, Some(0.5)),
+            "good".to_string() => luck_level(None, None),
+            "bad".to_string() => luck_level(None, None),
+        };
+
+        let error = slips_per_luck_level(
+            &luck_levels,
+            Some(100),
+            &for_all_keys(40),
+            &for_all_keys(40),
+        )
+        .unwrap_err();
+
+        assert!(error.to_string().contains("'great' needs 50 slips"));
+    }
+
+    #[test]
+    fn test_default_slips_below_max() {
+        let luck_levels = indexmap! {
+            "great".to_string() => luck_level(Some(30), None),
+            "good".to_string() => luck_level(None, None),
+        };
+
+        let nr_slips =
+            slips_per_luck_level(&luck_levels, None, &for_all_keys(10), &for_all_keys(100))
+                .unwrap();
+
+        assert_eq!(nr_slips["great"], 30);
+        assert_eq!(nr_slips["good"], 10);
+    }
+
+    #[rstest]
+    #[case(-0.5)]
+    #[case(f64::NAN)]
+    #[case(f64::INFINITY)]
+    fn test_invalid_weight(#[case] weight: f64) {
+        let luck_levels = indexmap! {
+            "great".to_string() => luck_level(None, Some(weight)),
+            "good".to_string() => luck_level(None, None),
+        };
+
+        let error = slips_per_luck_level(
+            &luck_levels,
+            Some(100),
+            &for_all_keys(100),
+            &for_all_keys(100),
+        )
+        .unwrap_err();
+
+        assert!(error
+            .to_string()
+            .contains("'great' needs a weight of zero or more"));
+    }
+
+    #[test]
+    fn test_weight_needs_total() {
+        let luck_levels = indexmap! {
+            "great".to_string() => luck_level(None, Some(0.5)),
+        };
+
+        assert!(
+            slips_per_luck_level(&luck_levels, None, &for_all_keys(40), &for_all_keys(40)).is_err()
+        );
+    }
+}
@@ -13,53 +13,235 @@
 //! # std::fs::remove_file("fortune_slips.pdf")?;
 //! # Ok(())
 //! ```
-use std::env;
 
+use std::path::Path;
+
+use log::LevelFilter;
 use owo_colors::OwoColorize;
 use owo_colors::Stream;
 
-use crate::{cli::windows, error::Error};
+use crate::{
+    cli::{card, windows, CliArgs, Command},
+    error::Error,
+    fortune::{
+        fortune_data::fortune_settings::PaperSize,
+        fortune_manifest::{read_manifest, select_records, SlipSelector},
+    },
+    server::FortuneServer,
+};
 
 mod cli;
 mod constants;
 mod pdf;
+mod server;
 mod svg;
 
 pub mod error;
 pub mod fortune;
+pub mod renderer;
 
 pub use crate::fortune::fortune_data;
+pub use crate::fortune::fortune_manifest;
 pub use crate::fortune::fortune_splitter;
 pub use crate::fortune::FortuneGenerator;
 
 pub fn run() -> Result<(), Error> {
     let cli_args = cli::execute().map_err(|_| Error::ChecksFailed)?;
 
-    if cli_args.verbose {
-        enable_logging();
+    enable_logging(cli_args.verbose);
+
+    match cli_args.command {
+        Some(Command::Lint) => lint(&cli_args),
+        Some(Command::Reprint {
+            ref manifest,
+            ref slips,
+        }) => reprint(&cli_args, manifest, slips),
+        Some(Command::Draw { ref luck, json }) => draw(&cli_args, luck.as_deref(), json),
+        Some(Command::Serve { port }) => serve(&cli_args, port),
+        None => generate(&cli_args),
     }
+}
 
+fn generate(cli_args: &CliArgs) -> Result<(), Error> {
     println!("Generating fortunes...");
-    let fortune_generator = FortuneGenerator::open(&cli_args.config)
-        .map_err(|e| Error::FortuneSettingsLoadFailure(e.to_string()))?;
+    let fortune_generator = open_fortune_generator(cli_args)?;
+    println!("Using seed {}", fortune_generator.seed());
+
+    if cli_args.reset_history {
+        fortune_generator
+            .reset_history()
+            .map_err(|e| Error::PdfGenerateFailure(e.to_string()))?;
+    }
+
+    println!("Generating {}...", output_kind(cli_args));
+    fortune_generator
+        .generate(cli_args.output_path(), &cli_args.export_options())
+        .map_err(|e| Error::PdfGenerateFailure(e.to_string()))?;
 
-    println!("Generating PDF...");
+    print_success(cli_args);
+    Ok(())
+}
+
+fn reprint(cli_args: &CliArgs, manifest: &Path, slips: &[SlipSelector]) -> Result<(), Error> {
+    println!("Reprinting fortunes...");
+    let records = read_manifest(manifest)
+        .and_then(|records| select_records(&records, slips))
+        .map_err(|e| Error::PdfGenerateFailure(e.to_string()))?;
+
+    let mut fortune_generator = open_fortune_generator(cli_args)?;
+    // Keeps the `{{seed}}` of the slips, unless they come from several runs.
+    if let Some(first) = records.first() {
+        if cli_args.seed.is_none() && records.iter().all(|record| record.seed == first.seed) {
+            fortune_generator.set_seed(first.seed);
+        }
+    }
+
+    println!("Generating {}...", output_kind(cli_args));
     fortune_generator
-        .generate_to_pdf(&cli_args.output)
+        .reprint(&records, cli_args.output_path(), &cli_args.export_options())
         .map_err(|e| Error::PdfGenerateFailure(e.to_string()))?;
 
+    print_success(cli_args);
+    Ok(())
+}
+
+/// Opens the fortune settings and applies the CLI options for generating the slips.
+fn open_fortune_generator(cli_args: &CliArgs) -> Result<FortuneGenerator, Error> {
+    let mut fortune_generator = FortuneGenerator::open(&cli_args.config)
+        .map_err(|e| Error::FortuneSettingsLoadFailure(e.to_string()))?;
+
+    if let Some(render_backend) = cli_args.renderer {
+        fortune_generator.set_renderer(renderer::for_backend(render_backend));
+    }
+
+    if let Some(duplex) = cli_args.duplex {
+        fortune_generator.set_duplex(duplex);
+    }
+
+    let mut print_marks = fortune_generator.print_marks();
+    print_marks.crop_marks |= cli_args.crop_marks;
+    print_marks.registration_marks |= cli_args.registration_marks;
+    if let Some(bleed) = cli_args.bleed {
+        print_marks.bleed = bleed;
+    }
+    fortune_generator.set_print_marks(print_marks);
+
+    if let Some(paper) = cli_args.paper {
+        let mut imposition = fortune_generator.imposition().unwrap_or_default();
+        imposition.paper = PaperSize::Named(paper);
+        fortune_generator.set_imposition(Some(imposition));
+    }
+
+    if let Some(seed) = cli_args.seed {
+        fortune_generator.set_seed(seed);
+    }
+
+    if let Some(manifest_path) = &cli_args.manifest {
+        fortune_generator.set_manifest_path(manifest_path);
+    }
+
+    if let Some(serial_start) = cli_args.serial_start {
+        fortune_generator.set_serial_start(serial_start);
+    }
+
+    for (name, value) in &cli_args.variables {
+        fortune_generator.set_variable(name, value);
+    }
+
+    Ok(fortune_generator)
+}
+
+/// Returns what is generated, e.g. `PDF` or `PNG files`.
+fn output_kind(cli_args: &CliArgs) -> String {
+    let export = cli_args.export_options();
+    if export.is_single_pdf() {
+        return "PDF".to_string();
+    }
+
+    format!("{} files", export.format.extension().to_uppercase())
+}
+
+fn print_success(cli_args: &CliArgs) {
     println!();
     println!(
-        "{} PDF generated at '{}'",
+        "{} {} generated at '{}'",
         "Success!".if_supports_color(Stream::Stdout, |text| text.green()),
-        &cli_args.output.display()
+        output_kind(cli_args),
+        cli_args.output_path().display()
     );
 
     windows::press_a_key_to_continue_windows_only();
+}
+
+fn draw(cli_args: &CliArgs, luck: Option<&str>, json: bool) -> Result<(), Error> {
+    let fortune_generator = open_fortune_generator(cli_args)?;
+
+    let slip = fortune_generator
+        .draw(luck)
+        .map_err(|e| Error::DrawFailure(e.to_string()))?;
+
+    if json {
+        let slip_json = card::format_json(&slip).map_err(|e| Error::DrawFailure(e.to_string()))?;
+        println!("{slip_json}");
+    } else {
+        println!("{}", card::format_card(&slip));
+    }
+
     Ok(())
 }
 
-fn enable_logging() {
-    env::set_var("RUST_LOG", "DEBUG");
-    pretty_env_logger::init();
+fn serve(cli_args: &CliArgs, port: u16) -> Result<(), Error> {
+    let fortune_generator = open_fortune_generator(cli_args)?;
+
+    FortuneServer::new(fortune_generator)
+        .and_then(|mut server| server.run(port))
+        .map_err(|e| Error::ServeFailure(e.to_string()))
+}
+
+fn lint(cli_args: &CliArgs) -> Result<(), Error> {
+    println!("Checking fortunes...");
+    let fortune_generator = FortuneGenerator::open(&cli_args.config)
+        .map_err(|e| Error::FortuneSettingsLoadFailure(e.to_string()))?;
+
+    let issues = fortune_generator.lint().map_err(|e| {
+        println!(
+            "{} Could not check the fortune texts: {e:#}",
+            "Failed!".if_supports_color(Stream::Stdout, |text| text.red())
+        );
+        Error::LintIssuesFound(1)
+    })?;
+
+    println!();
+    for issue in &issues {
+        println!("{issue}");
+    }
+
+    if !issues.is_empty() {
+        println!();
+        println!(
+            "{} Found {} issues in the fortune texts",
+            "Failed!".if_supports_color(Stream::Stdout, |text| text.red()),
+            issues.len()
+        );
+        return Err(Error::LintIssuesFound(issues.len()));
+    }
+
+    println!(
+        "{} No issues found in the fortune texts",
+        "Success!".if_supports_color(Stream::Stdout, |text| text.green()),
+    );
+    Ok(())
+}
+
+/// Warnings of this program, such as fortunes that do not fit the template, are always shown.
+fn enable_logging(verbose: bool) {
+    let mut builder = pretty_env_logger::formatted_builder();
+
+    if verbose {
+        builder.filter_level(LevelFilter::Debug);
+    } else {
+        builder.filter_module(env!("CARGO_CRATE_NAME"), LevelFilter::Warn);
+    }
+
+    builder.init();
 }
@@ -14,6 +14,15 @@ fn main() {
                 println!("PDF generate failure: {}", msg);
                 std::process::exit(3)
             }
+            Error::LintIssuesFound(_) => std::process::exit(4),
+            Error::DrawFailure(msg) => {
+                println!("Draw failure: {}", msg);
+                std::process::exit(5)
+            }
+            Error::ServeFailure(msg) => {
+                println!("Serve failure: {}", msg);
+                std::process::exit(6)
+            }
         }
     }
 }
@@ -1,54 +1,105 @@
 use anyhow::{anyhow, bail, Context, Result};
-use lopdf::{Bookmark, Document, Object, ObjectId};
-use std::{collections::BTreeMap, path::Path};
+use chrono::{DateTime, FixedOffset};
+use lopdf::{Bookmark, Dictionary, Document, Object, ObjectId, StringFormat};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+/// Entries of the document information dictionary, e.g. `("Seed", "42")`.
+pub type DocumentInfo = BTreeMap<String, String>;
+
+/// An input PDF to merge, with how its pages are placed and the bookmarks to its first page.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PdfInput {
+    pub path: PathBuf,
+    pub transform: PageTransform,
+    pub bookmarks: Vec<PdfBookmark>,
+}
+
+/// A bookmark in the merged PDF, under the bookmark of its group.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PdfBookmark {
+    /// The title of the bookmark to group it under, e.g. `Great Luck`.
+    pub group: String,
+    /// The title of the bookmark itself, e.g. `Great Luck – sheet 3 (front)`.
+    pub title: String,
+}
+
+/// How the pages of an input PDF are placed in the merged PDF.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum PageTransform {
+    #[default]
+    None,
+    /// Turns the pages upside down, e.g. for the backs of short-edge duplex printing.
+    Rotate180,
+}
 
+impl PageTransform {
+    /// Returns the clockwise rotation in degrees, for the `Rotate` entry of a page.
+    fn rotation(self) -> i64 {
+        match self {
+            PageTransform::None => 0,
+            PageTransform::Rotate180 => 180,
+        }
+    }
+}
+
+/// Merges the input PDFs in order, transforming the pages of every input PDF.
+///
+/// The bookmarks of the inputs are grouped by their `group`, in the order the groups first
+/// appear.
+///
 /// This code was copy-pasted from the lopdf crate.
 /// Sadly, there's no high-level crate for PDF manipulation yet.
-pub fn merge_pdf(input_paths: &[impl AsRef<Path>], output_path: impl AsRef<Path>) -> Result<()> {
-    for input_path in input_paths {
-        if !input_path.as_ref().is_file() {
+pub fn merge_pdf(
+    inputs: &[PdfInput],
+    output_path: impl AsRef<Path>,
+    document_info: &DocumentInfo,
+) -> Result<()> {
+    for input in inputs {
+        if !input.path.is_file() {
             bail!("The path to the input PDF file is not a file");
         }
     }
 
     // Load all PDF files
-    let documents = input_paths
+    let documents = inputs
         .iter()
-        .map(Document::load)
-        .map(|x| x.map_err(|e| anyhow!(e)))
+        .map(|input| Ok((Document::load(&input.path)?, input)))
+        .map(|x| x.map_err(|e: lopdf::Error| anyhow!(e)))
         .collect::<Result<Vec<_>>>()?;
 
     // Define a starting max_id (will be used as start index for object_ids)
     let mut max_id = 1;
-    let mut pagenum = 1;
     // Collect all Documents Objects grouped by a map
     let mut documents_pages = BTreeMap::new();
     let mut documents_objects = BTreeMap::new();
     let mut document = Document::with_version("1.5");
+    let mut group_bookmarks: HashMap<&str, u32> = HashMap::new();
 
-    for mut doc in documents {
-        let mut first = false;
+    for (mut doc, input) in documents {
         doc.renumber_objects_with(max_id);
 
         max_id = doc.max_id + 1;
 
+        if let Some(&first_page) = doc.get_pages().values().next() {
+            for bookmark in &input.bookmarks {
+                let group_id = *group_bookmarks.entry(&bookmark.group).or_insert_with(|| {
+                    document.add_bookmark(new_bookmark(&bookmark.group, first_page), None)
+                });
+                document.add_bookmark(new_bookmark(&bookmark.title, first_page), Some(group_id));
+            }
+        }
+
+        let transform = input.transform;
         documents_pages.extend(
             doc.get_pages()
                 .into_values()
                 .map(|object_id| {
-                    if !first {
-                        let bookmark = Bookmark::new(
-                            format!("Page_{}", pagenum),
-                            [0.0, 0.0, 1.0],
-                            0,
-                            object_id,
-                        );
-                        document.add_bookmark(bookmark, None);
-                        first = true;
-                        pagenum += 1;
-                    }
-
-                    (object_id, doc.get_object(object_id).unwrap().to_owned())
+                    let mut page = doc.get_object(object_id).unwrap().to_owned();
+                    transform_page(&mut page, transform);
+                    (object_id, page)
                 })
                 .collect::<BTreeMap<ObjectId, Object>>(),
         );
@@ -174,12 +225,17 @@ pub fn merge_pdf(input_paths: &[impl AsRef<Path>], output_path: impl AsRef<Path>
     document.adjust_zero_pages();
 
     // Set all bookmarks to the PDF Object tree then set the Outlines to the Bookmark content map.
+    let first_outline_id = document.max_id + 1;
     if let Some(n) = document.build_outline() {
         if let Ok(Object::Dictionary(ref mut dict)) = document.get_object_mut(catalog_object.0) {
             dict.set("Outlines", Object::Reference(n));
         }
+        encode_outline_titles(&mut document, first_outline_id);
     }
 
+    set_document_info(&mut document, document_info);
+    make_deterministic(&mut document);
+
     document.compress();
 
     // Save the merged PDF
@@ -190,13 +246,208 @@ pub fn merge_pdf(input_paths: &[impl AsRef<Path>], output_path: impl AsRef<Path>
     Ok(())
 }
 
+fn new_bookmark(title: &str, page: ObjectId) -> Bookmark {
+    Bookmark::new(title.to_string(), [0.0, 0.0, 0.0], 0, page)
+}
+
+/// Returns a text string for the PDF, e.g. for the title of a bookmark.
+///
+/// Text that is not ASCII is encoded as UTF-16 with a byte order mark, so PDF viewers show it
+/// correctly, e.g. `大吉` or the dash in `Great Luck – sheet 3 (front)`.
+fn text_string(text: &str) -> Object {
+    if text.is_ascii() {
+        return Object::string_literal(text);
+    }
+
+    let bytes = [0xFE, 0xFF]
+        .into_iter()
+        .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+        .collect();
+    Object::String(bytes, StringFormat::Hexadecimal)
+}
+
+/// Re-encodes the titles of the bookmarks, which lopdf writes as UTF-8.
+///
+/// The outline is built after all other objects, so its objects have an id of at least
+/// `first_outline_id`. The titles of the objects of the input PDFs are left as they are.
+fn encode_outline_titles(document: &mut Document, first_outline_id: u32) {
+    let outline_objects = document
+        .objects
+        .range_mut((first_outline_id, 0)..)
+        .map(|(_, object)| object);
+    for object in outline_objects {
+        if let Ok(dictionary) = object.as_dict_mut() {
+            let title = match dictionary.get(b"Title") {
+                Ok(Object::String(bytes, _)) => String::from_utf8(bytes.clone()).ok(),
+                _ => None,
+            };
+            if let Some(title) = title {
+                dictionary.set("Title", text_string(&title));
+            }
+        }
+    }
+}
+
+/// Returns the date in the format of dates in a PDF, e.g. `D:20250405143000+02'00'`.
+pub fn pdf_date(date: DateTime<FixedOffset>) -> String {
+    let offset = date.format("%:z").to_string().replace(':', "'");
+    format!("D:{}{offset}'", date.format("%Y%m%d%H%M%S"))
+}
+
+/// Adds the rotation of the transform to the rotation the page already has.
+fn transform_page(page: &mut Object, transform: PageTransform) {
+    if transform == PageTransform::None {
+        return;
+    }
+
+    if let Ok(dictionary) = page.as_dict_mut() {
+        let rotation = dictionary
+            .get(b"Rotate")
+            .and_then(Object::as_i64)
+            .unwrap_or(0);
+        dictionary.set("Rotate", (rotation + transform.rotation()).rem_euclid(360));
+    }
+}
+
+fn set_document_info(document: &mut Document, document_info: &DocumentInfo) {
+    if document_info.is_empty() {
+        return;
+    }
+
+    let mut dictionary = Dictionary::new();
+    for (key, value) in document_info {
+        dictionary.set(key.as_bytes(), text_string(value));
+    }
+
+    let info_id = document.add_object(dictionary);
+    document.trailer.set("Info", info_id);
+}
+
+/// Renumbers the objects in the order they are reached from the trailer and sorts all dictionaries.
+///
+/// `svg2pdf` writes fonts and resources in hash map order, so without this step the same
+/// input pages would not result in a byte-for-byte identical PDF.
+fn make_deterministic(document: &mut Document) {
+    for object in document.objects.values_mut() {
+        sort_dictionaries(object);
+    }
+    let mut trailer = Object::Dictionary(std::mem::take(&mut document.trailer));
+    sort_dictionaries(&mut trailer);
+
+    let mut new_ids = BTreeMap::new();
+    assign_new_ids(&trailer, &document.objects, &mut new_ids);
+
+    let mut old_objects = std::mem::take(&mut document.objects);
+    for (old_id, new_id) in &new_ids {
+        if let Some(mut object) = old_objects.remove(old_id) {
+            replace_references(&mut object, &new_ids);
+            document.objects.insert(*new_id, object);
+        }
+    }
+
+    replace_references(&mut trailer, &new_ids);
+    if let Object::Dictionary(trailer) = trailer {
+        document.trailer = trailer;
+    }
+    document.max_id = new_ids.len() as u32;
+}
+
+fn sort_dictionaries(object: &mut Object) {
+    match object {
+        Object::Array(array) => array.iter_mut().for_each(sort_dictionaries),
+        Object::Dictionary(dictionary) => sort_dictionary(dictionary),
+        Object::Stream(stream) => sort_dictionary(&mut stream.dict),
+        _ => {}
+    }
+}
+
+fn sort_dictionary(dictionary: &mut Dictionary) {
+    let mut entries = std::mem::take(dictionary.as_hashmap_mut())
+        .into_iter()
+        .collect::<Vec<_>>();
+    entries.sort_by(|(left, _), (right, _)| left.cmp(right));
+    entries
+        .iter_mut()
+        .for_each(|(_, value)| sort_dictionaries(value));
+    *dictionary = entries.into_iter().collect();
+}
+
+fn assign_new_ids(
+    object: &Object,
+    objects: &BTreeMap<ObjectId, Object>,
+    new_ids: &mut BTreeMap<ObjectId, ObjectId>,
+) {
+    match object {
+        Object::Reference(id) if !new_ids.contains_key(id) => {
+            if let Some(referenced) = objects.get(id) {
+                new_ids.insert(*id, (new_ids.len() as u32 + 1, 0));
+                assign_new_ids(referenced, objects, new_ids);
+            }
+        }
+        Object::Array(array) => array
+            .iter()
+            .for_each(|x| assign_new_ids(x, objects, new_ids)),
+        Object::Dictionary(dictionary) => dictionary
+            .iter()
+            .for_each(|(_, x)| assign_new_ids(x, objects, new_ids)),
+        Object::Stream(stream) => stream
+            .dict
+            .iter()
+            .for_each(|(_, x)| assign_new_ids(x, objects, new_ids)),
+        _ => {}
+    }
+}
+
+fn replace_references(object: &mut Object, new_ids: &BTreeMap<ObjectId, ObjectId>) {
+    match object {
+        Object::Reference(id) => {
+            if let Some(new_id) = new_ids.get(id) {
+                *id = *new_id;
+            }
+        }
+        Object::Array(array) => array
+            .iter_mut()
+            .for_each(|x| replace_references(x, new_ids)),
+        Object::Dictionary(dictionary) => dictionary
+            .iter_mut()
+            .for_each(|(_, x)| replace_references(x, new_ids)),
+        Object::Stream(stream) => stream
+            .dict
+            .iter_mut()
+            .for_each(|(_, x)| replace_references(x, new_ids)),
+        _ => {}
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use lopdf::Document;
-    use std::{fs::File, io::Write, path::PathBuf};
+    use chrono::{FixedOffset, TimeZone};
+    use lopdf::{dictionary, Document, Object};
+    use maplit::btreemap;
+    use pretty_assertions::assert_eq;
+    use std::{
+        fs::File,
+        io::Write,
+        path::{Path, PathBuf},
+    };
     use tempfile::tempdir;
 
-    use crate::pdf::merge_pdf;
+    use crate::pdf::{merge_pdf, pdf_date, DocumentInfo, PageTransform, PdfBookmark, PdfInput};
+
+    fn pdf_input(path: &Path, transform: PageTransform) -> PdfInput {
+        PdfInput {
+            path: path.to_path_buf(),
+            transform,
+            bookmarks: vec![],
+        }
+    }
+
+    fn bookmark(group: &str, title: &str) -> PdfBookmark {
+        PdfBookmark {
+            group: group.to_string(),
+            title: title.to_string(),
+        }
+    }
 
     #[test]
     fn test_merge_pdf() {
@@ -205,13 +456,193 @@ mod tests {
         let input_path2 = PathBuf::from("test_utils/data/pdf_samples/dummy2.pdf");
         let output_path = tempdir.path().join("output.pdf");
 
-        merge_pdf(&[&input_path1, &input_path2], &output_path).unwrap();
+        merge_pdf(
+            &[
+                pdf_input(&input_path1, PageTransform::None),
+                pdf_input(&input_path2, PageTransform::None),
+            ],
+            &output_path,
+            &DocumentInfo::new(),
+        )
+        .unwrap();
 
         let pdf = Document::load(&output_path).unwrap();
         let pages = pdf.get_pages();
         assert_eq!(pages.len(), 2);
     }
 
+    #[test]
+    fn test_merge_pdf_with_rotated_page() {
+        let tempdir = tempdir().unwrap();
+        let input_path = PathBuf::from("test_utils/data/pdf_samples/dummy1.pdf");
+        let output_path = tempdir.path().join("output.pdf");
+
+        merge_pdf(
+            &[
+                pdf_input(&input_path, PageTransform::None),
+                pdf_input(&input_path, PageTransform::Rotate180),
+            ],
+            &output_path,
+            &DocumentInfo::new(),
+        )
+        .unwrap();
+
+        let pdf = Document::load(&output_path).unwrap();
+        let rotations = pdf
+            .get_pages()
+            .into_values()
+            .map(|page_id| {
+                let page = pdf.get_dictionary(page_id).unwrap();
+                page.get(b"Rotate").and_then(|x| x.as_i64()).unwrap_or(0)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(rotations, vec![0, 180]);
+    }
+
+    #[test]
+    fn test_merge_pdf_with_document_info() {
+        let tempdir = tempdir().unwrap();
+        let input_path = PathBuf::from("test_utils/data/pdf_samples/dummy1.pdf");
+        let output_path = tempdir.path().join("output.pdf");
+        let document_info = btreemap! { "Seed".to_string() => "42".to_string() };
+
+        merge_pdf(
+            &[pdf_input(&input_path, PageTransform::None)],
+            &output_path,
+            &document_info,
+        )
+        .unwrap();
+
+        let pdf = Document::load(&output_path).unwrap();
+        let info_id = pdf.trailer.get(b"Info").unwrap().as_reference().unwrap();
+        let info = pdf.get_dictionary(info_id).unwrap();
+        assert_eq!(info.get(b"Seed").unwrap().as_str().unwrap(), b"42");
+    }
+
+    #[test]
+    fn test_merge_pdf_with_bookmarks() {
+        let tempdir = tempdir().unwrap();
+        let input_path = PathBuf::from("test_utils/data/pdf_samples/dummy1.pdf");
+        let output_path = tempdir.path().join("output.pdf");
+        let input = |bookmarks| PdfInput {
+            bookmarks,
+            ..pdf_input(&input_path, PageTransform::None)
+        };
+
+        merge_pdf(
+            &[
+                input(vec![bookmark("Great Luck", "Great Luck – sheet 1")]),
+                input(vec![
+                    bookmark("Great Luck", "Great Luck – sheet 2"),
+                    bookmark("大吉", "大吉 – sheet 2"),
+                ]),
+                input(vec![]),
+            ],
+            &output_path,
+            &DocumentInfo::new(),
+        )
+        .unwrap();
+
+        let pdf = Document::load(&output_path).unwrap();
+        let mut toc = pdf
+            .get_toc()
+            .unwrap()
+            .toc
+            .into_iter()
+            .map(|entry| (entry.page, entry.level, entry.title))
+            .collect::<Vec<_>>();
+        toc.sort();
+        let entry = |page, level, title: &str| (page, level, title.to_string());
+        assert_eq!(
+            toc,
+            vec![
+                entry(1, 1, "Great Luck"),
+                entry(1, 2, "Great Luck – sheet 1"),
+                entry(2, 1, "大吉"),
+                entry(2, 2, "Great Luck – sheet 2"),
+                entry(2, 2, "大吉 – sheet 2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_pdf_with_bookmarks_keeps_other_titles() {
+        let tempdir = tempdir().unwrap();
+        let input_path = tempdir.path().join("input.pdf");
+        let output_path = tempdir.path().join("output.pdf");
+        // A dictionary of the input with a title in UTF-8, which is not a bookmark.
+        let mut input = Document::load("test_utils/data/pdf_samples/dummy1.pdf").unwrap();
+        let annotation_id = input.add_object(dictionary! {
+            "Type" => "Annot",
+            "Title" => Object::string_literal("大吉".as_bytes()),
+        });
+        let page_id = *input.get_pages().values().next().unwrap();
+        input
+            .get_dictionary_mut(page_id)
+            .unwrap()
+            .set("Annots", vec![Object::Reference(annotation_id)]);
+        input.save(&input_path).unwrap();
+
+        merge_pdf(
+            &[PdfInput {
+                bookmarks: vec![bookmark("大吉", "大吉 – sheet 1")],
+                ..pdf_input(&input_path, PageTransform::None)
+            }],
+            &output_path,
+            &DocumentInfo::new(),
+        )
+        .unwrap();
+
+        let pdf = Document::load(&output_path).unwrap();
+        let page_id = *pdf.get_pages().values().next().unwrap();
+        let annotation_id = pdf
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"Annots")
+            .unwrap()
+            .as_array()
+            .unwrap()[0]
+            .as_reference()
+            .unwrap();
+        let title = pdf
+            .get_dictionary(annotation_id)
+            .unwrap()
+            .get(b"Title")
+            .unwrap();
+        assert_eq!(title.as_str().unwrap(), "大吉".as_bytes());
+        let toc = pdf.get_toc().unwrap().toc;
+        assert_eq!(toc[0].title, "大吉");
+    }
+
+    #[test]
+    fn test_merge_pdf_with_text_in_document_info() {
+        let tempdir = tempdir().unwrap();
+        let input_path = PathBuf::from("test_utils/data/pdf_samples/dummy1.pdf");
+        let output_path = tempdir.path().join("output.pdf");
+        let document_info = btreemap! { "Title".to_string() => "大吉".to_string() };
+
+        merge_pdf(
+            &[pdf_input(&input_path, PageTransform::None)],
+            &output_path,
+            &document_info,
+        )
+        .unwrap();
+
+        let pdf = Document::load(&output_path).unwrap();
+        let info_id = pdf.trailer.get(b"Info").unwrap().as_reference().unwrap();
+        let info = pdf.get_dictionary(info_id).unwrap();
+        let title = info.get(b"Title").unwrap().as_str().unwrap();
+        assert_eq!(title, [0xFE, 0xFF, 0x59, 0x27, 0x54, 0x09]);
+    }
+
+    #[test]
+    fn test_pdf_date() {
+        let offset = FixedOffset::east_opt(2 * 3600).unwrap();
+        let date = offset.with_ymd_and_hms(2025, 4, 5, 14, 30, 0).unwrap();
+
+        assert_eq!(pdf_date(date), "D:20250405143000+02'00'");
+    }
+
     #[test]
     fn test_merge_pdf_invalid_input_path() {
         // Create a temporary directory
@@ -226,8 +657,12 @@ mod tests {
 
         // Merge the input files
         let result = merge_pdf(
-            &[&input_path, &input_path],
+            &[
+                pdf_input(&input_path, PageTransform::None),
+                pdf_input(&input_path, PageTransform::None),
+            ],
             tempdir.path().join("output.pdf"),
+            &DocumentInfo::new(),
         );
 
         // Verify that the merge operation failed with an error message
@@ -0,0 +1,39 @@
+//! Backends that convert a single SVG page to a PDF file.
+//!
+//! A [`FortuneGenerator`](crate::FortuneGenerator) can be given any [`Renderer`]:
+//! ```
+//! # use anyhow::Ok;
+//! use fortune_generator::{renderer::MockRenderer, FortuneGenerator};
+//!
+//! # let temp_dir = tempfile::tempdir()?;
+//! let mut fortune_gen = FortuneGenerator::open("test_utils/data/fortune_settings.yaml")?;
+//! fortune_gen.set_renderer(Box::new(MockRenderer));
+//! fortune_gen.generate_to_pdf(temp_dir.path().join("mock_slips.pdf"))?; // Every page is blank
+//! # Ok(())
+//! ```
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::fortune::fortune_data::fortune_settings::RenderBackend;
+
+pub use self::{inkscape::InkscapeRenderer, mock::MockRenderer, native::NativeRenderer};
+
+mod inkscape;
+mod mock;
+mod native;
+
+/// Converts an SVG file to a PDF file.
+///
+/// Pages are rendered in parallel, hence the `Send + Sync` bound.
+pub trait Renderer: Send + Sync {
+    fn render(&self, svg_path: &Path, pdf_path: &Path) -> Result<()>;
+}
+
+/// Returns the renderer that belongs to the backend from the settings or CLI.
+pub fn for_backend(render_backend: RenderBackend) -> Box<dyn Renderer> {
+    match render_backend {
+        RenderBackend::Native => Box::new(NativeRenderer),
+        RenderBackend::Inkscape => Box::new(InkscapeRenderer),
+    }
+}
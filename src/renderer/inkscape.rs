@@ -7,7 +7,19 @@ use std::{
 use anyhow::{anyhow, Ok, Result};
 use log::debug;
 
-pub fn svg_to_pdf(path_to_svg: impl AsRef<Path>, output_path: impl AsRef<Path>) -> Result<()> {
+use super::Renderer;
+
+/// Renders by calling the `inkscape` binary, which needs to be added to the PATH.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InkscapeRenderer;
+
+impl Renderer for InkscapeRenderer {
+    fn render(&self, svg_path: &Path, pdf_path: &Path) -> Result<()> {
+        svg_to_pdf(svg_path, pdf_path)
+    }
+}
+
+fn svg_to_pdf(path_to_svg: impl AsRef<Path>, output_path: impl AsRef<Path>) -> Result<()> {
     let output_path_str = output_path
         .as_ref()
         .to_str()
@@ -75,6 +87,7 @@ mod tests {
                                </svg>";
 
     #[test]
+    #[ignore = "requires Inkscape"]
     fn convert_svg_expect_pdf() {
         let temp_file = test_utils::create_temp_file("temp.svg", SVG_EXAMPLE);
         let temp_pdf_path = temp_file.dir.path().join("temp.pdf");
@@ -85,6 +98,7 @@ mod tests {
     }
 
     #[test]
+    #[ignore = "requires Inkscape"]
     fn test_execute_inkscape_command() {
         let temp_file = test_utils::create_temp_file("temp.svg", SVG_EXAMPLE);
         let temp_pdf_path = temp_file.dir.path().join("temp.pdf");
@@ -99,6 +113,7 @@ mod tests {
     }
 
     #[test]
+    #[ignore = "requires Inkscape"]
     fn simple_inkscape_test() {
         let result = Command::new("inkscape")
             .arg("--help")
@@ -0,0 +1,74 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use lopdf::{dictionary, Document, Object, Stream};
+
+use super::Renderer;
+
+/// Width and height of an A4 page in PDF points.
+const A4_MEDIA_BOX: [i64; 4] = [0, 0, 595, 842];
+
+/// Writes a blank A4 page without looking at the SVG.
+///
+/// Useful to run the whole pipeline in tests, or on machines without any renderer available.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MockRenderer;
+
+impl Renderer for MockRenderer {
+    fn render(&self, _svg_path: &Path, pdf_path: &Path) -> Result<()> {
+        let mut document = blank_document();
+        document
+            .save(pdf_path)
+            .with_context(|| format!("Cannot save to {:?}", pdf_path))?;
+
+        Ok(())
+    }
+}
+
+fn blank_document() -> Document {
+    let mut document = Document::with_version("1.5");
+    let pages_id = document.new_object_id();
+
+    let content_id = document.add_object(Stream::new(dictionary! {}, vec![]));
+    let page_id = document.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_id,
+        "MediaBox" => A4_MEDIA_BOX.iter().map(|&x| x.into()).collect::<Vec<Object>>(),
+    });
+
+    let pages = dictionary! {
+        "Type" => "Pages",
+        "Kids" => vec![page_id.into()],
+        "Count" => 1,
+    };
+    document.objects.insert(pages_id, Object::Dictionary(pages));
+
+    let catalog_id = document.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    document.trailer.set("Root", catalog_id);
+
+    document
+}
+
+#[cfg(test)]
+mod tests {
+    use lopdf::Document;
+
+    use super::{MockRenderer, Renderer};
+
+    #[test]
+    fn test_render_expect_single_blank_page() {
+        let temp_file = test_utils::create_temp_file("temp.svg", "not even an svg");
+        let temp_pdf_path = temp_file.dir.path().join("temp.pdf");
+
+        MockRenderer
+            .render(&temp_file.path, &temp_pdf_path)
+            .unwrap();
+
+        let pdf = Document::load(&temp_pdf_path).unwrap();
+        assert_eq!(pdf.get_pages().len(), 1);
+    }
+}
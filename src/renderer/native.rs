@@ -0,0 +1,73 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use svg2pdf::{usvg::Tree, ConversionOptions, PageOptions};
+
+use crate::svg::font_metrics::usvg_options;
+
+use super::Renderer;
+
+/// Renders in-process with `svg2pdf`, embedding the fonts in the PDF.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NativeRenderer;
+
+impl Renderer for NativeRenderer {
+    fn render(&self, svg_path: &Path, pdf_path: &Path) -> Result<()> {
+        svg_to_pdf(svg_path, pdf_path)
+    }
+}
+
+fn svg_to_pdf(path_to_svg: impl AsRef<Path>, output_path: impl AsRef<Path>) -> Result<()> {
+    let path_to_svg = path_to_svg.as_ref();
+    let output_path = output_path.as_ref();
+
+    debug!(
+        "Converting {} to {}",
+        path_to_svg.display(),
+        output_path.display()
+    );
+
+    let svg_data = fs::read(path_to_svg)
+        .with_context(|| format!("Could not read SVG file at path {:?}", path_to_svg))?;
+    let tree = Tree::from_data(&svg_data, &usvg_options(path_to_svg))
+        .with_context(|| format!("Could not parse SVG file at path {:?}", path_to_svg))?;
+
+    let pdf = svg2pdf::to_pdf(&tree, ConversionOptions::default(), PageOptions::default())
+        .map_err(|e| anyhow!("Failed to convert {:?} to PDF: {}", path_to_svg, e))?;
+
+    fs::write(output_path, pdf).with_context(|| format!("Cannot save to {:?}", output_path))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use lopdf::Document;
+
+    use super::svg_to_pdf;
+    const SVG_EXAMPLE: &str = "<svg height='100' width='100' xmlns='http://www.w3.org/2000/svg'>
+                                 <circle cx='50' cy='50' r='40' />
+                               </svg>";
+
+    #[test]
+    fn convert_svg_expect_pdf() {
+        let temp_file = test_utils::create_temp_file("temp.svg", SVG_EXAMPLE);
+        let temp_pdf_path = temp_file.dir.path().join("temp.pdf");
+
+        assert!(!temp_pdf_path.exists());
+        svg_to_pdf(&temp_file.path, &temp_pdf_path).unwrap();
+
+        let pdf = Document::load(&temp_pdf_path).unwrap();
+        assert_eq!(pdf.get_pages().len(), 1);
+    }
+
+    #[test]
+    fn convert_invalid_svg_expect_error() {
+        let temp_file = test_utils::create_temp_file("temp.svg", "not an svg");
+        let temp_pdf_path = temp_file.dir.path().join("temp.pdf");
+
+        assert!(svg_to_pdf(&temp_file.path, &temp_pdf_path).is_err());
+        assert!(!temp_pdf_path.exists());
+    }
+}
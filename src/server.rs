@@ -0,0 +1,247 @@
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::{
+    cli::card,
+    fortune::{fortune_slip_writer::FortuneSlipWriter, FortuneGenerator},
+};
+
+const INDEX_HTML: &str = include_str!("server/index.html");
+
+/// A response of the server: status code, content type and body.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Reply {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Reply {
+    fn ok(content_type: &'static str, body: String) -> Self {
+        Reply {
+            status: 200,
+            content_type,
+            body,
+        }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Reply {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: message.into(),
+        }
+    }
+}
+
+/// Serves random slips on localhost, as JSON, as SVG and on a page to shake the box with.
+///
+/// Everything is served by this program, so the page works without internet.
+pub struct FortuneServer {
+    fortune_generator: FortuneGenerator,
+    slip_writer: FortuneSlipWriter,
+    /// Seeds every drawn slip, so a server started with the same seed draws the same slips.
+    rng: StdRng,
+}
+
+impl FortuneServer {
+    pub fn new(fortune_generator: FortuneGenerator) -> Result<Self> {
+        let slip_writer = fortune_generator.open_single_slip_template_writer()?;
+        let rng = StdRng::seed_from_u64(fortune_generator.seed());
+
+        Ok(FortuneServer {
+            fortune_generator,
+            slip_writer,
+            rng,
+        })
+    }
+
+    /// Serves on `127.0.0.1` at the port, one request at a time, until the program is stopped.
+    pub fn run(&mut self, port: u16) -> Result<()> {
+        let server = Server::http(("127.0.0.1", port))
+            .map_err(|e| anyhow!("Could not listen on port {port}: {e}"))?;
+        println!("Serving fortunes at http://localhost:{port}/ (press Ctrl+C to stop)");
+
+        for request in server.incoming_requests() {
+            let reply = self.respond(request.method(), request.url());
+            info!("{} {} {}", request.method(), request.url(), reply.status);
+
+            let content_type = Header::from_bytes("Content-Type", reply.content_type)
+                .map_err(|()| anyhow!("Invalid content type '{}'", reply.content_type))?;
+            let response = Response::from_string(reply.body)
+                .with_status_code(reply.status)
+                .with_header(content_type);
+
+            if let Err(e) = request.respond(response) {
+                warn!("Could not send the response: {e}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the reply to the request for the URL, e.g. `/api/slip?luck=great_luck`.
+    pub fn respond(&mut self, method: &Method, url: &str) -> Reply {
+        if *method != Method::Get {
+            return Reply::error(405, "Only GET is allowed");
+        }
+
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let luck = query_param(query, "luck");
+
+        let reply = match path {
+            "/" | "/index.html" => Ok(Reply::ok("text/html; charset=utf-8", INDEX_HTML.into())),
+            "/api/slip" => self.slip_json(luck.as_deref()),
+            "/api/slip.svg" => self.slip_svg(luck.as_deref()),
+            _ => return Reply::error(404, format!("Not found: {path}")),
+        };
+
+        reply.unwrap_or_else(|e| Reply::error(400, e.to_string()))
+    }
+
+    fn slip_json(&mut self, luck: Option<&str>) -> Result<Reply> {
+        let slip = self
+            .fortune_generator
+            .draw_with_seed(luck, self.rng.gen())?;
+        Ok(Reply::ok("application/json", card::format_json(&slip)?))
+    }
+
+    fn slip_svg(&mut self, luck: Option<&str>) -> Result<Reply> {
+        let slip = self
+            .fortune_generator
+            .draw_with_seed(luck, self.rng.gen())?;
+        self.slip_writer.write_page(&[slip])?;
+        Ok(Reply::ok(
+            "image/svg+xml",
+            self.slip_writer.to_svg_string()?,
+        ))
+    }
+}
+
+/// Returns the decoded value of the parameter in the query, e.g. `luck=Great%20Luck`.
+fn query_param(query: &str, name: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| percent_decode(value))
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        let hex = bytes
+            .get(idx + 1..idx + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[idx], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                idx += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                idx += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                idx += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use tiny_http::Method;
+
+    use crate::FortuneGenerator;
+
+    use super::{percent_decode, query_param, FortuneServer};
+
+    fn server() -> Result<FortuneServer> {
+        let mut fortune_generator =
+            FortuneGenerator::open("test_utils/data/fortune_settings_imposition.yaml")?;
+        fortune_generator.set_seed(7);
+        FortuneServer::new(fortune_generator)
+    }
+
+    #[rstest]
+    #[case("Great%20Luck", "Great Luck")]
+    #[case("good+luck", "good luck")]
+    #[case("%E4%B8%AD%E5%90%89", "中吉")]
+    #[case("100%", "100%")]
+    fn test_percent_decode(#[case] value: &str, #[case] expected: &str) {
+        assert_eq!(percent_decode(value), expected);
+    }
+
+    #[test]
+    fn test_query_param() {
+        assert_eq!(
+            query_param("a=1&luck=bad_luck", "luck").as_deref(),
+            Some("bad_luck")
+        );
+        assert_eq!(query_param("a=1", "luck"), None);
+    }
+
+    #[test]
+    fn test_respond() -> Result<()> {
+        let mut server = server()?;
+
+        let page = server.respond(&Method::Get, "/");
+        assert_eq!(page.status, 200);
+        assert!(page.body.contains("/api/slip.svg"));
+
+        let slip = server.respond(&Method::Get, "/api/slip?luck=Bad%20Luck");
+        assert_eq!(slip.content_type, "application/json");
+        let json: serde_json::Value = serde_json::from_str(&slip.body)?;
+        assert_eq!(json["header"], "凶");
+
+        let svg = server.respond(&Method::Get, "/api/slip.svg?luck=good_luck");
+        assert_eq!(svg.content_type, "image/svg+xml");
+        assert!(svg.body.contains("<svg"));
+        assert!(svg.body.contains("中吉"));
+
+        assert_eq!(server.respond(&Method::Get, "/api/slip?luck=x").status, 400);
+        assert_eq!(server.respond(&Method::Get, "/missing").status, 404);
+        assert_eq!(server.respond(&Method::Post, "/api/slip").status, 405);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_server_needs_single_slip_template() -> Result<()> {
+        let fortune_generator = FortuneGenerator::open("test_utils/data/fortune_settings.yaml")?;
+
+        let error = FortuneServer::new(fortune_generator).err().unwrap();
+
+        assert!(error.to_string().contains("no template_single_slip"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_same_seed_draws_same_slips() -> Result<()> {
+        let (mut first, mut second) = (server()?, server()?);
+
+        for _ in 0..3 {
+            assert_eq!(
+                first.respond(&Method::Get, "/api/slip"),
+                second.respond(&Method::Get, "/api/slip")
+            );
+        }
+
+        Ok(())
+    }
+}
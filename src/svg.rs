@@ -1,3 +1,5 @@
+pub mod font_metrics;
+pub mod svg_bounds;
 pub mod svg_editor;
 pub mod svg_file;
-mod svg_to_pdf;
+pub mod svg_to_png;
@@ -0,0 +1,206 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::{anyhow, Result};
+use log::debug;
+use svg2pdf::usvg::{
+    fontdb::{Database, Family, Query, Style, Weight},
+    Options,
+};
+use ttf_parser::{Face, GlyphId};
+
+const DEFAULT_FONT_SIZE: f32 = 16.0;
+/// Line height relative to the font size, when the element has no `data-line-height`.
+const DEFAULT_LINE_HEIGHT: f32 = 1.2;
+
+/// Loading the system fonts is slow, so the font database is shared by the whole program.
+static FONT_DB: OnceLock<Arc<Database>> = OnceLock::new();
+
+/// Returns the database with all fonts installed on the system.
+pub fn font_db() -> Arc<Database> {
+    FONT_DB
+        .get_or_init(|| {
+            let mut font_db = Database::new();
+            font_db.load_system_fonts();
+            debug!("Loaded {} font faces", font_db.len());
+            Arc::new(font_db)
+        })
+        .clone()
+}
+
+/// Returns the options to parse the SVG file with, using the system fonts and resolving images
+/// relative to the file.
+pub fn usvg_options(path_to_svg: &Path) -> Options<'static> {
+    Options {
+        resources_dir: path_to_svg.parent().map(Path::to_path_buf),
+        fontdb: font_db(),
+        ..Default::default()
+    }
+}
+
+/// The font attributes of an SVG text element that affect the width of its text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextStyle {
+    pub font_family: String,
+    pub font_size: f32,
+    pub font_weight: u16,
+    pub italic: bool,
+    pub letter_spacing: f32,
+    /// Distance between the baselines of two lines, from `data-line-height`.
+    pub line_height: f32,
+}
+
+impl TextStyle {
+    /// Reads the style from the attributes of a text element, using the SVG defaults when absent.
+    pub fn from_attr(attr: &HashMap<String, String>) -> Self {
+        let font_size = attr
+            .get("font-size")
+            .and_then(|x| parse_length(x, DEFAULT_FONT_SIZE))
+            .unwrap_or(DEFAULT_FONT_SIZE);
+
+        let font_weight = match attr.get("font-weight").map(String::as_str) {
+            Some("bold") => Weight::BOLD.0,
+            Some(weight) => weight.parse().unwrap_or(Weight::NORMAL.0),
+            None => Weight::NORMAL.0,
+        };
+
+        TextStyle {
+            font_family: attr
+                .get("font-family")
+                .map(|x| x.trim_matches(|c| c == '\'' || c == '"').to_string())
+                .unwrap_or_default(),
+            font_size,
+            font_weight,
+            italic: attr.get("font-style").is_some_and(|x| x == "italic"),
+            letter_spacing: attr
+                .get("letter-spacing")
+                .and_then(|x| parse_length(x, font_size))
+                .unwrap_or(0.0),
+            line_height: attr
+                .get("data-line-height")
+                .and_then(|x| parse_length(x, font_size))
+                .unwrap_or(font_size * DEFAULT_LINE_HEIGHT),
+        }
+    }
+
+    /// Runs `f` with a function that returns the width of a single line of text in this style.
+    ///
+    /// The font is only looked up once, so `f` can measure as many texts as it needs.
+    /// Falls back to the default sans-serif font when the font family is not installed.
+    pub fn with_measure<T>(&self, f: impl FnOnce(&dyn Fn(&str) -> f32) -> T) -> Result<T> {
+        let font_db = font_db();
+        let query = Query {
+            families: &[Family::Name(&self.font_family), Family::SansSerif],
+            weight: Weight(self.font_weight),
+            style: if self.italic {
+                Style::Italic
+            } else {
+                Style::Normal
+            },
+            ..Default::default()
+        };
+
+        let font_id = font_db
+            .query(&query)
+            .ok_or(anyhow!("No font found for family '{}'", self.font_family))?;
+
+        font_db
+            .with_face_data(font_id, |data, face_index| {
+                let face = Face::parse(data, face_index).ok()?;
+                let measure = |text: &str| self.measure_with_face(&face, text);
+                Some(f(&measure))
+            })
+            .flatten()
+            .ok_or(anyhow!(
+                "Could not load font for family '{}'",
+                self.font_family
+            ))
+    }
+
+    fn measure_with_face(&self, face: &Face, text: &str) -> f32 {
+        let scale = self.font_size / face.units_per_em() as f32;
+
+        text.chars()
+            .map(|c| {
+                let glyph_id = face.glyph_index(c).unwrap_or(GlyphId(0));
+                let advance = face.glyph_hor_advance(glyph_id).unwrap_or(0);
+                advance as f32 * scale + self.letter_spacing
+            })
+            .sum()
+    }
+}
+
+/// Formats a length without the rounding noise of `f32`, e.g. `52.8` instead of `52.800003`.
+pub fn format_length(length: f32) -> String {
+    ((length * 1000.0).round() / 1000.0).to_string()
+}
+
+/// Parses an SVG length such as `12`, `12px` or `0.1em`, where `em` is relative to `font_size`.
+fn parse_length(value: &str, font_size: f32) -> Option<f32> {
+    let value = value.trim();
+
+    if let Some(em) = value.strip_suffix("em") {
+        return em.trim().parse::<f32>().ok().map(|x| x * font_size);
+    }
+
+    value.trim_end_matches("px").trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use maplit::hashmap;
+    use pretty_assertions::assert_eq;
+
+    use super::{format_length, parse_length, TextStyle};
+
+    #[test]
+    fn test_parse_length() {
+        assert_eq!(parse_length("12", 10.0), Some(12.0));
+        assert_eq!(parse_length("12px", 10.0), Some(12.0));
+        assert_eq!(parse_length("0.5em", 10.0), Some(5.0));
+        assert_eq!(parse_length("wide", 10.0), None);
+    }
+
+    #[test]
+    fn test_format_length() {
+        assert_eq!(format_length(24.0 + 28.8), "52.8");
+        assert_eq!(format_length(12.0), "12");
+    }
+
+    #[test]
+    fn test_text_style_from_attr() {
+        let attr = hashmap! {
+            "font-family".to_string() => "Dosis".to_string(),
+            "font-size".to_string() => "11".to_string(),
+            "font-weight".to_string() => "bold".to_string(),
+            "letter-spacing".to_string() => "0.1em".to_string(),
+        };
+
+        let text_style = TextStyle::from_attr(&attr);
+
+        assert_eq!(text_style.font_family, "Dosis");
+        assert_eq!(text_style.font_size, 11.0);
+        assert_eq!(text_style.font_weight, 700);
+        assert!(!text_style.italic);
+        assert!((text_style.letter_spacing - 1.1).abs() < 1e-6);
+        assert!((text_style.line_height - 13.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_measure_longer_text_is_wider() {
+        let text_style = TextStyle::from_attr(&hashmap! {
+            "font-family".to_string() => "Dosis".to_string(),
+            "font-size".to_string() => "12".to_string(),
+        });
+
+        let (short, long) = text_style
+            .with_measure(|measure| (measure("Luck"), measure("Luck and love")))
+            .unwrap();
+
+        assert!(short > 0.0);
+        assert!(long > short);
+    }
+}
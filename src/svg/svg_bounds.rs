@@ -0,0 +1,174 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use svg2pdf::usvg::Tree;
+
+use crate::svg::font_metrics::usvg_options;
+
+/// The templates are drawn in points, e.g. 595 × 842 for A4.
+pub const UNITS_PER_MM: f32 = 72.0 / 25.4;
+
+/// A rectangle on the page, in the user units of the SVG.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Bounds {
+    pub fn right(&self) -> f32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.y + self.height
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    /// Returns whether the other rectangle lies within this one, give or take `tolerance`.
+    pub fn contains(&self, other: &Bounds, tolerance: f32) -> bool {
+        other.x >= self.x - tolerance
+            && other.y >= self.y - tolerance
+            && other.right() <= self.right() + tolerance
+            && other.bottom() <= self.bottom() + tolerance
+    }
+
+    /// Returns the smallest rectangle that holds both rectangles.
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        Bounds {
+            x,
+            y,
+            width: self.right().max(other.right()) - x,
+            height: self.bottom().max(other.bottom()) - y,
+        }
+    }
+}
+
+/// The size of the page and the bounds of its elements, as they are drawn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageBounds<T> {
+    pub width: f32,
+    pub height: f32,
+    /// The measured elements with their bounds, in the order they were asked for. Elements that
+    /// draw nothing are left out.
+    pub elems: Vec<(T, Bounds)>,
+}
+
+/// Lays out the SVG data of the file at `svg_path` and measures the elements by their id,
+/// including their text. Every element is paired with a value to tell them apart.
+pub fn measure<T>(
+    svg_data: &[u8],
+    svg_path: &Path,
+    elems: Vec<(String, T)>,
+) -> Result<PageBounds<T>> {
+    let tree = Tree::from_data(svg_data, &usvg_options(svg_path))
+        .with_context(|| format!("Could not lay out the SVG file at path {:?}", svg_path))?;
+
+    let elems = elems
+        .into_iter()
+        .filter_map(|(id, value)| {
+            let rect = tree.node_by_id(&id)?.abs_bounding_box();
+            if rect.width() == 0.0 && rect.height() == 0.0 {
+                return None;
+            }
+            let bounds = Bounds {
+                x: rect.x(),
+                y: rect.y(),
+                width: rect.width(),
+                height: rect.height(),
+            };
+            Some((value, bounds))
+        })
+        .collect();
+
+    Ok(PageBounds {
+        width: tree.size().width(),
+        height: tree.size().height(),
+        elems,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use pretty_assertions::assert_eq;
+
+    use super::{measure, Bounds};
+
+    const SVG_EXAMPLE: &str = r#"
+        <svg width="200" height="100" viewBox="0 0 400 200" xmlns="http://www.w3.org/2000/svg">
+            <g id="left"><rect x="0" y="0" width="100" height="50"/></g>
+            <rect id="right" x="300" y="100" width="100" height="100"/>
+            <g id="empty"/>
+        </svg>"#;
+
+    #[test]
+    fn test_measure() {
+        let ids = ["left", "right", "empty"].map(|id| (id.to_string(), id));
+        let page_bounds = measure(
+            SVG_EXAMPLE.as_bytes(),
+            Path::new("example.svg"),
+            ids.to_vec(),
+        )
+        .unwrap();
+
+        assert_eq!((page_bounds.width, page_bounds.height), (200.0, 100.0));
+        assert_eq!(
+            page_bounds.elems,
+            vec![
+                (
+                    "left",
+                    Bounds {
+                        x: 0.0,
+                        y: 0.0,
+                        width: 50.0,
+                        height: 25.0
+                    }
+                ),
+                (
+                    "right",
+                    Bounds {
+                        x: 150.0,
+                        y: 50.0,
+                        width: 50.0,
+                        height: 50.0
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_union() {
+        let a = Bounds {
+            x: 0.0,
+            y: 10.0,
+            width: 10.0,
+            height: 10.0,
+        };
+        let b = Bounds {
+            x: 5.0,
+            y: 0.0,
+            width: 10.0,
+            height: 5.0,
+        };
+
+        assert_eq!(
+            a.union(&b),
+            Bounds {
+                x: 0.0,
+                y: 0.0,
+                width: 15.0,
+                height: 20.0
+            }
+        );
+        assert_eq!(a.center(), (5.0, 15.0));
+    }
+}
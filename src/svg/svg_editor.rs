@@ -1,7 +1,8 @@
 use self::{text_elem::TextElem, xml_elem_utils::ElemUtils, xml_tree::XmlTree};
+use super::svg_bounds::{measure, PageBounds};
 
-use anyhow::{anyhow, Result};
-use std::path::Path;
+use anyhow::{anyhow, bail, Result};
+use std::{collections::HashMap, io::Write, path::Path};
 use xmltree::Element;
 
 pub mod text_elem;
@@ -17,7 +18,10 @@ pub struct SvgEditor {
 
 impl SvgEditor {
     pub fn open<P: AsRef<Path>>(svg_file_path: P) -> Result<SvgEditor> {
-        let xml_tree = XmlTree::open(svg_file_path)?;
+        Self::from_xml_tree(XmlTree::open(svg_file_path)?)
+    }
+
+    fn from_xml_tree(xml_tree: XmlTree) -> Result<SvgEditor> {
         let elem_keys = xml_tree.get_elems_with_tag("text");
 
         let text_elems_ordered = elem_keys
@@ -35,6 +39,125 @@ impl SvgEditor {
         self.text_elems_ordered.iter().collect()
     }
 
+    /// Returns the text elements, in document order, paired with the value of `attr_name` on
+    /// the text element itself or its nearest ancestor group.
+    pub fn get_text_elems_with_inherited_attr(
+        &self,
+        attr_name: &str,
+    ) -> Result<Vec<(&TextElem, Option<&str>)>> {
+        self.xml_tree
+            .get_elems_with_tag_and_inherited_attr("text", attr_name)
+            .into_iter()
+            .map(|(xml_elem, value)| {
+                let elem_id = xml_elem.attributes.get("id").map_or("", String::as_str);
+                Ok((self.get_elem_with_id(elem_id)?, value))
+            })
+            .collect()
+    }
+
+    /// Returns the id and the value of `marker_name` of every element with that attribute, in
+    /// document order, paired with the value of `attr_name` on the element or its nearest
+    /// ancestor.
+    pub fn get_marked_elems_with_inherited_attr(
+        &self,
+        marker_name: &str,
+        attr_name: &str,
+    ) -> Result<Vec<(&str, &str, Option<&str>)>> {
+        self.xml_tree
+            .get_elems_with_attr_and_inherited_attr(marker_name, attr_name)
+            .into_iter()
+            .map(|(xml_elem, value)| {
+                let elem_id = xml_elem.attributes.get("id").ok_or(anyhow!(
+                    "SvgEditor: An element with {marker_name} needs an id."
+                ))?;
+                Ok((
+                    elem_id.as_str(),
+                    xml_elem.attributes[marker_name].as_str(),
+                    value,
+                ))
+            })
+            .collect()
+    }
+
+    /// Returns the id of every element with an attribute value that contains `pattern`, in
+    /// document order, paired with the value of `attr_name` on the element or its nearest
+    /// ancestor.
+    pub fn get_elems_with_attr_containing(
+        &self,
+        pattern: &str,
+        attr_name: &str,
+    ) -> Result<Vec<(&str, Option<&str>)>> {
+        let matches = |xml_elem: &Element| {
+            xml_elem
+                .attributes
+                .values()
+                .any(|value| value.contains(pattern))
+        };
+
+        self.xml_tree
+            .get_matching_elems_and_inherited_attr(&matches, attr_name)
+            .into_iter()
+            .map(|(xml_elem, value)| {
+                let elem_id = xml_elem.attributes.get("id").ok_or(anyhow!(
+                    "SvgEditor: An element with {pattern} in an attribute needs an id."
+                ))?;
+                Ok((elem_id.as_str(), value))
+            })
+            .collect()
+    }
+
+    /// Returns all attributes of any element in the SVG.
+    pub fn get_attrs_of_elem(&self, elem_id: &str) -> Option<&HashMap<String, String>> {
+        self.xml_tree
+            .get_elem_with_id(elem_id)
+            .map(|elem| &elem.attributes)
+    }
+
+    /// Sets an attribute of any element in the SVG, or removes it when `value` is `None`.
+    pub fn set_attr_of_elem(
+        &mut self,
+        elem_id: &str,
+        attr_name: &str,
+        value: Option<&str>,
+    ) -> Result<()> {
+        if let Ok(text_elem) = self.get_elem_with_id_mut(elem_id) {
+            match value {
+                Some(value) => text_elem
+                    .attr
+                    .insert(attr_name.to_string(), value.to_string()),
+                None => text_elem.attr.remove(attr_name),
+            };
+        }
+
+        self.xml_tree.set_attr_of_elem(elem_id, attr_name, value)
+    }
+
+    /// Replaces any element other than a text element by a new element with the same id, e.g. a
+    /// placeholder `<rect>` by a `<path>`.
+    pub fn replace_elem(
+        &mut self,
+        elem_id: &str,
+        tag: &str,
+        mut attrs: HashMap<String, String>,
+    ) -> Result<()> {
+        if self.get_elem_with_id(elem_id).is_ok() {
+            bail!("SvgEditor: The text element '{elem_id}' can't be replaced.");
+        }
+
+        let mut new_xml_elem = Element::new(tag);
+        attrs.insert("id".to_string(), elem_id.to_string());
+        new_xml_elem.attributes = attrs;
+
+        self.xml_tree.replace_elem_by_id(new_xml_elem)
+    }
+
+    /// Adds a new element on top of the page, e.g. a `<path>` with print marks.
+    pub fn append_elem(&mut self, tag: &str, attrs: HashMap<String, String>) {
+        let mut new_xml_elem = Element::new(tag);
+        new_xml_elem.attributes = attrs;
+        self.xml_tree.append_to_root(new_xml_elem);
+    }
+
     pub fn update_text_elem_by_id(&mut self, new_text_elem: TextElem) -> Result<()> {
         let text_elem = self.get_elem_with_id_mut(&new_text_elem.id)?;
         *text_elem = new_text_elem;
@@ -54,6 +177,14 @@ impl SvgEditor {
             ))
     }
 
+    /// Returns an attribute of any element in the SVG, e.g. the `width` of a `<rect>`.
+    pub fn get_attr_of_elem(&self, elem_id: &str, attr_name: &str) -> Option<&str> {
+        self.xml_tree
+            .get_elem_with_id(elem_id)
+            .and_then(|elem| elem.attributes.get(attr_name))
+            .map(String::as_str)
+    }
+
     fn get_elem_with_id_mut(&mut self, text_elem_id: &str) -> Result<&mut TextElem> {
         self.text_elems_ordered
             .iter_mut()
@@ -90,6 +221,119 @@ impl SvgEditor {
         self.xml_tree.save(svg_path)?;
         Ok(())
     }
+
+    /// Returns the size of the page and the bounds of every element with the attribute, paired
+    /// with the value of the attribute, in document order.
+    ///
+    /// `svg_path` is where the SVG is read from, to find its images.
+    pub fn get_bounds_of_elems_with_attr(
+        &self,
+        attr_name: &str,
+        svg_path: &Path,
+    ) -> Result<PageBounds<String>> {
+        // Elements without an id cannot be found after the layout, so they get a temporary one.
+        let mut xml_tree = self.xml_tree.clone();
+        let elems = xml_tree.assign_ids_to_elems_with_attr(attr_name, "__measured_");
+
+        let mut svg_data = vec![];
+        xml_tree.write(&mut svg_data)?;
+        measure(&svg_data, svg_path, elems)
+    }
+
+    /// Returns the bounds of the elements with the ids, as they are drawn.
+    ///
+    /// `svg_path` is where the SVG is read from, to find its images.
+    pub fn get_bounds_of_elems(
+        &self,
+        elem_ids: &[&str],
+        svg_path: &Path,
+    ) -> Result<PageBounds<String>> {
+        let mut svg_data = vec![];
+        self.xml_tree.write(&mut svg_data)?;
+        let elems = elem_ids
+            .iter()
+            .map(|id| (id.to_string(), id.to_string()))
+            .collect();
+        measure(&svg_data, svg_path, elems)
+    }
+
+    /// Returns the size of the page, as it is drawn.
+    ///
+    /// `svg_path` is where the SVG is read from, to find its images.
+    pub fn get_page_size(&self, svg_path: &Path) -> Result<(f32, f32)> {
+        let mut svg_data = vec![];
+        self.xml_tree.write(&mut svg_data)?;
+        let page_bounds = measure::<()>(&svg_data, svg_path, vec![])?;
+        Ok((page_bounds.width, page_bounds.height))
+    }
+
+    /// Checks that a unit of the SVG is a point, e.g. `width="595"` for A4, as millimetres are
+    /// converted to units with [`UNITS_PER_MM`](super::svg_bounds::UNITS_PER_MM).
+    ///
+    /// The page can't have a size in another unit, e.g. `width="210mm"`, as it would be laid out
+    /// at 96 dpi, or a `viewBox` of another size, as the units inside it would be scaled.
+    pub fn check_units_are_points(&self, svg_path: &Path) -> Result<()> {
+        let mut page_size = [0.0; 2];
+        for (length, name) in page_size.iter_mut().zip(["width", "height"]) {
+            let Some(value) = self.xml_tree.get_root_attr(name) else {
+                continue;
+            };
+            *length = value.trim().trim_end_matches("px").parse().map_err(|_| {
+                anyhow!(
+                    "The {name} of '{}' is {value}, but its units are taken as points. Give \
+                         the {name} in points without a unit, e.g. width=\"595\" for A4.",
+                    svg_path.display()
+                )
+            })?;
+        }
+
+        if let Some(view_box) = self.xml_tree.get_root_attr("viewBox") {
+            let view_box_size = view_box
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|number| !number.is_empty())
+                .skip(2)
+                .map(str::parse::<f32>)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap_or_default();
+            let scaled = view_box_size
+                .iter()
+                .zip(page_size)
+                .any(|(view_box_length, length)| {
+                    length > 0.0 && (view_box_length - length).abs() > 0.01
+                });
+            if scaled {
+                bail!(
+                    "The viewBox of '{}' is {view_box}, which scales its units to the width and \
+                     height of the page. Give the viewBox the same size as the page, so its \
+                     units are points.",
+                    svg_path.display()
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns a page of `page_size` with a copy of the SVG, of `copy_size`, at every position,
+    /// in a group with `group_attr="{idx}"`, like [`XmlTree::impose`].
+    pub fn impose(
+        &self,
+        page_size: (f32, f32),
+        copy_size: (f32, f32),
+        positions: &[(f32, f32)],
+        group_attr: &str,
+        id_ref_attrs: &[&str],
+    ) -> Result<SvgEditor> {
+        let xml_tree =
+            self.xml_tree
+                .impose(page_size, copy_size, positions, group_attr, id_ref_attrs);
+        Self::from_xml_tree(xml_tree)
+    }
+
+    /// Writes the SVG to the writer, e.g. to send it without saving it to a file.
+    pub fn write_svg(&self, writer: impl Write) -> Result<()> {
+        self.xml_tree.write(writer)
+    }
 }
 
 #[cfg(test)]
@@ -160,6 +404,68 @@ mod tests {
         assert_eq!(new_text_elem.attr["fill"], "red");
     }
 
+    #[rstest]
+    fn test_save_to_svg_with_multiple_lines(mut svg_editor: SvgEditor) {
+        let mut text_elem = svg_editor.get_text_elems_ordered()[0].clone();
+        text_elem.text = "Mine\nturtle!".to_string();
+        svg_editor.update_text_elem_by_id(text_elem).unwrap();
+
+        let (_dir, path) = create_file_and_save(&mut svg_editor);
+
+        let new_svg_editor = SvgEditor::open(path).unwrap();
+        assert_eq!(
+            new_svg_editor.get_text_elems_ordered()[0].text,
+            "Mine\nturtle!"
+        );
+        assert_eq!(new_svg_editor.get_attr_of_elem("tspan1", "y"), Some("24"));
+    }
+
+    #[test]
+    fn test_set_attr_of_elem() -> Result<()> {
+        let temp_file = test_utils::create_temp_file(
+            "marked.svg",
+            r#"<svg><g data-slip="1"><rect id="rect1" data-style="header" fill="black"/></g>
+               <text id="text1" data-style="accent"><tspan>Hi</tspan></text></svg>"#,
+        );
+        let mut svg_editor = SvgEditor::open(temp_file.path)?;
+
+        let marked = svg_editor.get_marked_elems_with_inherited_attr("data-style", "data-slip")?;
+        assert_eq!(
+            marked,
+            vec![("rect1", "header", Some("1")), ("text1", "accent", None)]
+        );
+
+        svg_editor.set_attr_of_elem("rect1", "fill", Some("red"))?;
+        svg_editor.set_attr_of_elem("text1", "fill", Some("gold"))?;
+        assert_eq!(svg_editor.get_attr_of_elem("rect1", "fill"), Some("red"));
+        assert_eq!(svg_editor.get_elem_with_id("text1")?.attr["fill"], "gold");
+
+        svg_editor.set_attr_of_elem("rect1", "fill", None)?;
+        assert_eq!(svg_editor.get_attr_of_elem("rect1", "fill"), None);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(r#"width="595" height="842""#, true)]
+    #[case(r#"width="595px" height="842" viewBox="0 0 595 842""#, true)]
+    #[case(r#"viewBox="0,0,100,50""#, true)]
+    #[case(r#"width="210mm" height="297mm""#, false)]
+    #[case(r#"width="595pt" height="842pt""#, false)]
+    #[case(r#"width="100%" height="100%""#, false)]
+    #[case(r#"width="595" height="842" viewBox="0 0 210 297""#, false)]
+    fn test_check_units_are_points(#[case] root_attrs: &str, #[case] is_ok: bool) -> Result<()> {
+        let temp_file = test_utils::create_temp_file("units.svg", &format!("<svg {root_attrs}/>"));
+        let svg_editor = SvgEditor::open(&temp_file.path)?;
+
+        assert_eq!(
+            svg_editor.check_units_are_points(&temp_file.path).is_ok(),
+            is_ok
+        );
+
+        Ok(())
+    }
+
     fn update_fill_to_red(svg_editor: &mut SvgEditor) {
         let text_elem = svg_editor.get_text_elems_ordered()[0];
         assert_eq!(text_elem.attr["fill"], "black");
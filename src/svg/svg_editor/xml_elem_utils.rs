@@ -1,37 +1,70 @@
 use anyhow::Result;
 use xmltree::{Element, XMLNode};
 
+use crate::svg::font_metrics::{format_length, TextStyle};
+
 pub trait ElemUtils {
     fn get_inner_text(&self) -> Result<String>;
     fn set_inner_text(&mut self, new_text: &str) -> Result<()>;
 }
 
 impl ElemUtils for Element {
+    /// Returns the text of the child elements (e.g. `tspan`), one line per child element.
     fn get_inner_text(&self) -> Result<String> {
-        let text = self
+        let lines = self
             .children
-            .first()
-            .and_then(|node| node.as_element())
-            .and_then(|node| node.get_text())
-            .ok_or(anyhow::anyhow!(
-                "No child element found with text for element {:?}.",
-                self
-            ))?;
-
-        Ok(text.to_string())
+            .iter()
+            .filter_map(|node| node.as_element())
+            .map(|node| node.get_text().unwrap_or_default().to_string())
+            .collect::<Vec<_>>();
+
+        if lines.is_empty() {
+            anyhow::bail!("No child element found with text for element {:?}.", self);
+        }
+
+        Ok(lines.join("\n"))
     }
 
+    /// Replaces the text of the child elements, with one child element per line.
+    ///
+    /// Every line is a copy of the first child element, moved down by the line height. The line
+    /// height is `data-line-height` on the element, or otherwise relative to the font size.
     fn set_inner_text(&mut self, new_text: &str) -> Result<()> {
         let first_child = self
             .children
-            .first_mut()
-            .and_then(|node| node.as_mut_element())
-            .ok_or(anyhow::anyhow!("No child element found."))?;
+            .iter()
+            .find_map(|node| node.as_element())
+            .ok_or(anyhow::anyhow!("No child element found."))?
+            .clone();
 
-        first_child.children.clear();
-        first_child
-            .children
-            .push(XMLNode::Text(new_text.to_string()));
+        let line_height = TextStyle::from_attr(&self.attributes).line_height;
+        let first_y = first_child
+            .attributes
+            .get("y")
+            .and_then(|y| y.parse::<f32>().ok());
+
+        self.children = new_text
+            .split('\n')
+            .enumerate()
+            .map(|(idx, line)| {
+                let mut child = first_child.clone();
+                child.children = vec![XMLNode::Text(line.to_string())];
+
+                if idx > 0 {
+                    child.attributes.remove("id");
+                    match first_y {
+                        Some(y) => child
+                            .attributes
+                            .insert("y".to_string(), format_length(y + idx as f32 * line_height)),
+                        None => child
+                            .attributes
+                            .insert("dy".to_string(), format_length(line_height)),
+                    };
+                }
+
+                XMLNode::Element(child)
+            })
+            .collect();
 
         Ok(())
     }
@@ -58,4 +91,29 @@ mod tests {
         elem.set_inner_text("new text").unwrap();
         assert_eq!(elem.get_inner_text().unwrap(), "new text");
     }
+
+    #[test]
+    fn test_set_inner_text_multiple_lines() {
+        let mut elem = Element::parse(SVG_EXAMPLE.as_bytes()).unwrap();
+        elem.set_inner_text("first line\nsecond line").unwrap();
+
+        assert_eq!(elem.get_inner_text().unwrap(), "first line\nsecond line");
+
+        let second_line = elem.children[1].as_element().unwrap();
+        assert_eq!(second_line.attributes["x"], "12");
+        // The default line height is 1.2 times the font size of 24.
+        assert_eq!(second_line.attributes["y"], "52.8");
+    }
+
+    #[test]
+    fn test_set_inner_text_custom_line_height() {
+        let mut elem = Element::parse(SVG_EXAMPLE.as_bytes()).unwrap();
+        elem.attributes
+            .insert("data-line-height".to_string(), "30".to_string());
+        elem.set_inner_text("first line\nsecond line\nthird line")
+            .unwrap();
+
+        let third_line = elem.children[2].as_element().unwrap();
+        assert_eq!(third_line.attributes["y"], "84");
+    }
 }
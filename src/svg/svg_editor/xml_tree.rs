@@ -1,10 +1,11 @@
 use anyhow::{anyhow, Context, Result};
 use std::{
+    collections::{HashMap, HashSet},
     fs::File,
-    io::{BufReader, BufWriter},
+    io::{BufReader, BufWriter, Write},
     path::Path,
 };
-use xmltree::Element;
+use xmltree::{Element, XMLNode};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct XmlTree {
@@ -52,6 +53,73 @@ impl XmlTree {
         mut_elems
     }
 
+    /// Returns all elements whose tag matches given name, in document order.
+    ///
+    /// Each element is paired with the value of `attr_name` on the element itself or, when
+    /// missing, on its nearest ancestor. For example, `<g data-slip="1"><text/></g>` returns
+    /// the text element with `Some("1")` for `data-slip`.
+    pub fn get_elems_with_tag_and_inherited_attr<'a>(
+        &'a self,
+        name: &str,
+        attr_name: &str,
+    ) -> Vec<(&'a Element, Option<&'a str>)> {
+        let mut elems = vec![];
+        let matches = |element: &Element| element.name == name;
+        Self::collect_with_inherited_attr(&self.root, &matches, attr_name, None, &mut elems);
+        elems
+    }
+
+    /// Returns all elements that have an attribute `marker_name`, in document order.
+    ///
+    /// Each element is paired with the inherited value of `attr_name`, like in
+    /// [`XmlTree::get_elems_with_tag_and_inherited_attr`].
+    pub fn get_elems_with_attr_and_inherited_attr<'a>(
+        &'a self,
+        marker_name: &str,
+        attr_name: &str,
+    ) -> Vec<(&'a Element, Option<&'a str>)> {
+        let matches = |element: &Element| element.attributes.contains_key(marker_name);
+        self.get_matching_elems_and_inherited_attr(&matches, attr_name)
+    }
+
+    /// Returns all elements for which `matches` is true, in document order.
+    ///
+    /// Each element is paired with the inherited value of `attr_name`, like in
+    /// [`XmlTree::get_elems_with_tag_and_inherited_attr`].
+    pub fn get_matching_elems_and_inherited_attr<'a>(
+        &'a self,
+        matches: &dyn Fn(&Element) -> bool,
+        attr_name: &str,
+    ) -> Vec<(&'a Element, Option<&'a str>)> {
+        let mut elems = vec![];
+        Self::collect_with_inherited_attr(&self.root, matches, attr_name, None, &mut elems);
+        elems
+    }
+
+    fn collect_with_inherited_attr<'a>(
+        element: &'a Element,
+        matches: &dyn Fn(&Element) -> bool,
+        attr_name: &str,
+        inherited_value: Option<&'a str>,
+        elems: &mut Vec<(&'a Element, Option<&'a str>)>,
+    ) {
+        let value = element
+            .attributes
+            .get(attr_name)
+            .map(String::as_str)
+            .or(inherited_value);
+
+        if matches(element) {
+            elems.push((element, value));
+        }
+
+        element
+            .children
+            .iter()
+            .filter_map(|x| x.as_element())
+            .for_each(|x| Self::collect_with_inherited_attr(x, matches, attr_name, value, elems));
+    }
+
     pub fn get_elem_with_id<'a>(&'a self, elem_id: &str) -> Option<&'a Element> {
         Self::find_matching_elem(&self.root, elem_id)
     }
@@ -82,6 +150,25 @@ impl XmlTree {
         Ok(())
     }
 
+    /// Sets an attribute of the element with the given id, or removes it when `value` is `None`.
+    pub fn set_attr_of_elem(
+        &mut self,
+        elem_id: &str,
+        attr_name: &str,
+        value: Option<&str>,
+    ) -> Result<()> {
+        let found_elem = Self::find_matching_elem_mut(&mut self.root, elem_id)
+            .ok_or(anyhow!("Could not find element with id '{:?}'.", elem_id))?;
+
+        match value {
+            Some(value) => found_elem
+                .attributes
+                .insert(attr_name.to_string(), value.to_string()),
+            None => found_elem.attributes.remove(attr_name),
+        };
+        Ok(())
+    }
+
     fn find_matching_elem_mut<'a>(
         element: &'a mut Element,
         elem_id: &str,
@@ -99,11 +186,185 @@ impl XmlTree {
             .find_map(|x| Self::find_matching_elem_mut(x, elem_id))
     }
 
+    /// Gives every element with the attribute an id, `{id_prefix}{n}` when it has none yet.
+    ///
+    /// Returns the id and the value of the attribute of every element, in document order.
+    pub fn assign_ids_to_elems_with_attr(
+        &mut self,
+        attr_name: &str,
+        id_prefix: &str,
+    ) -> Vec<(String, String)> {
+        let mut elems = vec![];
+        Self::assign_ids(&mut self.root, attr_name, id_prefix, &mut elems);
+        elems
+    }
+
+    fn assign_ids(
+        element: &mut Element,
+        attr_name: &str,
+        id_prefix: &str,
+        elems: &mut Vec<(String, String)>,
+    ) {
+        if let Some(value) = element.attributes.get(attr_name).cloned() {
+            let id = element
+                .attributes
+                .entry("id".to_string())
+                .or_insert_with(|| format!("{id_prefix}{}", elems.len()))
+                .clone();
+            elems.push((id, value));
+        }
+
+        element
+            .children
+            .iter_mut()
+            .filter_map(|x| x.as_mut_element())
+            .for_each(|x| Self::assign_ids(x, attr_name, id_prefix, elems));
+    }
+
+    /// Returns the value of the attribute of the root, e.g. the `width` of the page.
+    pub fn get_root_attr(&self, attr_name: &str) -> Option<&str> {
+        self.root.attributes.get(attr_name).map(String::as_str)
+    }
+
+    /// Adds the element as the last child of the root, so it is drawn on top of everything.
+    pub fn append_to_root(&mut self, elem: Element) {
+        self.root.children.push(XMLNode::Element(elem));
+    }
+
+    /// Returns a page of `width` × `height` with a copy of the tree at the top left corner of
+    /// every position, e.g. to print a template with a single slip several times on a page.
+    ///
+    /// Every copy is drawn in a group with `group_attr="{idx}"`, with an empty frame of
+    /// `copy_size` so the group is at least as large as the copy. The ids of the copy get the
+    /// suffix `_{idx}` to stay unique. The references to them follow: `url(#id)`, `href="#id"`
+    /// and the attributes in `id_ref_attrs`, whose value is an id.
+    pub fn impose(
+        &self,
+        (width, height): (f32, f32),
+        (copy_width, copy_height): (f32, f32),
+        positions: &[(f32, f32)],
+        group_attr: &str,
+        id_ref_attrs: &[&str],
+    ) -> XmlTree {
+        let mut ids = HashSet::new();
+        Self::collect_ids(&self.root, &mut ids);
+
+        let mut root = self.root.clone();
+        root.children.clear();
+        root.attributes = HashMap::from([
+            ("width".to_string(), width.to_string()),
+            ("height".to_string(), height.to_string()),
+            ("viewBox".to_string(), format!("0 0 {width} {height}")),
+        ]);
+
+        for (idx, (x, y)) in positions.iter().enumerate() {
+            let mut copy = self.root.clone();
+            copy.attributes.remove("x");
+            copy.attributes.remove("y");
+            // Artwork that runs past the edge of the copy, such as bleed, is still drawn.
+            copy.attributes
+                .insert("overflow".to_string(), "visible".to_string());
+            Self::rename_ids(&mut copy, &ids, idx, group_attr, id_ref_attrs);
+
+            let mut frame = Element::new("rect");
+            frame.attributes = HashMap::from([
+                ("width".to_string(), copy_width.to_string()),
+                ("height".to_string(), copy_height.to_string()),
+                ("fill".to_string(), "none".to_string()),
+            ]);
+
+            let mut group = Element::new("g");
+            group.attributes = HashMap::from([
+                (group_attr.to_string(), idx.to_string()),
+                ("transform".to_string(), format!("translate({x} {y})")),
+            ]);
+            group.children.push(XMLNode::Element(frame));
+            group.children.push(XMLNode::Element(copy));
+            root.children.push(XMLNode::Element(group));
+        }
+
+        XmlTree { root }
+    }
+
+    fn collect_ids(element: &Element, ids: &mut HashSet<String>) {
+        if let Some(id) = element.attributes.get("id") {
+            ids.insert(id.clone());
+        }
+
+        element
+            .children
+            .iter()
+            .filter_map(|x| x.as_element())
+            .for_each(|x| Self::collect_ids(x, ids));
+    }
+
+    fn rename_ids(
+        element: &mut Element,
+        ids: &HashSet<String>,
+        idx: usize,
+        group_attr: &str,
+        id_ref_attrs: &[&str],
+    ) {
+        let rename = |id: &str| format!("{id}_{idx}");
+
+        for (name, value) in element.attributes.iter_mut() {
+            if name == group_attr {
+                *value = idx.to_string();
+            } else if name == "id" || (id_ref_attrs.contains(&name.as_str()) && ids.contains(value))
+            {
+                *value = rename(value);
+            } else if let Some(id) = value
+                .strip_prefix('#')
+                .filter(|id| name == "href" && ids.contains(*id))
+            {
+                *value = format!("#{}", rename(id));
+            } else if value.contains("url(#") {
+                *value = Self::rename_url_refs(value, ids, &rename);
+            }
+        }
+
+        element
+            .children
+            .iter_mut()
+            .filter_map(|x| x.as_mut_element())
+            .for_each(|x| Self::rename_ids(x, ids, idx, group_attr, id_ref_attrs));
+    }
+
+    /// Renames the ids in every `url(#id)` of the value, e.g. `fill: url(#gradient)`.
+    fn rename_url_refs(
+        value: &str,
+        ids: &HashSet<String>,
+        rename: &dyn Fn(&str) -> String,
+    ) -> String {
+        let mut renamed = String::new();
+        let mut rest = value;
+
+        while let Some((before, after)) = rest.split_once("url(#") {
+            let Some((id, after_id)) = after.split_once(')') else {
+                break;
+            };
+            renamed.push_str(before);
+            renamed.push_str("url(#");
+            renamed.push_str(&if ids.contains(id) {
+                rename(id)
+            } else {
+                id.to_string()
+            });
+            rest = after_id;
+            renamed.push(')');
+        }
+
+        renamed.push_str(rest);
+        renamed
+    }
+
     pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let file = File::create(path)?;
-        let mut writer = BufWriter::new(file);
-        self.root.write(&mut writer)?;
+        self.write(BufWriter::new(file))
+    }
 
+    pub fn write(&self, writer: impl Write) -> Result<()> {
+        self.root.write(writer)?;
         Ok(())
     }
 }
@@ -144,6 +405,33 @@ mod tests {
         assert_eq!(tspan_elem.get_text().unwrap(), "Mine turtle!");
     }
 
+    #[rstest]
+    fn test_get_elems_with_inherited_attr() {
+        let temp_file = test_utils::create_temp_file(
+            "temp.svg",
+            r#"
+            <svg>
+                <g data-slip="0">
+                    <text id="a"><tspan>A</tspan></text>
+                    <g><text id="b" data-slip="1"><tspan>B</tspan></text></g>
+                </g>
+                <text id="c"><tspan>C</tspan></text>
+            </svg>"#,
+        );
+        let xml_tree = XmlTree::open(&temp_file.path).unwrap();
+
+        let elems = xml_tree.get_elems_with_tag_and_inherited_attr("text", "data-slip");
+        let ids_and_values = elems
+            .iter()
+            .map(|(elem, value)| (elem.attributes["id"].as_str(), *value))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            ids_and_values,
+            vec![("a", Some("0")), ("b", Some("1")), ("c", None)]
+        );
+    }
+
     #[rstest]
     fn test_nested_tag_expect_inner_text(xml_tree: XmlTree) {
         let text_elems = xml_tree.get_elems_with_tag("text");
@@ -5,7 +5,7 @@ use std::{
 
 use anyhow::{bail, Result};
 
-use super::svg_to_pdf;
+use crate::{renderer::Renderer, svg::svg_to_png::svg_to_png};
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct SvgFile {
@@ -22,15 +22,22 @@ impl SvgFile {
         Ok(Self { path })
     }
 
-    pub fn to_pdf(&self, pdf_path: impl AsRef<Path>) -> Result<PathBuf> {
-        svg_to_pdf::svg_to_pdf(&self.path, pdf_path.as_ref())?;
+    pub fn to_pdf(&self, pdf_path: impl AsRef<Path>, renderer: &dyn Renderer) -> Result<PathBuf> {
+        renderer.render(&self.path, pdf_path.as_ref())?;
 
-        return Ok(pdf_path.as_ref().to_path_buf());
+        Ok(pdf_path.as_ref().to_path_buf())
     }
 
-    pub fn to_pdf_same_name(&self) -> Result<PathBuf> {
+    pub fn to_pdf_same_name(&self, renderer: &dyn Renderer) -> Result<PathBuf> {
         let pdf_path = self.path.with_extension("pdf");
-        self.to_pdf(pdf_path)
+        self.to_pdf(pdf_path, renderer)
+    }
+
+    /// Renders the SVG to a PNG file at the resolution in dots per inch.
+    pub fn to_png(&self, png_path: impl AsRef<Path>, dpi: f32) -> Result<PathBuf> {
+        svg_to_png(&self.path, png_path.as_ref(), dpi)?;
+
+        Ok(png_path.as_ref().to_path_buf())
     }
 }
 
@@ -58,8 +65,14 @@ mod tests {
 
     use pretty_assertions::assert_eq;
 
+    use crate::renderer::NativeRenderer;
+
     use super::*;
 
+    const SVG_EXAMPLE: &str = "<svg height='100' width='100' xmlns='http://www.w3.org/2000/svg'>
+                                 <circle cx='50' cy='50' r='40' />
+                               </svg>";
+
     #[test]
     fn test_svg_file_new_with_valid_svg() {
         let temp_file = create_temp_file("test.svg", "test");
@@ -84,18 +97,18 @@ mod tests {
 
     #[test]
     fn test_svg_file_to_pdf() {
-        let temp_file = create_temp_file("test.svg", "test");
+        let temp_file = create_temp_file("test.svg", SVG_EXAMPLE);
         let pdf_path = temp_file.dir.path().join("hello.pdf");
         let svg_file = SvgFile::new(&temp_file.path).unwrap();
-        let result = svg_file.to_pdf(&pdf_path);
+        let result = svg_file.to_pdf(&pdf_path, &NativeRenderer);
         assert_eq!(result.unwrap(), pdf_path);
     }
 
     #[test]
     fn test_svg_file_to_pdf_same_name() {
-        let temp_file = create_temp_file("test.svg", "test");
+        let temp_file = create_temp_file("test.svg", SVG_EXAMPLE);
         let svg_file = SvgFile::new(&temp_file.path).unwrap();
-        let result = svg_file.to_pdf_same_name();
+        let result = svg_file.to_pdf_same_name(&NativeRenderer);
 
         let mut pdf_path = temp_file.path.clone();
         pdf_path.set_extension("pdf");
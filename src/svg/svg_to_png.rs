@@ -0,0 +1,85 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use resvg::tiny_skia::{Pixmap, Transform};
+use svg2pdf::usvg::Tree;
+
+use crate::svg::font_metrics::usvg_options;
+
+/// The resolution of the user units of an SVG file, e.g. `width="100"`.
+const SVG_DPI: f32 = 96.0;
+
+/// Renders the SVG file to a PNG file at the resolution in dots per inch.
+pub fn svg_to_png(
+    path_to_svg: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    dpi: f32,
+) -> Result<()> {
+    let path_to_svg = path_to_svg.as_ref();
+    let output_path = output_path.as_ref();
+
+    debug!(
+        "Rendering {} to {} at {dpi} DPI",
+        path_to_svg.display(),
+        output_path.display()
+    );
+
+    let svg_data = fs::read(path_to_svg)
+        .with_context(|| format!("Could not read SVG file at path {:?}", path_to_svg))?;
+    let tree = Tree::from_data(&svg_data, &usvg_options(path_to_svg))
+        .with_context(|| format!("Could not parse SVG file at path {:?}", path_to_svg))?;
+
+    let scale = dpi / SVG_DPI;
+    let size = tree
+        .size()
+        .to_int_size()
+        .scale_by(scale)
+        .ok_or(anyhow!("Cannot render {:?} at {dpi} DPI", path_to_svg))?;
+    let mut pixmap = Pixmap::new(size.width(), size.height())
+        .ok_or(anyhow!("Cannot render {:?} at {dpi} DPI", path_to_svg))?;
+
+    resvg::render(
+        &tree,
+        Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    pixmap
+        .save_png(output_path)
+        .with_context(|| format!("Cannot save to {:?}", output_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::svg_to_png;
+
+    const SVG_EXAMPLE: &str = "<svg height='100' width='50' xmlns='http://www.w3.org/2000/svg'>
+                                 <circle cx='25' cy='50' r='20' />
+                               </svg>";
+
+    #[rstest]
+    #[case(96.0, (50, 100))]
+    #[case(192.0, (100, 200))]
+    fn test_svg_to_png(#[case] dpi: f32, #[case] expected_size: (u32, u32)) {
+        let temp_file = test_utils::create_temp_file("temp.svg", SVG_EXAMPLE);
+        let png_path = temp_file.dir.path().join("temp.png");
+
+        svg_to_png(&temp_file.path, &png_path, dpi).unwrap();
+
+        let pixmap = resvg::tiny_skia::Pixmap::load_png(&png_path).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), expected_size);
+    }
+
+    #[test]
+    fn test_svg_to_png_with_invalid_svg_expect_error() {
+        let temp_file = test_utils::create_temp_file("temp.svg", "not an svg");
+        let png_path = temp_file.dir.path().join("temp.png");
+
+        assert!(svg_to_png(&temp_file.path, &png_path, 96.0).is_err());
+        assert!(!png_path.exists());
+    }
+}
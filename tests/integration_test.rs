@@ -91,6 +91,201 @@ fn test_cli_verbose(mut cmd: Command) {
     cleanup();
 }
 
+#[rstest]
+fn test_cli_with_seed(mut cmd: Command) {
+    cmd.arg("--seed")
+        .arg("42")
+        .arg("--config")
+        .arg("test_utils/data/fortune_settings.yaml")
+        .arg("--output")
+        .arg("seed_output.pdf");
+
+    let assert = cmd.assert().success();
+
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("Using seed 42"));
+
+    fs::remove_file("seed_output.pdf").unwrap();
+}
+
+#[rstest]
+fn test_cli_with_invalid_var_should_fail(mut cmd: Command) {
+    cmd.arg("--var")
+        .arg("event_name")
+        .arg("--config")
+        .arg("test_utils/data/fortune_settings.yaml");
+
+    let assert = cmd.assert().failure();
+
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+    assert!(stderr.contains("expected KEY=VALUE"));
+}
+
+#[rstest]
+fn test_cli_with_manifest(mut cmd: Command) {
+    cmd.arg("--seed")
+        .arg("42")
+        .arg("--config")
+        .arg("test_utils/data/fortune_settings.yaml")
+        .arg("--output")
+        .arg("manifest_output.pdf")
+        .arg("--manifest")
+        .arg("manifest_output.csv");
+
+    cmd.assert().success();
+
+    let manifest = fs::read_to_string("manifest_output.csv").unwrap();
+    let mut lines = manifest.lines();
+    assert_eq!(
+        lines.next(),
+        Some("page,slot,serial,luck_level,header,seed,general,health,love")
+    );
+    // Fortunes with a line break span multiple lines, so only check the slip of the last page.
+    assert!(lines.any(|line| line.starts_with("2,1,5,")));
+
+    fs::remove_file("manifest_output.pdf").unwrap();
+    fs::remove_file("manifest_output.csv").unwrap();
+}
+
+#[rstest]
+fn test_cli_png_per_slip(mut cmd: Command) {
+    cmd.arg("--config")
+        .arg("test_utils/data/fortune_settings.yaml")
+        .arg("--output")
+        .arg("png_output.pdf")
+        .arg("--format")
+        .arg("png")
+        .arg("--per-slip")
+        .arg("--dpi")
+        .arg("10")
+        .arg("--manifest")
+        .arg("png_output.csv");
+
+    cmd.assert().success();
+
+    // The `.pdf` extension is dropped for the output directory.
+    let dir = Path::new("png_output");
+    assert_eq!(fs::read_dir(dir).unwrap().count(), 5);
+    assert!(dir.join("slip_1.png").exists());
+
+    // Every slip is a file of its own, numbered like a page.
+    let manifest = fs::read_to_string("png_output.csv").unwrap();
+    assert!(manifest.lines().any(|line| line.starts_with("5,1,5,")));
+
+    fs::remove_dir_all(dir).unwrap();
+    fs::remove_file("png_output.csv").unwrap();
+}
+
+#[rstest]
+#[case("--dpi", "0")]
+#[case("--dpi", "-72")]
+#[case("--dpi", "NaN")]
+#[case("--dpi", "inf")]
+#[case("--bleed", "-3")]
+#[case("--bleed", "NaN")]
+#[case("--bleed", "inf")]
+fn test_cli_with_invalid_number_should_fail(
+    mut cmd: Command,
+    #[case] option: &str,
+    #[case] value: &str,
+) {
+    cmd.arg("--config")
+        .arg("test_utils/data/fortune_settings.yaml")
+        .arg(format!("{option}={value}"));
+
+    let assert = cmd.assert().failure();
+
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+    assert!(stderr.contains(option));
+}
+
+#[rstest]
+fn test_cli_reprint() {
+    cmd()
+        .arg("--config")
+        .arg("test_utils/data/fortune_settings.yaml")
+        .arg("--output")
+        .arg("reprint_original.pdf")
+        .arg("--manifest")
+        .arg("reprint_original.json")
+        .assert()
+        .success();
+
+    let assert = cmd()
+        .arg("--config")
+        .arg("test_utils/data/fortune_settings.yaml")
+        .arg("--output")
+        .arg("reprint_output.pdf")
+        .arg("reprint")
+        .arg("reprint_original.json")
+        .arg("2")
+        .arg("2/1")
+        .assert()
+        .success();
+
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("Reprinting 2 fortunes"));
+    assert!(Path::new("reprint_output.pdf").exists());
+
+    cmd()
+        .arg("--config")
+        .arg("test_utils/data/fortune_settings.yaml")
+        .arg("--output")
+        .arg("reprint_output.pdf")
+        .arg("reprint")
+        .arg("reprint_original.json")
+        .arg("9/9")
+        .assert()
+        .failure();
+
+    fs::remove_file("reprint_original.pdf").unwrap();
+    fs::remove_file("reprint_original.json").unwrap();
+    fs::remove_file("reprint_output.pdf").unwrap();
+}
+
+#[rstest]
+fn test_cli_draw_json(mut cmd: Command) {
+    cmd.arg("--config")
+        .arg("test_utils/data/fortune_settings.yaml")
+        .arg("draw")
+        .arg("--luck")
+        .arg("bad luck")
+        .arg("--json");
+
+    let assert = cmd.assert().success();
+
+    // Only the slip is printed, so the output can be parsed.
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.starts_with('{'));
+    assert!(stdout.contains(r#""luck_level": "Bad Luck""#));
+}
+
+#[rstest]
+fn test_cli_draw_with_unknown_luck_should_fail(mut cmd: Command) {
+    cmd.arg("--config")
+        .arg("test_utils/data/fortune_settings.yaml")
+        .arg("draw")
+        .arg("--luck")
+        .arg("great_luck");
+
+    let assert = cmd.assert().failure().code(5);
+
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("Allowed: good_luck, bad_luck"));
+}
+
+#[rstest]
+fn test_cli_lint(mut cmd: Command) {
+    cmd.arg("--config")
+        .arg("test_utils/data/fortune_settings.yaml")
+        .arg("lint");
+
+    let assert = cmd.assert().success();
+
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("No issues found"));
+}
+
 fn assert_cmd_and_ok_x(cmd: &mut Command, ok: usize, x: usize) {
     let assert = match ok {
         0..=2 => cmd.assert().failure().code(EXIT_CODE_ERROR),